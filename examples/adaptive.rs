@@ -2,7 +2,7 @@ extern crate maths_traits;
 extern crate numerical_integration;
 
 use maths_traits::analysis::metric::InnerProductMetric;
-use numerical_integration::*;
+use numerical_integration::{AdaptiveIntegrator, DORMAND_PRINCE};
 
 fn main() {
     fn f(_t: f64, y: f64) -> ((), f64) {
@@ -0,0 +1,81 @@
+extern crate maths_traits;
+extern crate numerical_integration;
+
+use maths_traits::analysis::*;
+use numerical_integration::*;
+
+fn main() {
+    //
+    //Gradient of a cost on the Lotka-Volterra equations with respect to their parameters
+    //
+    //the discrete and continuous adjoints are compared to central differences of the cost
+    //itself, along with how many steps and checkpoints each schedule takes
+    //
+
+    fn lotka_volterra<T: Real>(_t: T, y: &SVector<T, 2>, p: &[T]) -> SVector<T, 2> {
+        let (x, z) = (y[0].clone(), y[1].clone());
+        SVector([
+            p[0].clone() * x.clone() - p[1].clone() * x.clone() * z.clone(),
+            p[2].clone() * x * z.clone() - p[3].clone() * z,
+        ])
+    }
+
+    //the squared prey population at the end, plus the integral of the predation
+    fn terminal<T: Real>(y: &SVector<T, 2>) -> T {
+        y[0].clone() * y[0].clone()
+    }
+    fn running<T: Real>(_t: T, y: &SVector<T, 2>, p: &[T]) -> T {
+        p[1].clone() * y[0].clone() * y[1].clone()
+    }
+
+    let params = vec![1.0, 0.5, 0.2, 0.6];
+    let y0 = SVector([2.0, 1.0]);
+    let cost = AutoDiffCost::new(terminal::<Dual<f64>>, running::<Dual<f64>>);
+    let adjoint = Adjoint::new(0.0, 5.0, 500);
+
+    let problem = |p: &[f64]| AutoDiffJacobians::new(lotka_volterra::<Dual<f64>>, p.to_vec());
+
+    let h = 1e-6;
+    let differences: Vec<f64> = (0..params.len())
+        .map(|i| {
+            let (mut forward, mut backward) = (params.clone(), params.clone());
+            forward[i] += h;
+            backward[i] -= h;
+            let forward = adjoint.gradient(&RK4, y0, &problem(&forward), &cost).cost;
+            let backward = adjoint.gradient(&RK4, y0, &problem(&backward), &cost).cost;
+            (forward - backward) / (2.0 * h)
+        })
+        .collect();
+
+    println!(
+        "{:>24} | {:>13} {:>13} {:>13} {:>13} | {:>5} {:>5}",
+        "", "dC/da", "dC/db", "dC/dc", "dC/dd", "steps", "kept"
+    );
+    for kind in [AdjointKind::Discrete, AdjointKind::Continuous] {
+        for checkpointing in [
+            Checkpointing::All,
+            Checkpointing::UniformIntervals(20),
+            Checkpointing::Binomial(8),
+        ] {
+            let gradient = adjoint
+                .clone()
+                .kind(kind)
+                .checkpointing(checkpointing)
+                .gradient(&RK4, y0, &problem(&params), &cost);
+            println!(
+                "{:>24} | {:13.8} {:13.8} {:13.8} {:13.8} | {:5} {:5}",
+                format!("{:?} {:?}", kind, checkpointing),
+                gradient.params[0],
+                gradient.params[1],
+                gradient.params[2],
+                gradient.params[3],
+                gradient.forward_steps,
+                gradient.max_checkpoints
+            );
+        }
+    }
+    println!(
+        "{:>24} | {:13.8} {:13.8} {:13.8} {:13.8} |",
+        "finite differences", differences[0], differences[1], differences[2], differences[3]
+    );
+}
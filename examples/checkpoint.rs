@@ -0,0 +1,60 @@
+extern crate maths_traits;
+extern crate numerical_integration;
+extern crate serde_json;
+
+use maths_traits::analysis::metric::InnerProductMetric;
+use numerical_integration::*;
+
+fn main() {
+    //
+    //Checkpointing a long adaptive run of the Lorenz system and resuming it
+    //
+    //the run is split in two, with the iterator saved to JSON and loaded back in between,
+    //and compared to an uninterrupted run of the same system
+    //
+
+    fn lorenz(_t: f64, y: SVector<f64, 3>) -> ((), SVector<f64, 3>) {
+        let [x, y, z] = y.0;
+        (
+            (),
+            SVector([10.0 * (y - x), x * (28.0 - z) - y, x * y - 8.0 / 3.0 * z]),
+        )
+    }
+
+    let y0 = SVector([1.0, 1.0, 1.0]);
+    let tolerance = 1e-9;
+
+    //the uninterrupted run
+    let whole: Vec<_> = DORMAND_PRINCE
+        .adaptive_iter(0.0, y0, tolerance, lorenz, InnerProductMetric)
+        .until(20.0)
+        .sample(1.0)
+        .collect();
+
+    //the first half, after which the iterator is saved
+    let mut first = DORMAND_PRINCE
+        .adaptive_iter(0.0, y0, tolerance, lorenz, InnerProductMetric)
+        .until(20.0)
+        .sample(1.0);
+    let mut split: Vec<_> = first.by_ref().take(10).collect();
+    let saved = serde_json::to_string(&first.checkpoint()).unwrap();
+    println!("checkpoint at t = {} ({} bytes)", first.time(), saved.len());
+
+    //and the second half, resumed from what was saved
+    let checkpoint: Checkpoint<f64, (f64, (), SVector<f64, 3>)> =
+        serde_json::from_str(&saved).unwrap();
+    let second = AdaptiveSteps::resume(DORMAND_PRINCE, checkpoint, lorenz, InnerProductMetric);
+    split.extend(second);
+
+    for ((t, _, a), (_, _, b)) in whole.iter().zip(split.iter()) {
+        println!(
+            "{:>5.1} | {:>14.9} {:>14.9} {:>14.9} | {}",
+            t,
+            a[0],
+            a[1],
+            a[2],
+            if a == b { "identical" } else { "different" }
+        );
+    }
+    assert_eq!(whole, split);
+}
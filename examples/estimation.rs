@@ -0,0 +1,71 @@
+extern crate maths_traits;
+extern crate numerical_integration;
+
+use maths_traits::analysis::metric::InnerProductMetric;
+use maths_traits::analysis::*;
+use numerical_integration::*;
+
+fn main() {
+    //
+    //Fitting the Lotka-Volterra equations to noisy observations of both populations
+    //
+    //the data comes from known parameters, and the fit starts from a wrong guess for those and
+    //for the initial number of predators
+    //
+
+    fn lotka_volterra<T: Real>(_t: T, y: &SVector<T, 2>, p: &[T]) -> SVector<T, 2> {
+        let (x, z) = (y[0].clone(), y[1].clone());
+        SVector([
+            p[0].clone() * x.clone() - p[1].clone() * x.clone() * z.clone(),
+            p[2].clone() * x * z.clone() - p[3].clone() * z,
+        ])
+    }
+
+    let truth = [1.0, 0.5, 0.2, 0.6];
+    let y0 = SVector([2.0, 1.0]);
+    let times: Vec<f64> = (1..=40).map(|i| f64::from(i) * 0.25).collect();
+
+    //the exact solution with some deterministic noise on top
+    let solution = Solver::new(0.0, 10.0).unwrap().t_eval(times.clone()).solve(
+        &RK4,
+        y0,
+        0.01,
+        |t: f64, y: SVector<f64, 2>| ((), lotka_volterra(t, &y, &truth)),
+    );
+    let values = solution
+        .states
+        .iter()
+        .enumerate()
+        .map(|(i, y)| {
+            let i = i as f64;
+            SVector([y[0] + 0.02 * (1.7 * i).sin(), y[1] + 0.02 * (2.3 * i).cos()])
+        })
+        .collect();
+    let data = Observations::new(times, values).sigma(vec![0.02, 0.02]);
+
+    let guess = vec![1.3, 0.4, 0.25, 0.5];
+    let problem = AutoDiffJacobians::new(lotka_volterra::<Dual<f64>>, guess);
+    let fit = LeastSquares::new(0.0)
+        .fit_initial(vec![1])
+        .fit_adaptive(
+            &DORMAND_PRINCE,
+            1e-8,
+            SensitivityMetric::new(InnerProductMetric, true),
+            problem,
+            SVector([2.0, 1.5]),
+            &data,
+        )
+        .unwrap();
+
+    println!(
+        "converged: {} after {} iterations, sum of squares {:.4}",
+        fit.converged, fit.iterations, fit.sum_of_squares
+    );
+    let errors = fit.standard_errors().unwrap();
+    let names = ["a", "b", "c", "d", "z0"];
+    let fitted = fit.params.iter().chain([&fit.initial[1]]);
+    let actual = truth.iter().chain([&y0[1]]);
+    for (((name, x), error), actual) in names.iter().zip(fitted).zip(&errors).zip(actual) {
+        println!("{:>3} = {:.5} ± {:.5} (actual {})", name, x, error, actual);
+    }
+}
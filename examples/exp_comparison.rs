@@ -1,7 +1,7 @@
 extern crate maths_traits;
 extern crate numerical_integration;
 
-use numerical_integration::{Integrator, EULER, RK4};
+use numerical_integration::{Solver, EULER, RK4};
 
 fn main() {
     //
@@ -16,10 +16,10 @@ fn main() {
     //the time-step
     let dt = 0.125;
 
-    //the initial time and values
-    let mut t = 0.0;
-    let mut y1 = EULER.init(1.0, dt, f);
-    let mut y2 = RK4.init(1.0, dt, f);
+    //integrate over 100 steps starting at 1
+    let solver = Solver::new(0.0, 100.0 * dt).unwrap();
+    let euler = solver.solve(&EULER, 1.0, dt, f);
+    let rk4 = solver.solve(&RK4, 1.0, dt, f);
 
     //table column lables
     for _ in 0..(9 + 11 * 3) {
@@ -28,16 +28,21 @@ fn main() {
     println!();
     println!("|      t|     Euler|       RK4|f64::exp()|");
 
-    for _ in 0..100 {
-        //compute the next step and print
+    //print each step, skipping the initial value
+    for ((t, y1), y2) in euler
+        .times
+        .iter()
+        .zip(&euler.states)
+        .zip(&rk4.states)
+        .skip(1)
+    {
         println!(
             "|{: >7.3}|{: >10.2}|{: >10.2}|{: >10.2}|",
-            t + dt,
-            EULER.step(t, y1.as_mut(), dt, f).1,
-            RK4.step(t, y2.as_mut(), dt, f).1,
-            (t + dt).exp()
+            t,
+            y1,
+            y2,
+            t.exp()
         );
-        t += dt;
     }
 
     for _ in 0..(9 + 11 * 3) {
@@ -0,0 +1,71 @@
+extern crate maths_traits;
+extern crate numerical_integration;
+
+use maths_traits::analysis::metric::InnerProductMetric;
+use maths_traits::analysis::*;
+use numerical_integration::*;
+
+fn main() {
+    //
+    //Sensitivities of logistic growth to its rate and carrying capacity
+    //
+    //the same right-hand side is differentiated by hand, by finite differences, and with dual
+    //numbers, and the sensitivities at the end are compared to those of the exact solution
+    //
+
+    fn logistic<T: Real>(_t: T, y: &T, p: &[T]) -> T {
+        p[0].clone() * y.clone() * (T::one() - y.clone() / p[1].clone())
+    }
+
+    fn exact(t: f64, p: &[f64]) -> (f64, [f64; 2]) {
+        let (y0, r, k) = (0.5, p[0], p[1]);
+        let e = (r * t).exp();
+        let den = k + y0 * (e - 1.0);
+        let y = k * y0 * e / den;
+        let dr = y * t * (k - y0) / den;
+        let dk = y / k - y / den;
+        (y, [dr, dk])
+    }
+
+    let params = vec![1.5, 4.0];
+    let t1 = 3.0;
+
+    fn run<P: ParamRhs<f64, f64>>(name: &str, t1: f64, problem: P) {
+        let y0 = SensitivityState::zeros(0.5, 2);
+        let metric = SensitivityMetric::new(InnerProductMetric, true);
+        let (_, _, y) = DORMAND_PRINCE
+            .adaptive_iter(0.0, y0, 1e-9, Sensitivity(problem), metric)
+            .until(t1)
+            .last()
+            .unwrap();
+        println!("{:>18} | {:.10} {:.10} {:.10}", name, y.y, y.s[0], y.s[1]);
+    }
+
+    println!("{:>18} | {:>12} {:>12} {:>12}", "", "y", "dy/dr", "dy/dK");
+
+    run(
+        "analytic",
+        t1,
+        AnalyticJacobians::new(
+            logistic::<f64>,
+            |_t, y: &f64, p: &[f64]| {
+                DenseJacobian::from_rows(vec![vec![p[0] * (1.0 - 2.0 * y / p[1])]])
+            },
+            |_t, y: &f64, p: &[f64]| vec![y * (1.0 - y / p[1]), p[0] * y * y / (p[1] * p[1])],
+            params.clone(),
+        ),
+    );
+    run(
+        "finite difference",
+        t1,
+        FiniteDifferenceJacobians::new(logistic::<f64>, params.clone()),
+    );
+    run(
+        "dual numbers",
+        t1,
+        AutoDiffJacobians::new(logistic::<Dual<f64>>, params.clone()),
+    );
+
+    let (y, [dr, dk]) = exact(t1, &params);
+    println!("{:>18} | {:.10} {:.10} {:.10}", "exact", y, dr, dk);
+}
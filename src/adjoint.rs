@@ -0,0 +1,880 @@
+use super::*;
+
+use maths_traits::algebra::*;
+
+//Adjoint sensitivity analysis
+//
+//Finds the gradient of a cost `φ(y(t1)) + ∫g(t, y, p) dt` with respect to every parameter
+//(and the initial state) by integrating the adjoint `λ = ∂cost/∂y` backward in time from
+//`λ(t1) = ∂φ/∂y`. Since the backward pass needs the forward states in reverse order, those are
+//kept at a few checkpoints and recomputed from them, following a `Checkpointing` schedule.
+//
+//Only one adjoint is integrated whatever the number of parameters, but each of its stages also
+//needs the product `λ ∂f/∂p`. That only costs the same whatever the number of parameters when
+//it is given directly, with `AnalyticJacobians::vjp_p()`. Otherwise, `AnalyticJacobians` builds
+//the derivative to each parameter, and the finite difference and dual number Jacobians take
+//one or two evaluations of the right-hand side per parameter, as forward sensitivities would.
+//
+//The discrete adjoint differentiates the Runge-Kutta steps themselves, so its gradient is
+//that of the computed solution (up to rounding). The continuous adjoint instead integrates
+//the adjoint equations `λ' = -λJ_y - ∂g/∂y` with the same method, interpolating the forward
+//states between steps, so its gradient only converges to the exact one as the steps shrink.
+
+//a parameterized right-hand side that can also multiply its Jacobians from the left
+pub trait AdjointRhs<R, S>: ParamRhs<R, S> {
+    //returns `λJ_y` and adds `λ ∂f/∂p` to `dp`
+    fn vjp(&self, t: R, y: &S, lambda: &S, dp: &mut [R]) -> S;
+}
+
+//the `i`th unit vector with the same shape as `y`
+pub(crate) fn unit<R: Real, S: DualState<R>>(y: &S, i: usize) -> S {
+    S::split(y.seed(i)).1
+}
+
+//the vector with the given components in the same shape as `y`
+pub(crate) fn from_components<R: Real, S: State<R> + DualState<R>>(y: &S, components: Vec<R>) -> S {
+    components
+        .into_iter()
+        .enumerate()
+        .fold(S::zero(), |sum, (i, x)| sum + unit(y, i) * x)
+}
+
+//`λJ`, found one component at a time from the products of `J` with the unit vectors
+fn left_apply<R, S, J>(jac: &J, y: &S, lambda: &S) -> S
+where
+    R: Real,
+    S: State<R> + InnerProductSpace<R> + DualState<R>,
+    J: Jacobian<R, S>,
+{
+    let components = (0..y.dim())
+        .map(|i| lambda.clone().inner_product(jac.apply(&unit(y, i))))
+        .collect();
+    from_components(y, components)
+}
+
+impl<R, S, J, F, Jy, Jp> AdjointRhs<R, S> for AnalyticJacobians<F, Jy, Jp, R>
+where
+    R: Real,
+    S: State<R> + InnerProductSpace<R> + DualState<R>,
+    J: Jacobian<R, S>,
+    F: Fn(R, &S, &[R]) -> S,
+    Jy: Fn(R, &S, &[R]) -> J,
+    Jp: Fn(R, &S, &[R]) -> Vec<S>,
+{
+    fn vjp(&self, t: R, y: &S, lambda: &S, dp: &mut [R]) -> S {
+        let jac_y = (self.jac_y)(t.clone(), y, &self.params);
+        for (dp, df_dp) in dp.iter_mut().zip((self.jac_p)(t, y, &self.params)) {
+            *dp += lambda.clone().inner_product(df_dp);
+        }
+        left_apply(&jac_y, y, lambda)
+    }
+}
+
+impl<R, S, J, F, Jy, Jp, Vp> AdjointRhs<R, S> for AnalyticJacobians<F, Jy, Jp, R, Vp>
+where
+    R: Real,
+    S: State<R> + InnerProductSpace<R> + DualState<R>,
+    J: Jacobian<R, S>,
+    F: Fn(R, &S, &[R]) -> S,
+    Jy: Fn(R, &S, &[R]) -> J,
+    Jp: Fn(R, &S, &[R]) -> Vec<S>,
+    Vp: Fn(R, &S, &[R], &S, &mut [R]),
+{
+    fn vjp(&self, t: R, y: &S, lambda: &S, dp: &mut [R]) -> S {
+        let jac_y = (self.jac_y)(t.clone(), y, &self.params);
+        (self.vjp_p)(t, y, &self.params, lambda, dp);
+        left_apply(&jac_y, y, lambda)
+    }
+}
+
+impl<R, S, F> AdjointRhs<R, S> for FiniteDifferenceJacobians<F, R>
+where
+    R: Real,
+    S: State<R> + InnerProductSpace<R> + DualState<R>,
+    F: Fn(R, &S, &[R]) -> S,
+{
+    fn vjp(&self, t: R, y: &S, lambda: &S, dp: &mut [R]) -> S {
+        let two = R::one() + R::one();
+        let mut p = self.params.clone();
+        for (i, dp) in dp.iter_mut().enumerate() {
+            let p_i = self.params[i].clone();
+            let scale = p_i.clone().abs();
+            let h = self.step.clone() * if scale > R::one() { scale } else { R::one() };
+
+            p[i] = p_i.clone() + h.clone();
+            let forward = (self.f)(t.clone(), y, &p);
+            p[i] = p_i.clone() - h.clone();
+            let backward = (self.f)(t.clone(), y, &p);
+            p[i] = p_i;
+
+            *dp += lambda.clone().inner_product(forward - backward) / (h * two.clone());
+        }
+
+        let components = (0..y.dim())
+            .map(|i| {
+                let e = unit(y, i) * self.step.clone();
+                let forward = (self.f)(t.clone(), &(y.clone() + e.clone()), &self.params);
+                let backward = (self.f)(t.clone(), &(y.clone() - e), &self.params);
+                lambda.clone().inner_product(forward - backward) / (self.step.clone() * two.clone())
+            })
+            .collect();
+        from_components(y, components)
+    }
+}
+
+impl<R, S, F> AdjointRhs<R, S> for AutoDiffJacobians<F, R>
+where
+    R: Real,
+    S: State<R> + InnerProductSpace<R> + DualState<R>,
+    F: Fn(Dual<R>, &S::Dual, &[Dual<R>]) -> S::Dual,
+{
+    fn vjp(&self, t: R, y: &S, lambda: &S, dp: &mut [R]) -> S {
+        let t = Dual::constant(t);
+        let mut p: Vec<_> = self.params.iter().cloned().map(Dual::constant).collect();
+
+        let constant = y.lift(&S::zero());
+        for (i, dp) in dp.iter_mut().enumerate() {
+            p[i].deriv = R::one();
+            let df_dp = S::split((self.f)(t.clone(), &constant, &p)).1;
+            p[i].deriv = R::zero();
+            *dp += lambda.clone().inner_product(df_dp);
+        }
+
+        let components = (0..y.dim())
+            .map(|i| {
+                let column = S::split((self.f)(t.clone(), &y.seed(i), &p)).1;
+                lambda.clone().inner_product(column)
+            })
+            .collect();
+        from_components(y, components)
+    }
+}
+
+//The cost `φ(y(t1)) + ∫g(t, y, p) dt` whose gradient an `Adjoint` finds
+pub trait Cost<R, S> {
+    fn terminal(&self, y: &S) -> R;
+    //returns `∂φ/∂y`
+    fn terminal_grad(&self, y: &S) -> S;
+    fn running(&self, t: R, y: &S, p: &[R]) -> R;
+    //returns `∂g/∂y` and adds `∂g/∂p` to `dp`
+    fn running_grad(&self, t: R, y: &S, p: &[R], dp: &mut [R]) -> S;
+}
+
+//a cost differentiated with dual numbers, from functions usually written generically over the
+//real number type
+#[derive(Clone, Copy, Debug)]
+pub struct AutoDiffCost<Phi, G> {
+    pub terminal: Phi,
+    pub running: G,
+}
+
+impl<Phi, G> AutoDiffCost<Phi, G> {
+    pub fn new(terminal: Phi, running: G) -> Self {
+        AutoDiffCost { terminal, running }
+    }
+}
+
+impl<R, S, Phi, G> Cost<R, S> for AutoDiffCost<Phi, G>
+where
+    R: Real,
+    S: State<R> + DualState<R>,
+    Phi: Fn(&S::Dual) -> Dual<R>,
+    G: Fn(Dual<R>, &S::Dual, &[Dual<R>]) -> Dual<R>,
+{
+    fn terminal(&self, y: &S) -> R {
+        (self.terminal)(&y.lift(&S::zero())).value
+    }
+    fn terminal_grad(&self, y: &S) -> S {
+        let components = (0..y.dim())
+            .map(|i| (self.terminal)(&y.seed(i)).deriv)
+            .collect();
+        from_components(y, components)
+    }
+    fn running(&self, t: R, y: &S, p: &[R]) -> R {
+        let p: Vec<_> = p.iter().cloned().map(Dual::constant).collect();
+        (self.running)(Dual::constant(t), &y.lift(&S::zero()), &p).value
+    }
+    fn running_grad(&self, t: R, y: &S, p: &[R], dp: &mut [R]) -> S {
+        let t = Dual::constant(t);
+        let mut p: Vec<_> = p.iter().cloned().map(Dual::constant).collect();
+
+        let constant = y.lift(&S::zero());
+        for (i, dp) in dp.iter_mut().enumerate() {
+            p[i].deriv = R::one();
+            *dp += (self.running)(t.clone(), &constant, &p).deriv;
+            p[i].deriv = R::zero();
+        }
+
+        let components = (0..y.dim())
+            .map(|i| (self.running)(t.clone(), &y.seed(i), &p).deriv)
+            .collect();
+        from_components(y, components)
+    }
+}
+
+//How the states of the forward pass are kept for the backward pass
+//
+//The checkpoints are used last-in-first-out, so a schedule is just the sequence of
+//`CheckpointAction`s to take, starting from the initial state.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Checkpointing {
+    //every state is kept, so nothing is recomputed
+    #[default]
+    All,
+    //the given number of evenly spaced intervals, whose first states are kept as checkpoints
+    //and whose other states are recomputed (and kept) one interval at a time, so that up to
+    //`intervals + ⌈steps/intervals⌉ - 1` states are held at once
+    UniformIntervals(usize),
+    //binomial checkpointing (as in Revolve) with at most the given number of checkpoints,
+    //which recomputes as few steps as possible for that many checkpoints
+    Binomial(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CheckpointAction {
+    //takes the given number of steps forward
+    Advance(usize),
+    //keeps the current state as the newest checkpoint
+    Store,
+    //goes back to the newest checkpoint
+    Restore,
+    //drops the newest checkpoint
+    Free,
+    //takes a step of the adjoint backward over the step starting at the current state
+    Reverse,
+}
+
+//the number of steps that can be reversed with `c` checkpoints (including the one at the start)
+//when no step is taken more than `r` times, which is the binomial coefficient `(c+r choose c)`
+fn binomial(c: usize, r: usize) -> u128 {
+    (1..=c as u128).fold(1u128, |beta, i| beta.saturating_mul(r as u128 + i) / i)
+}
+
+//reverses the steps from `a` to `b` with `c` checkpoints besides the newest one, which has to
+//hold the state at `a` (which is also the current state)
+fn revolve(a: usize, b: usize, c: usize, schedule: &mut Vec<CheckpointAction>) {
+    use self::CheckpointAction::*;
+
+    let n = b - a;
+    if n == 1 {
+        schedule.push(Reverse);
+    } else if c == 0 {
+        for k in (a..b).rev() {
+            schedule.push(Restore);
+            if k > a {
+                schedule.push(Advance(k - a));
+            }
+            schedule.push(Reverse);
+        }
+    } else {
+        //the split of an optimal schedule, as chosen by Revolve from the fewest repetitions `t`
+        //that suffice with these `s` checkpoints (Griewank and Walther, 2000)
+        let (s, n) = (c + 1, n as u128);
+        let t = (1..).find(|&t| binomial(s, t) >= n).unwrap();
+        let fewer = |s: usize| binomial(s, t - 1);
+        let d = if n <= fewer(s) + fewer(s - 2) {
+            t.checked_sub(2).map_or(0, |t| binomial(s, t))
+        } else if n >= binomial(s, t) - s.checked_sub(3).map_or(0, |s| binomial(s, t)) {
+            fewer(s)
+        } else {
+            n - fewer(s - 1) - fewer(s - 2)
+        };
+        let d = d.clamp(1, n - 1) as usize;
+        schedule.extend([Advance(d), Store]);
+        revolve(a + d, b, c - 1, schedule);
+        schedule.extend([Free, Restore]);
+        revolve(a, a + d, c, schedule);
+    }
+}
+
+impl Checkpointing {
+    //the actions that reverse the given number of steps
+    pub fn schedule(self, steps: usize) -> Vec<CheckpointAction> {
+        use self::CheckpointAction::*;
+
+        let mut schedule = Vec::new();
+        if steps == 0 {
+            return schedule;
+        }
+        match self {
+            Checkpointing::All => return Checkpointing::UniformIntervals(steps).schedule(steps),
+            Checkpointing::UniformIntervals(intervals) => {
+                let interval = steps.div_ceil(intervals.clamp(1, steps));
+                let starts: Vec<_> = (0..steps).step_by(interval).collect();
+
+                schedule.push(Store);
+                for _ in 1..starts.len() {
+                    schedule.extend([Advance(interval), Store]);
+                }
+                for &start in starts.iter().rev() {
+                    let end = (start + interval).min(steps);
+                    schedule.push(Restore);
+                    for _ in start + 1..end {
+                        schedule.extend([Advance(1), Store]);
+                    }
+                    for _ in start..end {
+                        schedule.extend([Restore, Reverse, Free]);
+                    }
+                }
+            }
+            Checkpointing::Binomial(checkpoints) => {
+                schedule.push(Store);
+                revolve(0, steps, checkpoints.max(1) - 1, &mut schedule);
+                schedule.push(Free);
+            }
+        }
+        schedule
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AdjointKind {
+    #[default]
+    Discrete,
+    Continuous,
+}
+
+//the cost and its gradient, along with how much work the checkpointing took
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Gradient<R, S> {
+    pub cost: R,
+    pub params: Vec<R>,
+    pub initial: S,
+    //every forward step taken, including recomputed ones
+    pub forward_steps: usize,
+    pub max_checkpoints: usize,
+}
+
+//The state of the backward pass of the continuous adjoint: the adjoint itself, along with the
+//integrals giving the gradient with respect to the parameters and the running cost
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct AdjointState<S, R> {
+    pub lambda: S,
+    pub mu: DVector<R>,
+    pub cost: R,
+}
+
+macro_rules! impl_adjoint_ops {
+    ($($Op:ident::$op:ident $Assign:ident::$assign:ident),*) => {$(
+        impl<R: Zero + $Assign, S: $Assign> $Assign for AdjointState<S, R> {
+            fn $assign(&mut self, rhs: Self) {
+                $Assign::$assign(&mut self.lambda, rhs.lambda);
+                $Assign::$assign(&mut self.mu, rhs.mu);
+                $Assign::$assign(&mut self.cost, rhs.cost);
+            }
+        }
+
+        impl<'a, R: Clone + Zero + $Assign, S: $Assign<&'a S>> $Assign<&'a Self> for AdjointState<S, R> {
+            fn $assign(&mut self, rhs: &'a Self) {
+                $Assign::$assign(&mut self.lambda, &rhs.lambda);
+                $Assign::$assign(&mut self.mu, &rhs.mu);
+                $Assign::$assign(&mut self.cost, rhs.cost.clone());
+            }
+        }
+
+        impl<R: Zero + $Assign, S: $Assign> $Op for AdjointState<S, R> {
+            type Output = Self;
+            fn $op(mut self, rhs: Self) -> Self {
+                $Assign::$assign(&mut self, rhs);
+                self
+            }
+        }
+    )*};
+
+    ($(scalar $Op:ident::$op:ident $Assign:ident::$assign:ident),*) => {$(
+        impl<R: Clone + $Assign, S: $Assign<R>> $Assign<R> for AdjointState<S, R> {
+            fn $assign(&mut self, rhs: R) {
+                $Assign::$assign(&mut self.lambda, rhs.clone());
+                $Assign::$assign(&mut self.mu, rhs.clone());
+                $Assign::$assign(&mut self.cost, rhs);
+            }
+        }
+
+        impl<R: Clone + $Assign, S: $Assign<R>> $Op<R> for AdjointState<S, R> {
+            type Output = Self;
+            fn $op(mut self, rhs: R) -> Self {
+                $Assign::$assign(&mut self, rhs);
+                self
+            }
+        }
+    )*};
+}
+
+impl_adjoint_ops!(Add::add AddAssign::add_assign, Sub::sub SubAssign::sub_assign);
+impl_adjoint_ops!(
+    scalar Mul::mul MulAssign::mul_assign,
+    scalar Div::div DivAssign::div_assign
+);
+
+impl<R: Clone + Neg<Output = R>, S: Neg<Output = S>> Neg for AdjointState<S, R> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        AdjointState {
+            lambda: -self.lambda,
+            mu: -self.mu,
+            cost: -self.cost,
+        }
+    }
+}
+
+impl<R: Zero + AddAssign, S: Zero + AddAssign> Zero for AdjointState<S, R> {
+    fn zero() -> Self {
+        AdjointState {
+            lambda: S::zero(),
+            mu: DVector::zero(),
+            cost: R::zero(),
+        }
+    }
+    fn is_zero(&self) -> bool {
+        self.lambda.is_zero() && self.mu.is_zero() && self.cost.is_zero()
+    }
+}
+
+impl<S, R> AddAssociative for AdjointState<S, R> {}
+impl<S, R> AddCommutative for AdjointState<S, R> {}
+impl<S, R> Distributive<R> for AdjointState<S, R> {}
+
+//Finds gradients with an adjoint method over `steps` equal steps from `t0` to `t1`
+//
+//The forward and backward passes use any fixed-step explicit Runge-Kutta method. By default,
+//this uses the discrete adjoint and keeps every state.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Adjoint<R> {
+    t0: R,
+    t1: R,
+    steps: usize,
+    kind: AdjointKind,
+    checkpointing: Checkpointing,
+}
+
+impl<R: Real> Adjoint<R> {
+    pub fn new(t0: R, t1: R, steps: usize) -> Self {
+        assert!(steps > 0, "there must be at least one step");
+        Adjoint {
+            t0,
+            t1,
+            steps,
+            kind: Default::default(),
+            checkpointing: Default::default(),
+        }
+    }
+
+    pub fn kind(mut self, kind: AdjointKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn checkpointing(mut self, checkpointing: Checkpointing) -> Self {
+        self.checkpointing = checkpointing;
+        self
+    }
+
+    pub fn dt(&self) -> R {
+        (self.t1.clone() - self.t0.clone()) / R::embed_nat(self.steps as u64)
+    }
+
+    fn time(&self, step: usize) -> R {
+        self.t0.clone() + self.dt() * R::embed_nat(step as u64)
+    }
+
+    pub fn gradient<T, S, P, G>(&self, method: &T, y0: S, problem: &P, cost: &G) -> Gradient<R, S>
+    where
+        T: Tableau + Integrator,
+        S: State<R>,
+        P: AdjointRhs<R, S>,
+        G: Cost<R, S>,
+    {
+        use self::CheckpointAction::*;
+
+        let dt = self.dt();
+        let force = |t: R, y: S| ((), problem.eval(t, &y));
+        let forward = |step: usize, y: S| {
+            let mut buffer = method.init(y, dt.clone(), force);
+            method
+                .step(self.time(step), &mut buffer, dt.clone(), force)
+                .1
+        };
+
+        let mut current = (0, y0.clone());
+        let mut checkpoints: Vec<(usize, S)> = Vec::new();
+        let mut forward_steps = 0;
+        let mut max_checkpoints = 0;
+
+        //the adjoint and gradient so far, once the last step has been reached
+        let params = problem.params().len();
+        let mut adjoint: Option<AdjointState<S, R>> = None;
+
+        for action in self.checkpointing.schedule(self.steps) {
+            match action {
+                Advance(n) => {
+                    for _ in 0..n {
+                        current = (current.0 + 1, forward(current.0, current.1));
+                    }
+                    forward_steps += n;
+                }
+                Store => {
+                    checkpoints.push(current.clone());
+                    max_checkpoints = max_checkpoints.max(checkpoints.len());
+                }
+                Restore => current = checkpoints.last().unwrap().clone(),
+                Free => {
+                    checkpoints.pop();
+                }
+                Reverse => {
+                    let (step, y) = current.clone();
+                    let mut z = match adjoint.take() {
+                        Some(z) => z,
+                        None => {
+                            //the last step, which starts the adjoint from the terminal cost
+                            debug_assert_eq!(step + 1, self.steps);
+                            let y1 = forward(step, y.clone());
+                            forward_steps += 1;
+                            AdjointState {
+                                lambda: cost.terminal_grad(&y1),
+                                mu: DVector::zeros(params),
+                                cost: cost.terminal(&y1),
+                            }
+                        }
+                    };
+                    z = match self.kind {
+                        AdjointKind::Discrete => {
+                            self.discrete_step(method, step, y, z, problem, cost)
+                        }
+                        AdjointKind::Continuous => {
+                            let y1 = forward(step, y.clone());
+                            forward_steps += 1;
+                            self.continuous_step(method, step, y, y1, z, problem, cost)
+                        }
+                    };
+                    adjoint = Some(z);
+                }
+            }
+        }
+
+        let z = adjoint.unwrap();
+        Gradient {
+            cost: z.cost,
+            params: z.mu.0,
+            initial: z.lambda,
+            forward_steps,
+            max_checkpoints,
+        }
+    }
+
+    //the adjoint of one Runge-Kutta step, which goes through the stages in reverse
+    fn discrete_step<T, S, P, G>(
+        &self,
+        method: &T,
+        step: usize,
+        y: S,
+        mut z: AdjointState<S, R>,
+        problem: &P,
+        cost: &G,
+    ) -> AdjointState<S, R>
+    where
+        T: Tableau,
+        S: State<R>,
+        P: AdjointRhs<R, S>,
+        G: Cost<R, S>,
+    {
+        let dt = self.dt();
+        let t = self.time(step);
+        let stages = method.stages();
+        let p = problem.params();
+
+        //the times and inputs of each stage, as in the forward step
+        let mut times = Vec::with_capacity(stages);
+        let mut inputs: Vec<S> = Vec::with_capacity(stages);
+        let mut k: Vec<S> = Vec::with_capacity(stages);
+        for i in 0..stages {
+            let mut y_i = y.clone();
+            for (j, k_j) in k.iter().enumerate() {
+                let a_ij = method.a(i, j);
+                if !a_ij.is_zero() {
+                    y_i += k_j.clone() * (dt.clone() * a_ij.to_real::<R>());
+                }
+            }
+            let t_i = t.clone() + dt.clone() * method.c(i).to_real::<R>();
+            k.push(problem.eval(t_i.clone(), &y_i));
+            times.push(t_i);
+            inputs.push(y_i);
+        }
+
+        //the adjoints of the stage inputs, from the last stage to the first
+        let mut mu: Vec<R> = z.mu.0.clone();
+        mu.resize(p.len(), R::zero());
+        let mut stage_adjoints: Vec<S> = vec![S::zero(); stages];
+        for i in (0..stages).rev() {
+            let w = dt.clone() * method.b(i).to_real::<R>();
+            let mut lambda_k = z.lambda.clone() * w.clone();
+            for (j, adjoint_j) in stage_adjoints.iter().enumerate().skip(i + 1) {
+                let a_ji = method.a(j, i);
+                if !a_ji.is_zero() {
+                    lambda_k += adjoint_j.clone() * (dt.clone() * a_ji.to_real::<R>());
+                }
+            }
+
+            let mut adjoint_i = problem.vjp(times[i].clone(), &inputs[i], &lambda_k, &mut mu);
+            if !w.is_zero() {
+                //the running cost is integrated with the same weights as the state
+                let mut dg_dp = vec![R::zero(); p.len()];
+                let dg_dy = cost.running_grad(times[i].clone(), &inputs[i], p, &mut dg_dp);
+                adjoint_i += dg_dy * w.clone();
+                for (mu, dg_dp) in mu.iter_mut().zip(dg_dp) {
+                    *mu += dg_dp * w.clone();
+                }
+                z.cost += cost.running(times[i].clone(), &inputs[i], p) * w;
+            }
+            stage_adjoints[i] = adjoint_i;
+        }
+
+        for adjoint_i in stage_adjoints {
+            z.lambda += adjoint_i;
+        }
+        z.mu = DVector(mu);
+        z
+    }
+
+    //one step of the adjoint equations backward from the end of the step, with the forward
+    //state interpolated from both ends of it
+    #[allow(clippy::too_many_arguments)]
+    fn continuous_step<T, S, P, G>(
+        &self,
+        method: &T,
+        step: usize,
+        y0: S,
+        y1: S,
+        z: AdjointState<S, R>,
+        problem: &P,
+        cost: &G,
+    ) -> AdjointState<S, R>
+    where
+        T: Integrator,
+        S: State<R>,
+        P: AdjointRhs<R, S>,
+        G: Cost<R, S>,
+    {
+        let dt = self.dt();
+        let (t0, t1) = (self.time(step), self.time(step + 1));
+        let f0 = problem.eval(t0.clone(), &y0) * dt.clone();
+        let f1 = problem.eval(t1.clone(), &y1) * dt.clone();
+        let p = problem.params();
+
+        //cubic Hermite interpolation, which is accurate enough for methods up to fourth order
+        let state = |t: R| {
+            let s = (t - t0.clone()) / dt.clone();
+            let (s2, s3) = (s.clone() * s.clone(), s.clone() * s.clone() * s.clone());
+            let two = R::one() + R::one();
+            let three = two.clone() + R::one();
+            y0.clone() * (two.clone() * s3.clone() - three.clone() * s2.clone() + R::one())
+                + f0.clone() * (s3.clone() - two.clone() * s2.clone() + s.clone())
+                + y1.clone() * (three * s2.clone() - two * s3.clone())
+                + f1.clone() * (s3 - s2)
+        };
+
+        let adjoint = |t: R, z: AdjointState<S, R>| {
+            let y = state(t.clone());
+            let mut mu = vec![R::zero(); p.len()];
+            let lambda = problem.vjp(t.clone(), &y, &z.lambda, &mut mu);
+            let dg_dy = cost.running_grad(t.clone(), &y, p, &mut mu);
+            let dz = AdjointState {
+                lambda: lambda + dg_dy,
+                mu: DVector(mu),
+                cost: cost.running(t, &y, p),
+            };
+            ((), -dz)
+        };
+
+        let mut buffer = method.init(z, -dt.clone(), adjoint);
+        method.step(t1, &mut buffer, -dt.clone(), adjoint).1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //the total number of steps advanced by the schedule and the most checkpoints it holds at
+    //once, checking that it reverses every step in order
+    fn replay(schedule: &[CheckpointAction], steps: usize) -> (u128, usize) {
+        use self::CheckpointAction::*;
+
+        let (mut current, mut stored, mut reversed) = (0, Vec::new(), steps);
+        let (mut total, mut held) = (0, 0);
+        for &action in schedule {
+            match action {
+                Advance(n) => {
+                    current += n;
+                    total += n as u128;
+                }
+                Store => {
+                    stored.push(current);
+                    held = held.max(stored.len());
+                }
+                Restore => current = *stored.last().unwrap(),
+                Free => {
+                    stored.pop().unwrap();
+                }
+                Reverse => {
+                    assert_eq!(current + 1, reversed);
+                    reversed -= 1;
+                }
+            }
+        }
+        assert_eq!(reversed, 0);
+        assert!(stored.is_empty());
+        (total, held)
+    }
+
+    #[test]
+    fn binomial_schedules_are_optimal() {
+        for checkpoints in 1..=8 {
+            for steps in 1..=300 {
+                let schedule = Checkpointing::Binomial(checkpoints).schedule(steps);
+                let (total, held) = replay(&schedule, steps);
+                assert!(held <= checkpoints);
+
+                //the fewest steps that can be advanced, from Griewank and Walther
+                let n = steps as u128;
+                let t = (0..).find(|&t| binomial(checkpoints, t) >= n).unwrap();
+                let optimal = t as u128 * n
+                    - if t == 0 {
+                        0
+                    } else {
+                        binomial(checkpoints + 1, t - 1)
+                    };
+                assert_eq!(total, optimal, "{steps} steps, {checkpoints} checkpoints");
+            }
+        }
+
+        let cost = |steps, checkpoints| {
+            replay(&Checkpointing::Binomial(checkpoints).schedule(steps), steps).0
+        };
+        assert_eq!(cost(500, 8), 1785);
+        assert_eq!(cost(20, 4), 39);
+    }
+
+    //every kind of adjoint with every kind of checkpointing, over 100 steps from 0 to `t1`
+    #[cfg(feature = "std")]
+    fn gradients<G: Cost<f64, f64>>(p: f64, y0: f64, t1: f64, cost: &G) -> Vec<Gradient<f64, f64>> {
+        fn decay<T: Real>(_t: T, y: &T, p: &[T]) -> T {
+            -p[0].clone() * y.clone()
+        }
+
+        let problem = AutoDiffJacobians::new(decay::<Dual<f64>>, vec![p]);
+        let mut gradients = Vec::new();
+        for kind in [AdjointKind::Discrete, AdjointKind::Continuous] {
+            for checkpointing in [
+                Checkpointing::All,
+                Checkpointing::UniformIntervals(10),
+                Checkpointing::Binomial(4),
+            ] {
+                let adjoint = Adjoint::new(0.0, t1, 100)
+                    .kind(kind)
+                    .checkpointing(checkpointing);
+                gradients.push(adjoint.gradient(&RK4, y0, &problem, cost));
+            }
+        }
+        gradients
+    }
+
+    #[test]
+    fn uniform_intervals_hold_as_many_states_as_documented() {
+        for intervals in 1..=20 {
+            for steps in 1..=100 {
+                let schedule = Checkpointing::UniformIntervals(intervals).schedule(steps);
+                let (_, held) = replay(&schedule, steps);
+                assert!(held < intervals + steps.div_ceil(intervals));
+            }
+        }
+
+        let schedule = Checkpointing::UniformIntervals(20).schedule(500);
+        assert_eq!(replay(&schedule, 500).1, 44);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn gradients_of_the_terminal_cost_are_exact() {
+        let (p, y0, t1) = (0.7, 1.5, 2.0);
+        fn terminal<T: Real>(y: &T) -> T {
+            y.clone()
+        }
+        fn no_running<T: Real>(_t: T, _y: &T, _p: &[T]) -> T {
+            T::zero()
+        }
+        let cost = AutoDiffCost::new(terminal::<Dual<f64>>, no_running::<Dual<f64>>);
+
+        //for `y' = -py`, `y(t1) = y0*exp(-p*t1)`
+        let e = (-p * t1).exp();
+        for gradient in gradients(p, y0, t1, &cost) {
+            assert!((gradient.cost - y0 * e).abs() < 1e-8, "{gradient:?}");
+            assert!(
+                (gradient.params[0] + t1 * y0 * e).abs() < 1e-6,
+                "{gradient:?}"
+            );
+            assert!((gradient.initial - e).abs() < 1e-6, "{gradient:?}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn gradients_of_the_running_cost_are_exact() {
+        let (p, y0, t1) = (0.7, 1.5, 2.0);
+        fn no_terminal<T: Real>(_y: &T) -> T {
+            T::zero()
+        }
+        fn running<T: Real>(_t: T, y: &T, _p: &[T]) -> T {
+            y.clone()
+        }
+        let cost = AutoDiffCost::new(no_terminal::<Dual<f64>>, running::<Dual<f64>>);
+
+        //the integral of `y0*exp(-pt)` from 0 to `t1` and its derivatives
+        let e = (-p * t1).exp();
+        let integral = y0 * (1.0 - e) / p;
+        let d_p = y0 * t1 * e / p - integral / p;
+        let d_y0 = (1.0 - e) / p;
+        for gradient in gradients(p, y0, t1, &cost) {
+            assert!((gradient.cost - integral).abs() < 1e-6, "{gradient:?}");
+            assert!((gradient.params[0] - d_p).abs() < 1e-6, "{gradient:?}");
+            assert!((gradient.initial - d_y0).abs() < 1e-6, "{gradient:?}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn products_with_the_parameter_derivatives_can_be_given_directly() {
+        fn terminal<T: Real>(y: &T) -> T {
+            y.clone()
+        }
+        fn no_running<T: Real>(_t: T, _y: &T, _p: &[T]) -> T {
+            T::zero()
+        }
+        let cost = AutoDiffCost::new(terminal::<Dual<f64>>, no_running::<Dual<f64>>);
+
+        let (p, y0, t1) = (0.7, 1.5, 2.0);
+        let problem = AnalyticJacobians::new(
+            |_t, y: &f64, p: &[f64]| -p[0] * y,
+            |_t, _y: &f64, p: &[f64]| DenseJacobian::from_rows(vec![vec![-p[0]]]),
+            |_t, _y: &f64, _p: &[f64]| -> Vec<f64> { unreachable!("only `vjp_p` is used") },
+            vec![p],
+        )
+        .vjp_p(|_t, y: &f64, _p: &[f64], lambda: &f64, dp: &mut [f64]| dp[0] -= lambda * y);
+
+        let e = (-p * t1).exp();
+        for kind in [AdjointKind::Discrete, AdjointKind::Continuous] {
+            let gradient = Adjoint::new(0.0, t1, 100)
+                .kind(kind)
+                .gradient(&RK4, y0, &problem, &cost);
+            assert!(
+                (gradient.params[0] + t1 * y0 * e).abs() < 1e-6,
+                "{gradient:?}"
+            );
+            assert!((gradient.initial - e).abs() < 1e-6, "{gradient:?}");
+        }
+    }
+}
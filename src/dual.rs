@@ -0,0 +1,981 @@
+use super::*;
+
+use core::cmp::Ordering;
+use core::fmt::{Debug, Display, Formatter};
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::ops::{Rem, RemAssign};
+use maths_traits::algebra::*;
+use maths_traits::analysis::ordered::*;
+use num_traits::{FromPrimitive, ToPrimitive};
+
+//A dual number `value + deriv*ε` where `ε² = 0`
+//
+//Arithmetic on dual numbers carries a derivative along with each value, and since `Dual<R>`
+//is `Real` itself, running any integrator with dual components gives the derivative of the
+//solution along with it. Starting from `Dual::variable(y0)` gives `∂y/∂y0`, and using
+//`Dual::variable(p)` for a parameter of the right-hand side gives `∂y/∂p`.
+//
+//Comparisons only look at the values, so the step size control and any other branching
+//make exactly the same decisions as they would without the derivatives.
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dual<R> {
+    pub value: R,
+    pub deriv: R,
+}
+
+//a dual number of dual numbers, for second derivatives
+//
+//when both parts are seeded with one (as by `HyperDual::variable2()`), the result holds
+//`f + f'ε₁ + f'ε₂ + f''ε₁ε₂`
+pub type HyperDual<R> = Dual<Dual<R>>;
+
+impl<R> Dual<R> {
+    pub fn new(value: R, deriv: R) -> Self {
+        Dual { value, deriv }
+    }
+}
+
+impl<R: Real> Dual<R> {
+    //a value that nothing depends on
+    pub fn constant(value: R) -> Self {
+        Dual::new(value, R::zero())
+    }
+
+    //the value everything is differentiated with respect to
+    pub fn variable(value: R) -> Self {
+        Dual::new(value, R::one())
+    }
+
+    //the chain rule, for a function with the given value and derivative at `self.value`
+    fn chain(self, value: R, deriv: R) -> Self {
+        Dual::new(value, deriv * self.deriv)
+    }
+}
+
+impl<R: Real> HyperDual<R> {
+    //the variable of a second derivative
+    pub fn variable2(value: R) -> Self {
+        Dual::new(Dual::variable(value), Dual::constant(R::one()))
+    }
+}
+
+//the value and derivative of `f` at `x`
+pub fn derivative<R: Real, F: FnOnce(Dual<R>) -> Dual<R>>(f: F, x: R) -> (R, R) {
+    let y = f(Dual::variable(x));
+    (y.value, y.deriv)
+}
+
+//the value and first and second derivatives of `f` at `x`
+pub fn second_derivative<R: Real, F: FnOnce(HyperDual<R>) -> HyperDual<R>>(
+    f: F,
+    x: R,
+) -> (R, R, R) {
+    let y = f(HyperDual::variable2(x));
+    (y.value.value, y.value.deriv, y.deriv.deriv)
+}
+
+impl<R: Display> Display for Dual<R> {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        write!(f, "{} + {}ε", self.value, self.deriv)
+    }
+}
+
+impl<R: Display> Debug for Dual<R> {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl<R: PartialEq> PartialEq for Dual<R> {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.value == rhs.value
+    }
+}
+
+impl<R: PartialOrd> PartialOrd for Dual<R> {
+    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        self.value.partial_cmp(&rhs.value)
+    }
+}
+
+impl<R: Real> Neg for Dual<R> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Dual::new(-self.value, -self.deriv)
+    }
+}
+
+impl<R: Real> Add for Dual<R> {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Dual::new(self.value + rhs.value, self.deriv + rhs.deriv)
+    }
+}
+
+impl<R: Real> Sub for Dual<R> {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Dual::new(self.value - rhs.value, self.deriv - rhs.deriv)
+    }
+}
+
+impl<R: Real> Mul for Dual<R> {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Dual::new(
+            self.value.clone() * rhs.value.clone(),
+            self.value * rhs.deriv + self.deriv * rhs.value,
+        )
+    }
+}
+
+impl<R: Real> Div for Dual<R> {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        let value = self.value / rhs.value.clone();
+        Dual::new(value.clone(), (self.deriv - value * rhs.deriv) / rhs.value)
+    }
+}
+
+macro_rules! impl_assign {
+    ($($Assign:ident::$assign:ident => $op:tt),*) => {$(
+        impl<R: Real> $Assign for Dual<R> {
+            fn $assign(&mut self, rhs: Self) {
+                *self = self.clone() $op rhs;
+            }
+        }
+    )*};
+}
+
+impl_assign!(AddAssign::add_assign => +, SubAssign::sub_assign => -, MulAssign::mul_assign => *, DivAssign::div_assign => /);
+
+impl<R: Real> Zero for Dual<R> {
+    fn zero() -> Self {
+        Dual::constant(R::zero())
+    }
+    //only an exact zero, so that nothing skips over a term that still has a derivative
+    fn is_zero(&self) -> bool {
+        self.value.is_zero() && self.deriv.is_zero()
+    }
+}
+
+impl<R: Real> One for Dual<R> {
+    fn one() -> Self {
+        Dual::constant(R::one())
+    }
+}
+
+impl<R: Real> Inv for Dual<R> {
+    type Output = Self;
+    fn inv(self) -> Self {
+        let inv = self.value.clone().inv();
+        let deriv = -(inv.clone() * inv.clone());
+        self.chain(inv, deriv)
+    }
+}
+
+impl<R> AddAssociative for Dual<R> {}
+impl<R> AddCommutative for Dual<R> {}
+impl<R> MulAssociative for Dual<R> {}
+impl<R> MulCommutative for Dual<R> {}
+impl<R> Distributive for Dual<R> {}
+impl<R> NoZeroDivisors for Dual<R> {}
+impl<R> UniquelyFactorizable for Dual<R> {}
+impl<R: Real> AddOrdered for Dual<R> {}
+impl<R: Real> MulOrdered for Dual<R> {}
+impl<R: Real> ArchimedeanProperty for Dual<R> {}
+
+impl<R: Real> Sign for Dual<R> {
+    fn signum(self) -> Self {
+        Dual::constant(self.value.signum())
+    }
+    fn abs(self) -> Self {
+        if self.value < R::zero() {
+            -self
+        } else {
+            self
+        }
+    }
+}
+
+impl<R: Real> ArchimedeanDiv for Dual<R> {
+    fn embed_nat<N: Natural>(n: N) -> Self {
+        Dual::constant(R::embed_nat(n))
+    }
+    fn div_arch(self, rhs: Self) -> Self {
+        Dual::constant(self.value.div_arch(rhs.value))
+    }
+    fn rem_arch(self, rhs: Self) -> Self {
+        self.div_alg_arch(rhs).1
+    }
+    fn div_alg_arch(self, rhs: Self) -> (Self, Self) {
+        let q = self.clone().div_arch(rhs.clone());
+        (q.clone(), self - q * rhs)
+    }
+}
+
+//the same as for the primitive floats
+impl<R: Real> Divisibility for Dual<R> {
+    fn divides(self, _rhs: Self) -> bool {
+        true
+    }
+    fn divide(self, rhs: Self) -> Option<Self> {
+        Some(rhs / self)
+    }
+    fn unit(&self) -> bool {
+        true
+    }
+    fn inverse(self) -> Option<Self> {
+        Some(self.inv())
+    }
+}
+
+macro_rules! lift_constants {
+    ($($c:ident)*) => {$(
+        fn $c() -> Self {
+            Dual::constant(R::$c())
+        }
+    )*};
+}
+
+impl<R: Real> Trig for Dual<R> {
+    fn sin(self) -> Self {
+        let (sin, cos) = self.value.clone().sin_cos();
+        self.chain(sin, cos)
+    }
+    fn cos(self) -> Self {
+        let (sin, cos) = self.value.clone().sin_cos();
+        self.chain(cos, -sin)
+    }
+    fn tan(self) -> Self {
+        let tan = self.value.clone().tan();
+        self.chain(tan.clone(), R::one() + tan.clone() * tan)
+    }
+    fn sin_cos(self) -> (Self, Self) {
+        let (sin, cos) = self.value.sin_cos();
+        (
+            Dual::new(sin.clone(), cos.clone() * self.deriv.clone()),
+            Dual::new(cos, -sin * self.deriv),
+        )
+    }
+    fn sinh(self) -> Self {
+        let (sinh, cosh) = (self.value.clone().sinh(), self.value.clone().cosh());
+        self.chain(sinh, cosh)
+    }
+    fn cosh(self) -> Self {
+        let (sinh, cosh) = (self.value.clone().sinh(), self.value.clone().cosh());
+        self.chain(cosh, sinh)
+    }
+    fn tanh(self) -> Self {
+        let tanh = self.value.clone().tanh();
+        self.chain(tanh.clone(), R::one() - tanh.clone() * tanh)
+    }
+    fn try_asin(self) -> Option<Self> {
+        let x = self.value.clone();
+        let asin = x.clone().try_asin()?;
+        Some(self.chain(asin, (R::one() - x.clone() * x).sqrt().inv()))
+    }
+    fn try_acos(self) -> Option<Self> {
+        let x = self.value.clone();
+        let acos = x.clone().try_acos()?;
+        Some(self.chain(acos, -(R::one() - x.clone() * x).sqrt().inv()))
+    }
+    fn atan(self) -> Self {
+        let x = self.value.clone();
+        self.chain(x.clone().atan(), (R::one() + x.clone() * x).inv())
+    }
+    fn atan2(y: Self, x: Self) -> Self {
+        let r2 = x.value.clone() * x.value.clone() + y.value.clone() * y.value.clone();
+        Dual::new(
+            R::atan2(y.value.clone(), x.value.clone()),
+            (x.value * y.deriv - y.value * x.deriv) / r2,
+        )
+    }
+    fn try_asinh(self) -> Option<Self> {
+        let x = self.value.clone();
+        let asinh = x.clone().try_asinh()?;
+        Some(self.chain(asinh, (x.clone() * x + R::one()).sqrt().inv()))
+    }
+    fn try_acosh(self) -> Option<Self> {
+        let x = self.value.clone();
+        let acosh = x.clone().try_acosh()?;
+        Some(self.chain(acosh, (x.clone() * x - R::one()).sqrt().inv()))
+    }
+    fn try_atanh(self) -> Option<Self> {
+        let x = self.value.clone();
+        let atanh = x.clone().try_atanh()?;
+        Some(self.chain(atanh, (R::one() - x.clone() * x).inv()))
+    }
+
+    lift_constants!(
+        pi frac_2_pi frac_pi_2 frac_pi_3 frac_pi_4 frac_pi_6 frac_pi_8 pythag_const pythag_const_inv
+    );
+}
+
+impl<R: Real> Exponential for Dual<R> {
+    fn exp(self) -> Self {
+        let exp = self.value.clone().exp();
+        self.chain(exp.clone(), exp)
+    }
+    fn try_ln(self) -> Option<Self> {
+        let x = self.value.clone();
+        let ln = x.clone().try_ln()?;
+        Some(self.chain(ln, x.inv()))
+    }
+}
+
+//the defaults go through `ln()`, which loses accuracy and fails for bases that aren't
+//positive, so the usual rules are used instead
+impl<R: Real> RealExponential for Dual<R> {
+    fn try_pow(self, power: Self) -> Option<Self> {
+        let x = self.value;
+        let value = x.clone().try_pow(power.value.clone())?;
+
+        //d(x^p) = p*x^(p-1)*dx + x^p*ln(x)*dp, where the second term is left out for a
+        //constant power so that negative bases still work
+        let mut deriv =
+            power.value.clone() * x.clone().try_pow(power.value - R::one())? * self.deriv;
+        if !power.deriv.is_zero() {
+            deriv += value.clone() * x.try_ln()? * power.deriv;
+        }
+        Some(Dual::new(value, deriv))
+    }
+    fn try_log(self, base: Self) -> Option<Self> {
+        Some(self.try_ln()? / base.try_ln()?)
+    }
+    fn exp2(self) -> Self {
+        let exp2 = self.value.clone().exp2();
+        let ln2 = (R::one() + R::one()).ln();
+        self.chain(exp2.clone(), exp2 * ln2)
+    }
+    fn exp10(self) -> Self {
+        let exp10 = self.value.clone().exp10();
+        let ln10 = R::repr(10.0).ln();
+        self.chain(exp10.clone(), exp10 * ln10)
+    }
+    fn log2(self) -> Self {
+        let x = self.value.clone();
+        let ln2 = (R::one() + R::one()).ln();
+        self.chain(x.clone().log2(), (x * ln2).inv())
+    }
+    fn log10(self) -> Self {
+        let x = self.value.clone();
+        let ln10 = R::repr(10.0).ln();
+        self.chain(x.clone().log10(), (x * ln10).inv())
+    }
+    fn sqrt(self) -> Self {
+        let sqrt = self.value.clone().sqrt();
+        self.chain(sqrt.clone(), (sqrt.clone() + sqrt).inv())
+    }
+    fn cbrt(self) -> Self {
+        let cbrt = self.value.clone().cbrt();
+        let three = R::one() + R::one() + R::one();
+        self.chain(cbrt.clone(), (three * cbrt.clone() * cbrt).inv())
+    }
+    fn ln_1p(self) -> Self {
+        let x = self.value.clone();
+        self.chain(x.clone().ln_1p(), (R::one() + x).inv())
+    }
+    fn exp_m1(self) -> Self {
+        let x = self.value.clone();
+        self.chain(x.clone().exp_m1(), x.exp())
+    }
+}
+
+impl<R: Real> ComplexSubset for Dual<R> {
+    type Real = Self;
+    type Natural = DualNatural<R>;
+    type Integer = DualInteger<R>;
+
+    fn as_real(self) -> Self {
+        self
+    }
+    fn as_natural(self) -> DualNatural<R> {
+        DualNatural::new(self.value.approx() as u64)
+    }
+    fn as_integer(self) -> DualInteger<R> {
+        DualInteger::new(self.value.approx() as i64)
+    }
+
+    //these are all locally constant, so their derivatives vanish
+    fn floor(self) -> Self {
+        Dual::constant(self.value.floor())
+    }
+    fn ceil(self) -> Self {
+        Dual::constant(self.value.ceil())
+    }
+    fn round(self) -> Self {
+        Dual::constant(self.value.round())
+    }
+    fn trunc(self) -> Self {
+        Dual::constant(self.value.trunc())
+    }
+
+    fn fract(self) -> Self {
+        Dual::new(self.value.fract(), self.deriv)
+    }
+    fn im(self) -> Self {
+        Self::zero()
+    }
+    fn re(self) -> Self {
+        self
+    }
+    fn conj(self) -> Self {
+        self
+    }
+    fn modulus(self) -> Self {
+        self.abs()
+    }
+}
+
+impl<R: Real> Real for Dual<R> {
+    fn approx(self) -> f64 {
+        self.value.approx()
+    }
+    fn repr(f: f64) -> Self {
+        Dual::constant(R::repr(f))
+    }
+}
+
+impl<R: Real> InnerProductSpace<Dual<R>> for Dual<R> {
+    fn inner_product(self, rhs: Self) -> Self {
+        self * rhs
+    }
+    fn norm(self) -> Self {
+        self.abs()
+    }
+}
+
+//The natural numbers and integers of `Dual<R>`
+//
+//`maths_traits` needs each real number type to come with integer types that convert back to
+//it, so these are just `u64` and `i64` tagged with the real number type.
+pub struct DualNatural<R>(pub u64, PhantomData<R>);
+
+pub struct DualInteger<R>(pub i64, PhantomData<R>);
+
+macro_rules! impl_dual_int {
+    ($($name:ident($int:ident) from [$($from:ident)*] try_from [$($prim:ident)*];)*) => {$(
+        impl<R> $name<R> {
+            pub fn new(n: $int) -> Self {
+                $name(n, PhantomData)
+            }
+        }
+
+        $(
+            impl<R> From<$from> for $name<R> {
+                fn from(n: $from) -> Self {
+                    Self::new(n.into())
+                }
+            }
+        )*
+
+        $(
+            impl<R> TryFrom<$prim> for $name<R> {
+                type Error = <$int as TryFrom<$prim>>::Error;
+                fn try_from(n: $prim) -> Result<Self, Self::Error> {
+                    $int::try_from(n).map(Self::new)
+                }
+            }
+        )*
+
+        impl<R> Clone for $name<R> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<R> Copy for $name<R> {}
+
+        impl<R> PartialEq for $name<R> {
+            fn eq(&self, rhs: &Self) -> bool {
+                self.0 == rhs.0
+            }
+        }
+
+        impl<R> Eq for $name<R> {}
+
+        impl<R> PartialOrd for $name<R> {
+            fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+                Some(self.cmp(rhs))
+            }
+        }
+
+        impl<R> Ord for $name<R> {
+            fn cmp(&self, rhs: &Self) -> Ordering {
+                self.0.cmp(&rhs.0)
+            }
+        }
+
+        impl<R> Hash for $name<R> {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.hash(state)
+            }
+        }
+
+        impl<R> Display for $name<R> {
+            fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+                Display::fmt(&self.0, f)
+            }
+        }
+
+        impl<R> Debug for $name<R> {
+            fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+                Display::fmt(self, f)
+            }
+        }
+
+        impl_dual_int!(@ops $name
+            Add::add AddAssign::add_assign, Sub::sub SubAssign::sub_assign,
+            Mul::mul MulAssign::mul_assign, Div::div DivAssign::div_assign,
+            Rem::rem RemAssign::rem_assign
+        );
+
+        impl<R> Zero for $name<R> {
+            fn zero() -> Self {
+                Self::new(0)
+            }
+            fn is_zero(&self) -> bool {
+                self.0 == 0
+            }
+        }
+
+        impl<R> One for $name<R> {
+            fn one() -> Self {
+                Self::new(1)
+            }
+        }
+
+        impl<R> AddAssociative for $name<R> {}
+        impl<R> AddCommutative for $name<R> {}
+        impl<R> MulAssociative for $name<R> {}
+        impl<R> MulCommutative for $name<R> {}
+        impl<R> Distributive for $name<R> {}
+        impl<R> NoZeroDivisors for $name<R> {}
+        impl<R> UniquelyFactorizable for $name<R> {}
+        impl<R> AddOrdered for $name<R> {}
+        impl<R> MulOrdered for $name<R> {}
+        impl<R> ArchimedeanProperty for $name<R> {}
+
+        impl<R> Sign for $name<R> {
+            fn signum(self) -> Self {
+                Self::new(Sign::signum(self.0))
+            }
+            fn abs(self) -> Self {
+                Self::new(Sign::abs(self.0))
+            }
+        }
+
+        impl<R> ArchimedeanDiv for $name<R> {
+            fn embed_nat<N: Natural>(n: N) -> Self {
+                Self::new($int::embed_nat(n))
+            }
+            fn div_arch(self, rhs: Self) -> Self {
+                Self::new(self.0.div_arch(rhs.0))
+            }
+            fn rem_arch(self, rhs: Self) -> Self {
+                Self::new(self.0.rem_arch(rhs.0))
+            }
+            fn div_alg_arch(self, rhs: Self) -> (Self, Self) {
+                let (q, r) = self.0.div_alg_arch(rhs.0);
+                (Self::new(q), Self::new(r))
+            }
+        }
+
+        impl<R> Divisibility for $name<R> {
+            fn divides(self, rhs: Self) -> bool {
+                self.0.divides(rhs.0)
+            }
+            fn divide(self, rhs: Self) -> Option<Self> {
+                self.0.divide(rhs.0).map(Self::new)
+            }
+            fn unit(&self) -> bool {
+                self.0.unit()
+            }
+            fn inverse(self) -> Option<Self> {
+                self.0.inverse().map(Self::new)
+            }
+        }
+
+        impl<R> GCD for $name<R> {
+            fn gcd(self, rhs: Self) -> Self {
+                Self::new(self.0.gcd(rhs.0))
+            }
+            fn lcm(self, rhs: Self) -> Self {
+                Self::new(self.0.lcm(rhs.0))
+            }
+        }
+
+        impl<R> EuclideanDiv for $name<R> {
+            type Naturals = DualNatural<R>;
+            fn euclid_norm(&self) -> DualNatural<R> {
+                DualNatural::new(self.0.euclid_norm())
+            }
+            fn div_euc(self, rhs: Self) -> Self {
+                Self::new(self.0.div_euc(rhs.0))
+            }
+            fn rem_euc(self, rhs: Self) -> Self {
+                Self::new(self.0.rem_euc(rhs.0))
+            }
+            fn div_alg(self, rhs: Self) -> (Self, Self) {
+                let (q, r) = self.0.div_alg(rhs.0);
+                (Self::new(q), Self::new(r))
+            }
+        }
+
+        impl<R> Primality for $name<R> {
+            fn irreducible(&self) -> bool {
+                self.0.irreducible()
+            }
+            fn prime(&self) -> bool {
+                self.0.prime()
+            }
+        }
+
+        impl<R> ToPrimitive for $name<R> {
+            fn to_i64(&self) -> Option<i64> {
+                self.0.to_i64()
+            }
+            fn to_u64(&self) -> Option<u64> {
+                self.0.to_u64()
+            }
+        }
+
+        impl<R> FromPrimitive for $name<R> {
+            fn from_i64(n: i64) -> Option<Self> {
+                $int::from_i64(n).map(Self::new)
+            }
+            fn from_u64(n: u64) -> Option<Self> {
+                $int::from_u64(n).map(Self::new)
+            }
+        }
+
+        impl<R> IntegerSubset for $name<R> {
+            type Signed = DualInteger<R>;
+            type Unsigned = DualNatural<R>;
+            fn as_signed(self) -> DualInteger<R> {
+                DualInteger::new(self.0 as i64)
+            }
+            fn as_unsigned(self) -> DualNatural<R> {
+                DualNatural::new(self.0 as u64)
+            }
+        }
+    )*};
+
+    (@ops $name:ident $($Op:ident::$op:ident $Assign:ident::$assign:ident),*) => {$(
+        impl<R> $Op for $name<R> {
+            type Output = Self;
+            fn $op(self, rhs: Self) -> Self {
+                Self::new($Op::$op(self.0, rhs.0))
+            }
+        }
+
+        impl<R> $Assign for $name<R> {
+            fn $assign(&mut self, rhs: Self) {
+                $Assign::$assign(&mut self.0, rhs.0)
+            }
+        }
+    )*};
+}
+
+impl_dual_int!(
+    DualNatural(u64) from [u8 u16 u32 u64] try_from [i8 i16 i32 i64 i128 u128];
+    DualInteger(i64) from [i8 u8 i16 u16 i32 u32 i64] try_from [u64 i128 u128];
+);
+
+impl<R> Neg for DualInteger<R> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::new(-self.0)
+    }
+}
+
+impl<R> Natural for DualNatural<R> {}
+impl<R> Integer for DualInteger<R> {}
+
+impl<R: Real> ComplexSubset for DualNatural<R> {
+    type Real = Dual<R>;
+    type Natural = Self;
+    type Integer = DualInteger<R>;
+
+    fn as_real(self) -> Dual<R> {
+        Dual::embed_nat(self.0)
+    }
+    fn as_natural(self) -> Self {
+        self
+    }
+    fn as_integer(self) -> DualInteger<R> {
+        self.as_signed()
+    }
+
+    fn floor(self) -> Self {
+        self
+    }
+    fn ceil(self) -> Self {
+        self
+    }
+    fn round(self) -> Self {
+        self
+    }
+    fn trunc(self) -> Self {
+        self
+    }
+    fn fract(self) -> Self {
+        Self::zero()
+    }
+    fn im(self) -> Self {
+        Self::zero()
+    }
+    fn re(self) -> Self {
+        self
+    }
+    fn conj(self) -> Self {
+        self
+    }
+}
+
+impl<R: Real> ComplexSubset for DualInteger<R> {
+    type Real = Dual<R>;
+    type Natural = DualNatural<R>;
+    type Integer = Self;
+
+    fn as_real(self) -> Dual<R> {
+        let n = Dual::embed_nat(self.0.unsigned_abs());
+        if self.0 < 0 {
+            -n
+        } else {
+            n
+        }
+    }
+    fn as_natural(self) -> DualNatural<R> {
+        self.as_unsigned()
+    }
+    fn as_integer(self) -> Self {
+        self
+    }
+
+    fn floor(self) -> Self {
+        self
+    }
+    fn ceil(self) -> Self {
+        self
+    }
+    fn round(self) -> Self {
+        self
+    }
+    fn trunc(self) -> Self {
+        self
+    }
+    fn fract(self) -> Self {
+        Self::zero()
+    }
+    fn im(self) -> Self {
+        Self::zero()
+    }
+    fn re(self) -> Self {
+        self
+    }
+    fn conj(self) -> Self {
+        self
+    }
+}
+
+//A state whose components can be made into dual numbers
+//
+//This is what lets `DenseJacobian::autodiff()` differentiate a right-hand side that is
+//written generically over the real number type.
+pub trait DualState<R>: Sized {
+    type Dual;
+    fn dim(&self) -> usize;
+    //the state as dual numbers with every derivative zero except that of the `i`th component
+    fn seed(&self, i: usize) -> Self::Dual;
+    //writes the derivative parts of each component into `out`
+    fn derivs(dual: &Self::Dual, out: &mut [R]);
+    //the state as dual numbers with derivatives given by `tangent`, for directional derivatives
+    fn lift(&self, tangent: &Self) -> Self::Dual;
+    //the values and the derivatives of a state of dual numbers
+    fn split(dual: Self::Dual) -> (Self, Self);
+}
+
+impl<R: Real> DualState<R> for R {
+    type Dual = Dual<R>;
+    fn dim(&self) -> usize {
+        1
+    }
+    fn seed(&self, _i: usize) -> Dual<R> {
+        Dual::variable(self.clone())
+    }
+    fn derivs(dual: &Dual<R>, out: &mut [R]) {
+        out[0] = dual.deriv.clone();
+    }
+    fn lift(&self, tangent: &R) -> Dual<R> {
+        Dual::new(self.clone(), tangent.clone())
+    }
+    fn split(dual: Dual<R>) -> (R, R) {
+        (dual.value, dual.deriv)
+    }
+}
+
+impl<R: Real, const N: usize> DualState<R> for SVector<R, N> {
+    type Dual = SVector<Dual<R>, N>;
+    fn dim(&self) -> usize {
+        N
+    }
+    fn seed(&self, i: usize) -> SVector<Dual<R>, N> {
+        SVector(::core::array::from_fn(|j| {
+            Dual::new(self[j].clone(), if i == j { R::one() } else { R::zero() })
+        }))
+    }
+    fn derivs(dual: &SVector<Dual<R>, N>, out: &mut [R]) {
+        for (out, x) in out.iter_mut().zip(dual.iter()) {
+            *out = x.deriv.clone();
+        }
+    }
+    fn lift(&self, tangent: &Self) -> SVector<Dual<R>, N> {
+        SVector(::core::array::from_fn(|i| {
+            Dual::new(self[i].clone(), tangent[i].clone())
+        }))
+    }
+    fn split(dual: SVector<Dual<R>, N>) -> (Self, Self) {
+        (
+            SVector(dual.0.clone().map(|x| x.value)),
+            SVector(dual.0.map(|x| x.deriv)),
+        )
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<R: Real> DualState<R> for DVector<R> {
+    type Dual = DVector<Dual<R>>;
+    fn dim(&self) -> usize {
+        self.len()
+    }
+    fn seed(&self, i: usize) -> DVector<Dual<R>> {
+        self.iter()
+            .enumerate()
+            .map(|(j, x)| Dual::new(x.clone(), if i == j { R::one() } else { R::zero() }))
+            .collect()
+    }
+    //missing components count as zero, as they do everywhere else for `DVector`
+    fn derivs(dual: &DVector<Dual<R>>, out: &mut [R]) {
+        for (i, out) in out.iter_mut().enumerate() {
+            *out = dual.get(i).map_or(R::zero(), |x| x.deriv.clone());
+        }
+    }
+    fn lift(&self, tangent: &Self) -> DVector<Dual<R>> {
+        self.iter()
+            .enumerate()
+            .map(|(i, x)| Dual::new(x.clone(), tangent.get(i).cloned().unwrap_or(R::zero())))
+            .collect()
+    }
+    fn split(dual: DVector<Dual<R>>) -> (Self, Self) {
+        let (values, derivs) = dual.0.into_iter().map(|x| (x.value, x.deriv)).unzip();
+        (DVector(values), DVector(derivs))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-12 * (1.0 + b.abs())
+    }
+
+    //checks the value and first two derivatives of a function written generically over the real
+    //number type against the given ones
+    macro_rules! check {
+        ($x:expr, |$v:ident| $f:expr, $d1:expr, $d2:expr) => {{
+            fn f<T: Real>($v: T) -> T {
+                $f
+            }
+            let x: f64 = $x;
+            let (value, d1) = derivative(f::<Dual<f64>>, x);
+            let (value2, d1_2, d2) = second_derivative(f::<HyperDual<f64>>, x);
+            let expected = (f(x), $d1, $d2);
+            assert!(
+                close(value, expected.0) && close(value2, expected.0),
+                "{}",
+                stringify!($f)
+            );
+            assert!(
+                close(d1, expected.1) && close(d1_2, expected.1),
+                "{}",
+                stringify!($f)
+            );
+            assert!(
+                close(d2, expected.2),
+                "{}: {} {}",
+                stringify!($f),
+                d2,
+                expected.2
+            );
+        }};
+    }
+
+    #[test]
+    fn derivatives_of_elementary_functions() {
+        let x = 0.7f64;
+        check!(x, |x| x.exp(), x.exp(), x.exp());
+        check!(x, |x| x.ln(), 1.0 / x, -1.0 / (x * x));
+        check!(x, |x| x.sin(), x.cos(), -x.sin());
+        check!(x, |x| x.cos(), -x.sin(), -x.cos());
+        let tan = x.tan();
+        check!(
+            x,
+            |x| x.tan(),
+            1.0 + tan * tan,
+            2.0 * tan * (1.0 + tan * tan)
+        );
+        check!(x, |x| x.sqrt(), 0.5 / x.sqrt(), -0.25 / (x * x.sqrt()));
+    }
+
+    #[test]
+    fn derivatives_of_powers() {
+        let x = 0.7f64;
+        check!(
+            x,
+            |x| x.pow(T::repr(2.5)),
+            2.5 * x.powf(1.5),
+            3.75 * x.sqrt()
+        );
+        let ln2 = 2.0f64.ln();
+        check!(
+            x,
+            |x| T::repr(2.0).pow(x),
+            2.0f64.powf(x) * ln2,
+            2.0f64.powf(x) * ln2 * ln2
+        );
+        //a negative base with a constant integer power
+        check!(-x, |x| x.pow(T::repr(3.0)), 3.0 * x * x, 6.0 * -x);
+    }
+
+    #[test]
+    fn derivatives_of_functions_of_two_variables() {
+        let (x, c) = (0.7f64, 1.3f64);
+        let r2 = x * x + c * c;
+        check!(
+            x,
+            |x| T::atan2(x, T::repr(1.3)),
+            c / r2,
+            -2.0 * c * x / (r2 * r2)
+        );
+        check!(
+            x,
+            |x| T::atan2(T::repr(1.3), x),
+            -c / r2,
+            2.0 * c * x / (r2 * r2)
+        );
+
+        //`Real` has no `hypot`, so it is written out
+        let h = r2.sqrt();
+        check!(
+            x,
+            |x| (x.clone() * x + T::repr(1.69)).sqrt(),
+            x / h,
+            c * c / (h * h * h)
+        );
+    }
+}
@@ -0,0 +1,537 @@
+use super::*;
+
+use core::fmt::{Debug, Display, Formatter};
+
+//Parameter estimation
+//
+//Fits the parameters of a `ParamRhs`, and optionally some of the initial conditions, to
+//observations of the solution by nonlinear least squares. Each iteration integrates the state
+//along with its sensitivities to every unknown (with `Sensitivity`), which give the Jacobian
+//of the residuals, and then takes a Gauss-Newton or Levenberg-Marquardt step. The forward
+//model can use any fixed-step or adaptive method, through a `Solver` stopping at each of the
+//observation times.
+
+//Observations of the solution at increasing times
+//
+//Only the given `components` of each value are compared with the solution (all of them by
+//default), and with `sigma` the residuals of each component are divided by its standard
+//deviation.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Observations<R, S> {
+    pub times: Vec<R>,
+    pub values: Vec<S>,
+    pub components: Option<Vec<usize>>,
+    pub sigma: Option<Vec<R>>,
+}
+
+impl<R, S> Observations<R, S> {
+    pub fn new(times: Vec<R>, values: Vec<S>) -> Self {
+        assert_eq!(
+            times.len(),
+            values.len(),
+            "there must be a value for each observation time"
+        );
+        Observations {
+            times,
+            values,
+            components: None,
+            sigma: None,
+        }
+    }
+
+    //the components of the state that were observed
+    pub fn components(mut self, components: Vec<usize>) -> Self {
+        self.components = Some(components);
+        self
+    }
+
+    //the standard deviation of each observed component
+    pub fn sigma(mut self, sigma: Vec<R>) -> Self {
+        self.sigma = Some(sigma);
+        self
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FitAlgorithm {
+    //takes every full step, which converges quickly close to the fit but can diverge from
+    //further away
+    GaussNewton,
+    //damps the steps towards gradient descent until they reduce the residuals
+    #[default]
+    LevenbergMarquardt,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FitError<R> {
+    Integration(StepError<R>),
+    //the normal equations couldn't be solved, usually because some unknown has no effect on
+    //the observations
+    Singular,
+}
+
+impl<R> From<StepError<R>> for FitError<R> {
+    fn from(err: StepError<R>) -> Self {
+        FitError::Integration(err)
+    }
+}
+
+impl<R: Real> Display for FitError<R> {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        match self {
+            FitError::Integration(err) => write!(f, "Integration of the model failed: {}", err),
+            FitError::Singular => write!(
+                f,
+                "The normal equations are singular, so not every unknown can be determined"
+            ),
+        }
+    }
+}
+
+impl<R: Real> Debug for FitError<R> {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl<R: Real> ::core::error::Error for FitError<R> {}
+
+//The result of a fit
+//
+//The unknowns are the parameters followed by the fitted initial conditions, in the order they
+//were given, and `covariance` is their estimated covariance matrix (stored by rows). It is
+//scaled by the variance of the residuals, so it doesn't depend on `sigma` being absolute, and
+//is missing if there aren't more residuals than unknowns or the normal equations are singular.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fit<R, S> {
+    pub params: Vec<R>,
+    pub initial: S,
+    pub residuals: Vec<R>,
+    pub sum_of_squares: R,
+    pub covariance: Option<Vec<Vec<R>>>,
+    pub iterations: usize,
+    pub converged: bool,
+}
+
+impl<R: Real, S> Fit<R, S> {
+    //the standard error of each unknown, from the diagonal of the covariance
+    pub fn standard_errors(&self) -> Option<Vec<R>> {
+        self.covariance.as_ref().map(|covariance| {
+            covariance
+                .iter()
+                .enumerate()
+                .map(|(i, row)| row[i].clone().sqrt())
+                .collect()
+        })
+    }
+}
+
+//Fits parameters and initial conditions at `t0` by least squares
+//
+//By default, this uses Levenberg-Marquardt and only fits the parameters, stopping once an
+//iteration changes the sum of squares or each unknown by less than the relative `tolerance`.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LeastSquares<R> {
+    t0: R,
+    algorithm: FitAlgorithm,
+    initial: Vec<usize>,
+    max_iterations: usize,
+    tolerance: R,
+}
+
+fn sum_of_squares<R: Real>(residuals: &[R]) -> R {
+    residuals
+        .iter()
+        .fold(R::zero(), |sum, r| sum + r.clone() * r.clone())
+}
+
+impl<R: Real> LeastSquares<R> {
+    pub fn new(t0: R) -> Self {
+        LeastSquares {
+            t0,
+            algorithm: Default::default(),
+            initial: Vec::new(),
+            max_iterations: 100,
+            tolerance: R::repr(1e-10),
+        }
+    }
+
+    pub fn algorithm(mut self, algorithm: FitAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    //the components of the initial state to fit along with the parameters
+    pub fn fit_initial(mut self, components: Vec<usize>) -> Self {
+        self.initial = components;
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn tolerance(mut self, tolerance: R) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    //the solver stopping at each observation
+    fn solver<S>(&self, data: &Observations<R, S>) -> Solver<R> {
+        assert!(
+            !data.times.is_empty(),
+            "there must be at least one observation"
+        );
+        assert!(
+            data.times[0] >= self.t0 && data.times.windows(2).all(|t| t[0] < t[1]),
+            "the observation times must be increasing and not before t0"
+        );
+        Solver::new(self.t0.clone(), data.times.last().unwrap().clone())
+            .expect("the observations don't start before t0")
+            .t_eval(data.times.clone())
+    }
+
+    //fits with the forward model integrated in fixed steps of `dt`
+    pub fn fit<I, S, P>(
+        &self,
+        method: &I,
+        dt: R,
+        problem: P,
+        y0: S,
+        data: &Observations<R, S>,
+    ) -> Result<Fit<R, S>, FitError<R>>
+    where
+        I: Integrator + ?Sized,
+        S: State<R> + InnerProductSpace<R> + DualState<R>,
+        P: ParamRhs<R, S>,
+    {
+        let solver = self.solver(data);
+        self.fit_with(problem, y0, data, |rhs, y0| {
+            Ok(solver.solve(method, y0, dt.clone(), RhsRef(rhs)))
+        })
+    }
+
+    //fits with the forward model integrated adaptively, with the given metric (usually a
+    //`SensitivityMetric`) controlling the error of the state and its sensitivities
+    pub fn fit_adaptive<I, S, P, M>(
+        &self,
+        method: &I,
+        ds: R,
+        d: M,
+        problem: P,
+        y0: S,
+        data: &Observations<R, S>,
+    ) -> Result<Fit<R, S>, FitError<R>>
+    where
+        I: AdaptiveIntegrator + ?Sized,
+        S: State<R> + InnerProductSpace<R> + DualState<R>,
+        P: ParamRhs<R, S>,
+        M: Metric<SensitivityState<S>, R> + Clone,
+    {
+        let solver = self.solver(data);
+        self.fit_with(problem, y0, data, |rhs, y0| {
+            solver.solve_adaptive(method, y0, ds.clone(), RhsRef(rhs), d.clone())
+        })
+    }
+
+    fn fit_with<S, P, F>(
+        &self,
+        problem: P,
+        y0: S,
+        data: &Observations<R, S>,
+        forward: F,
+    ) -> Result<Fit<R, S>, FitError<R>>
+    where
+        S: State<R> + InnerProductSpace<R> + DualState<R>,
+        P: ParamRhs<R, S>,
+        F: Fn(
+            &Sensitivity<P>,
+            SensitivityState<S>,
+        ) -> Result<Trajectory<R, (), SensitivityState<S>>, StepError<R>>,
+    {
+        let component = |y: &S, c: usize| y.clone().inner_product(unit(y, c));
+        let dim = y0.dim();
+        assert!(
+            self.initial.iter().all(|&c| c < dim),
+            "the fitted initial conditions must be components of the state"
+        );
+
+        let observed = data
+            .components
+            .clone()
+            .unwrap_or_else(|| (0..dim).collect());
+        let weights: Vec<R> = match &data.sigma {
+            Some(sigma) => {
+                assert_eq!(
+                    sigma.len(),
+                    observed.len(),
+                    "there must be a standard deviation for each observed component"
+                );
+                sigma.iter().map(|s| R::one() / s.clone()).collect()
+            }
+            None => vec![R::one(); observed.len()],
+        };
+
+        let mut rhs = Sensitivity(problem);
+        let params = rhs.0.params().len();
+        let n = params + self.initial.len();
+        let m = data.times.len() * observed.len();
+
+        //the unknowns, which start from the given parameters and initial conditions
+        let mut x: Vec<R> = rhs.0.params().to_vec();
+        x.extend(self.initial.iter().map(|&c| component(&y0, c)));
+
+        let initial_state = |x: &[R]| {
+            self.initial
+                .iter()
+                .zip(&x[params..])
+                .fold(y0.clone(), |y, (&c, x_c)| {
+                    let shift = x_c.clone() - component(&y, c);
+                    y + unit(&y0, c) * shift
+                })
+        };
+
+        //the residuals and their Jacobian (stored by rows) at the given unknowns
+        let evaluate = |rhs: &mut Sensitivity<P>, x: &[R]| {
+            rhs.0.params_mut().clone_from_slice(&x[..params]);
+            let mut s = vec![S::zero(); params];
+            s.extend(self.initial.iter().map(|&c| unit(&y0, c)));
+            let solution = forward(rhs, SensitivityState::new(initial_state(x), s))?;
+
+            let mut residuals = Vec::with_capacity(m);
+            let mut jacobian = Vec::with_capacity(m * n);
+            for (state, value) in solution.states.iter().zip(&data.values) {
+                for (&c, w) in observed.iter().zip(&weights) {
+                    let r = component(&state.y, c) - component(value, c);
+                    residuals.push(r * w.clone());
+                    for j in 0..n {
+                        let ds = state.s.get(j).map_or(R::zero(), |s| component(s, c));
+                        jacobian.push(ds * w.clone());
+                    }
+                }
+            }
+            Ok::<_, StepError<R>>((residuals, jacobian))
+        };
+
+        //the normal equations `J^T J` and `J^T r`
+        let normal = |residuals: &[R], jacobian: &[R]| {
+            let mut a = vec![R::zero(); n * n];
+            let mut b = vec![R::zero(); n];
+            for (r, row) in residuals.iter().zip(jacobian.chunks(n.max(1))) {
+                for i in 0..n {
+                    b[i] += row[i].clone() * r.clone();
+                    for j in 0..n {
+                        a[i * n + j] += row[i].clone() * row[j].clone();
+                    }
+                }
+            }
+            (a, b)
+        };
+
+        let (mut residuals, mut jacobian) = evaluate(&mut rhs, &x)?;
+        let mut sum = sum_of_squares(&residuals);
+        let ten = R::embed_nat(10u64);
+        let mut damping = match self.algorithm {
+            FitAlgorithm::GaussNewton => R::zero(),
+            FitAlgorithm::LevenbergMarquardt => R::repr(1e-3),
+        };
+
+        let mut iterations = 0;
+        let mut converged = false;
+        while !converged && iterations < self.max_iterations {
+            iterations += 1;
+            let (a, b) = normal(&residuals, &jacobian);
+
+            //tries steps with more and more damping until one reduces the residuals
+            let (mut error, mut integrated) = (None, false);
+            let accepted = loop {
+                let mut shifted = a.clone();
+                for i in 0..n {
+                    shifted[i * n + i] += damping.clone() * a[i * n + i].clone();
+                }
+                let mut step: Vec<R> = b.iter().map(|b| -b.clone()).collect();
+                let trial = linalg::solve(n, &mut shifted, &mut step).then(|| {
+                    let x: Vec<R> = x
+                        .iter()
+                        .zip(&step)
+                        .map(|(x, dx)| x.clone() + dx.clone())
+                        .collect();
+                    (evaluate(&mut rhs, &x), x)
+                });
+
+                match (self.algorithm, trial) {
+                    (_, None) => return Err(FitError::Singular),
+                    (FitAlgorithm::GaussNewton, Some((trial, x))) => break Some((step, trial?, x)),
+                    (FitAlgorithm::LevenbergMarquardt, Some((Ok(trial), x)))
+                        if sum_of_squares(&trial.0) <= sum =>
+                    {
+                        damping /= ten.clone();
+                        break Some((step, trial, x));
+                    }
+                    (_, Some((trial, _))) => {
+                        match trial {
+                            Ok(_) => integrated = true,
+                            Err(err) => error = Some(err),
+                        }
+                        damping *= ten.clone();
+                        if damping > R::repr(1e16) {
+                            break None;
+                        }
+                    }
+                }
+            };
+
+            //when no step reduces the residuals, the fit stops where it is without having
+            //converged, or fails if none of the steps could even be integrated
+            let Some((step, (new_residuals, new_jacobian), new_x)) = accepted else {
+                if let (false, Some(err)) = (integrated, error) {
+                    return Err(err.into());
+                }
+                break;
+            };
+            let new_sum = sum_of_squares(&new_residuals);
+            let small_step = step.iter().zip(&x).all(|(dx, x)| {
+                dx.clone().abs()
+                    <= self.tolerance.clone() * (x.clone().abs() + self.tolerance.clone())
+            });
+            let change = (sum.clone() - new_sum.clone()).abs();
+            converged = small_step || change <= self.tolerance.clone() * sum.clone();
+
+            (x, residuals, jacobian, sum) = (new_x, new_residuals, new_jacobian, new_sum);
+        }
+
+        //the inverse of `J^T J`, scaled by the variance of the residuals
+        let covariance = if m > n {
+            let (a, _) = normal(&residuals, &jacobian);
+            let variance = sum.clone() / R::embed_nat((m - n) as u64);
+            (0..n)
+                .map(|i| {
+                    let mut column = vec![R::zero(); n];
+                    column[i] = R::one();
+                    linalg::solve(n, &mut a.clone(), &mut column).then(|| {
+                        column
+                            .into_iter()
+                            .map(|x| x * variance.clone())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Option<Vec<_>>>()
+        } else {
+            None
+        };
+
+        Ok(Fit {
+            initial: initial_state(&x),
+            params: x.into_iter().take(params).collect(),
+            residuals,
+            sum_of_squares: sum,
+            covariance,
+            iterations,
+            converged,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn decay<T: Real>(_t: T, y: &T, p: &[T]) -> T {
+        -p[0].clone() * y.clone()
+    }
+
+    //exact observations of `y0*exp(-pt)`
+    fn observations(p: f64, y0: f64, times: Vec<f64>) -> Observations<f64, f64> {
+        let values = times.iter().map(|t| y0 * (-p * t).exp()).collect();
+        Observations::new(times, values)
+    }
+
+    #[test]
+    fn fits_recover_the_parameters_and_initial_conditions() {
+        let times = (1..=10).map(|i| f64::from(i) * 0.5).collect();
+        let data = observations(0.7, 1.5, times);
+
+        for algorithm in [FitAlgorithm::GaussNewton, FitAlgorithm::LevenbergMarquardt] {
+            let problem = AutoDiffJacobians::new(decay::<Dual<f64>>, vec![0.4]);
+            let fit = LeastSquares::new(0.0)
+                .algorithm(algorithm)
+                .fit_initial(vec![0])
+                .fit(&RK4, 0.01, problem, 1.0, &data)
+                .unwrap();
+            assert!(fit.converged, "{fit:?}");
+            assert!((fit.params[0] - 0.7).abs() < 1e-6, "{fit:?}");
+            assert!((fit.initial - 1.5).abs() < 1e-6, "{fit:?}");
+            assert!(fit.covariance.is_some(), "{fit:?}");
+
+            let problem = AutoDiffJacobians::new(decay::<Dual<f64>>, vec![0.4]);
+            let fit = LeastSquares::new(0.0)
+                .algorithm(algorithm)
+                .fit_initial(vec![0])
+                .fit_adaptive(
+                    &DORMAND_PRINCE,
+                    1e-10,
+                    SensitivityMetric::new(InnerProductMetric, true),
+                    problem,
+                    1.0,
+                    &data,
+                )
+                .unwrap();
+            assert!(fit.converged, "{fit:?}");
+            assert!((fit.params[0] - 0.7).abs() < 1e-6, "{fit:?}");
+            assert!((fit.initial - 1.5).abs() < 1e-6, "{fit:?}");
+        }
+    }
+
+    #[test]
+    fn there_is_no_covariance_without_more_residuals_than_unknowns() {
+        let data = observations(0.7, 1.5, vec![1.0, 2.0]);
+        let problem = AutoDiffJacobians::new(decay::<Dual<f64>>, vec![0.4]);
+        let fit = LeastSquares::new(0.0)
+            .fit_initial(vec![0])
+            .fit(&RK4, 0.01, problem, 1.0, &data)
+            .unwrap();
+        assert!((fit.params[0] - 0.7).abs() < 1e-6, "{fit:?}");
+        assert_eq!(fit.covariance, None);
+    }
+
+    #[test]
+    fn unknowns_without_an_effect_are_singular() {
+        let data = observations(0.7, 1.5, vec![1.0, 2.0, 3.0]);
+        for algorithm in [FitAlgorithm::GaussNewton, FitAlgorithm::LevenbergMarquardt] {
+            //the second parameter is never used
+            let problem = AutoDiffJacobians::new(decay::<Dual<f64>>, vec![0.4, 1.0]);
+            let fit = LeastSquares::new(0.0)
+                .algorithm(algorithm)
+                .fit(&RK4, 0.01, problem, 1.5, &data);
+            assert!(matches!(fit, Err(FitError::Singular)), "{fit:?}");
+        }
+    }
+
+    #[test]
+    fn fits_fail_when_no_step_can_be_integrated() {
+        //observations so far off that even the most damped steps change the parameter
+        let data = Observations::new(vec![1.0, 2.0, 3.0], vec![1e6; 3]);
+
+        //the model can only be integrated with the parameter it starts from
+        let problem = AnalyticJacobians::new(
+            |_t, y: &f64, p: &[f64]| if p[0] == 0.4 { -p[0] * y } else { f64::NAN },
+            |_t, _y: &f64, p: &[f64]| DenseJacobian::from_rows(vec![vec![-p[0]]]),
+            |_t, y: &f64, _p: &[f64]| vec![-y],
+            vec![0.4],
+        );
+        let fit = LeastSquares::new(0.0).fit_adaptive(
+            &DORMAND_PRINCE,
+            1e-8,
+            SensitivityMetric::new(InnerProductMetric, false),
+            problem,
+            1.5,
+            &data,
+        );
+        assert!(matches!(fit, Err(FitError::Integration(_))), "{fit:?}");
+    }
+}
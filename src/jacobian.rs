@@ -0,0 +1,178 @@
+use super::*;
+
+use core::ops::{Index, IndexMut};
+
+//The Jacobian of a right-hand side with respect to the state
+//
+//The sensitivities and adjoints only use it through products with a vector. It can also solve
+//the shifted systems `(I - gamma*J) x = b` that implicit and Rosenbrock methods are built on,
+//though the crate doesn't have any such methods yet.
+pub trait Jacobian<R, S> {
+    fn apply(&self, v: &S) -> S;
+    fn solve_shifted(&self, gamma: R, b: &S) -> Option<S>;
+}
+
+//a dense square Jacobian, stored by rows
+#[derive(Clone, PartialEq, Debug)]
+pub struct DenseJacobian<R> {
+    n: usize,
+    entries: Vec<R>,
+}
+
+impl<R: Real> DenseJacobian<R> {
+    pub fn zeros(n: usize) -> Self {
+        DenseJacobian {
+            n,
+            entries: vec![R::zero(); n * n],
+        }
+    }
+
+    pub fn identity(n: usize) -> Self {
+        let mut j = Self::zeros(n);
+        for i in 0..n {
+            j[(i, i)] = R::one();
+        }
+        j
+    }
+
+    //panics if the rows aren't all of the same length as there are rows
+    pub fn from_rows(rows: Vec<Vec<R>>) -> Self {
+        let n = rows.len();
+        assert!(
+            rows.iter().all(|row| row.len() == n),
+            "a Jacobian must be square"
+        );
+        DenseJacobian {
+            n,
+            entries: rows.into_iter().flatten().collect(),
+        }
+    }
+
+    //the Jacobian of `f` at `(t, y)` by automatic differentiation, using one evaluation of `f`
+    //on dual numbers per component of the state
+    //
+    //`f` is usually the same generic function as the right-hand side given to the integrator,
+    //just with `Dual<R>` as the real number type, so the Jacobian never has to be written out
+    //by hand
+    pub fn autodiff<D, S: DualState<R>, F: Fn(Dual<R>, S::Dual) -> (D, S::Dual)>(
+        t: R,
+        y: &S,
+        f: F,
+    ) -> Self {
+        let n = y.dim();
+        let mut j = Self::zeros(n);
+        let mut column = vec![R::zero(); n];
+        for col in 0..n {
+            let (_, dy) = f(Dual::constant(t.clone()), y.seed(col));
+            S::derivs(&dy, &mut column);
+            for (row, x) in column.iter().enumerate() {
+                j[(row, col)] = x.clone();
+            }
+        }
+        j
+    }
+
+    pub fn dim(&self) -> usize {
+        self.n
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[R]> {
+        self.entries.chunks(self.n.max(1))
+    }
+
+    fn apply_slice(&self, v: &[R], out: &mut [R]) {
+        for (out, row) in out.iter_mut().zip(self.rows()) {
+            *out = row
+                .iter()
+                .zip(v)
+                .fold(R::zero(), |sum, (a, x)| sum + a.clone() * x.clone());
+        }
+    }
+
+    fn solve_shifted_slice(&self, gamma: R, b: &mut [R]) -> bool {
+        assert_eq!(
+            b.len(),
+            self.n,
+            "the vector must have as many components as the Jacobian"
+        );
+        let mut a: Vec<R> = self
+            .entries
+            .iter()
+            .map(|x| -(x.clone() * gamma.clone()))
+            .collect();
+        for i in 0..self.n {
+            a[i * self.n + i] += R::one();
+        }
+        linalg::solve(self.n, &mut a, b)
+    }
+}
+
+impl<R> Index<(usize, usize)> for DenseJacobian<R> {
+    type Output = R;
+    fn index(&self, (i, j): (usize, usize)) -> &R {
+        assert!(j < self.n, "column out of bounds");
+        &self.entries[i * self.n + j]
+    }
+}
+
+impl<R> IndexMut<(usize, usize)> for DenseJacobian<R> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut R {
+        assert!(j < self.n, "column out of bounds");
+        &mut self.entries[i * self.n + j]
+    }
+}
+
+impl<R: Real> Jacobian<R, R> for DenseJacobian<R> {
+    fn apply(&self, v: &R) -> R {
+        self[(0, 0)].clone() * v.clone()
+    }
+    fn solve_shifted(&self, gamma: R, b: &R) -> Option<R> {
+        let mut x = [b.clone()];
+        self.solve_shifted_slice(gamma, &mut x)
+            .then(|| x[0].clone())
+    }
+}
+
+impl<R: Real, const N: usize> Jacobian<R, SVector<R, N>> for DenseJacobian<R> {
+    fn apply(&self, v: &SVector<R, N>) -> SVector<R, N> {
+        let mut out = v.clone();
+        self.apply_slice(v, &mut out);
+        out
+    }
+    fn solve_shifted(&self, gamma: R, b: &SVector<R, N>) -> Option<SVector<R, N>> {
+        let mut x = b.clone();
+        self.solve_shifted_slice(gamma, &mut x).then_some(x)
+    }
+}
+
+impl<R: Real> Jacobian<R, DVector<R>> for DenseJacobian<R> {
+    fn apply(&self, v: &DVector<R>) -> DVector<R> {
+        let mut out = DVector::zeros(self.n);
+        self.apply_slice(v, &mut out);
+        out
+    }
+    fn solve_shifted(&self, gamma: R, b: &DVector<R>) -> Option<DVector<R>> {
+        let mut x = b.clone();
+        x.0.resize(self.n, R::zero());
+        self.solve_shifted_slice(gamma, &mut x).then_some(x)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn f<T: Real>(t: T, y: SVector<T, 2>) -> ((), SVector<T, 2>) {
+        let (a, b) = (y[0].clone(), y[1].clone());
+        let dy = SVector([a.clone() * b.clone() + t.sin(), a.clone() * a - b.exp()]);
+        ((), dy)
+    }
+
+    #[test]
+    fn autodiff_jacobians_match_those_written_by_hand() {
+        let (t, y) = (0.3, SVector([0.7, -0.4]));
+        let jacobian = DenseJacobian::autodiff(t, &y, f::<Dual<f64>>);
+        let exact = DenseJacobian::from_rows(vec![vec![y[1], y[0]], vec![2.0 * y[0], -y[1].exp()]]);
+        assert_eq!(jacobian, exact);
+    }
+}
@@ -22,6 +22,53 @@
 //!  that computes the velocity in an admittedly convoluted way. And
 //!  `AdaptiveIntegrator` takes in a minimum error value instead of a time-step.
 //!
+//!  The derivative can either be a closure taking the state by value and returning
+//!  a new one or, for large states, a closure wrapped in `InPlace` that writes the
+//!  derivative into storage owned by the integrator.
+//!
+//!  The state can be any type implementing `VectorSpace` from `maths_traits` that can also
+//!  be added to from a reference (`State`), which lets the steppers combine their stages
+//!  without cloning them. For the common case of a vector of numbers, `SVector` (of a fixed
+//!  size) and `DVector` (of any size) are provided and also work with `InnerProductMetric`.
+//!
+//!  With the `nalgebra` or `ndarray` cargo features, `NalgebraVector` and `NdArray`
+//!  wrap the vectors and arrays of those crates in the same way, and `NalgebraJacobian`
+//!  and `NdJacobian` (like `DenseJacobian`) hold the Jacobians used for sensitivities.
+//!
+//!  With the `serde` feature, states, tableaux, solver configurations and trajectories can
+//!  be serialized, as can the `Checkpoint` of a step iterator, from which a long run can be
+//!  resumed with exactly the same steps as if it had never been interrupted.
+//!
+//!  Without the default `std` feature, the crate is `#![no_std]`. The `alloc` feature brings
+//!  back everything that needs to allocate (such as the boxed state buffers from `init()`,
+//!  the adaptive methods, and `Solver`). Without it, the fixed-step methods can still be
+//!  used with a buffer of `buffer_len()` slots provided by the caller and initialized with
+//!  `init_in()`. Note that `maths_traits` only implements `Real` for `f32` and `f64` with
+//!  `std`, so a `no_std` build needs its own real number type.
+//!
+//!  With `Dual` (a dual number type that is `Real` itself), any of the integrators can be run
+//!  on dual numbers to get the derivatives of the solution with respect to its initial
+//!  conditions or to the parameters of the system, and `HyperDual` does the same for second
+//!  derivatives. `DenseJacobian::autodiff()` uses them to find the Jacobian of a right-hand side
+//!  written generically over the real number type. (There are no implicit or Rosenbrock
+//!  methods in the crate yet to use those Jacobians when stepping.)
+//!
+//!  For fitting models, `Sensitivity` integrates the forward sensitivities `∂y/∂p` of the
+//!  solution to the parameters of a `ParamRhs` along with the state, using Jacobians that are
+//!  written by hand (`AnalyticJacobians`), found by finite differences, or found with dual
+//!  numbers. `SensitivityMetric` decides whether the sensitivities count towards the error.
+//!
+//!  When there are many parameters but only one quantity to optimize, `Adjoint` instead finds
+//!  the gradient of a `Cost` (a terminal cost plus the integral of a running cost) by
+//!  integrating the adjoint backward with any explicit Runge-Kutta method, either as the exact
+//!  discrete adjoint of the steps or as the continuous adjoint equations. The forward states
+//!  are recomputed from uniform or binomial (Revolve) checkpoints to bound the memory used.
+//!
+//!  `LeastSquares` fits the parameters of a `ParamRhs` (and any of the initial conditions) to
+//!  `Observations` of some components of the solution with Gauss-Newton or Levenberg-Marquardt,
+//!  using any fixed-step or adaptive method for the model, and estimates the covariance of the
+//!  fitted values from the Jacobian of the residuals given by the sensitivities.
+//!
 //!  In addition to these traits are traits that are like the above but adapted to
 //!  not include generics in the function signature so that it can be used as in
 //!  `dyn` types.
@@ -41,72 +88,209 @@
 //!  satisfied with the current design and certain features I wish to add require
 //!  a slight redesign.
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
 extern crate maths_traits;
 
+//without `std`, the parts of its prelude that are used come from `alloc` instead
+#[cfg(feature = "alloc")]
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
 use maths_traits::algebra::module_like::*;
 use maths_traits::analysis::metric::*;
 use maths_traits::analysis::real::*;
 
 type Eval<'a, R, D, S> = &'a dyn Fn(R, S) -> (D, S);
+#[cfg(feature = "alloc")]
+type Dist<'a, R, S> = &'a dyn Metric<S, R>;
+
+//the state of a differential equation: a vector space that can also be added to from a
+//borrowed value, so that the steppers can combine their stages in place instead of cloning them
+pub trait State<R: Real>: VectorSpace<R> + for<'a> AddAssign<&'a Self> {}
+
+impl<R: Real, S: VectorSpace<R> + for<'a> AddAssign<&'a S>> State<R> for S {}
+
+//the right-hand side of a differential equation, evaluated in place so that the stepper can
+//reuse its own storage for the derivative instead of receiving a freshly allocated one
+//
+//closures of the form `Fn(R, S) -> (D, S)` implement this automatically, and closures of the
+//form `Fn(R, &S, &mut S) -> D` can be wrapped in `InPlace`
+pub trait Rhs<R, D, S> {
+    fn eval(&self, t: R, y: &S, dy: &mut S) -> D;
+}
+
+impl<R, D, S: Clone, F: Fn(R, S) -> (D, S)> Rhs<R, D, S> for F {
+    fn eval(&self, t: R, y: &S, dy: &mut S) -> D {
+        let (data, k) = self(t, y.clone());
+        *dy = k;
+        data
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct InPlace<F>(pub F);
+
+impl<R, D, S, F: Fn(R, &S, &mut S) -> D> Rhs<R, D, S> for InPlace<F> {
+    fn eval(&self, t: R, y: &S, dy: &mut S) -> D {
+        (self.0)(t, y, dy)
+    }
+}
+
+//lets a borrowed right-hand side be passed to the steppers, which take theirs by value
+#[cfg(feature = "alloc")]
+pub(crate) struct RhsRef<'a, F: ?Sized>(pub(crate) &'a F);
+
+#[cfg(feature = "alloc")]
+impl<'a, R, D, S, F: Rhs<R, D, S> + ?Sized> Rhs<R, D, S> for RhsRef<'a, F> {
+    fn eval(&self, t: R, y: &S, dy: &mut S) -> D {
+        self.0.eval(t, y, dy)
+    }
+}
 
 pub trait Integrator {
-    fn init<R: Real, D: Clone + Default, S: VectorSpace<R>, F: Fn(R, S) -> (D, S)>(
+    //the number of slots in the state buffer
+    fn buffer_len(&self) -> usize {
+        1
+    }
+    //initializes a state buffer of at least `buffer_len()` slots owned by the caller, so that
+    //no allocation is needed (eg an array on the stack)
+    fn init_in<R: Real, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>>(
         &self,
         state: S,
         _dt: R,
         _force: F,
+        buffer: &mut [(D, S)],
+    ) {
+        buffer[0] = (Default::default(), state);
+    }
+    #[cfg(feature = "alloc")]
+    fn init<R: Real, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>>(
+        &self,
+        state: S,
+        dt: R,
+        force: F,
     ) -> Box<[(D, S)]> {
-        Box::new([(Default::default(), state)])
+        let mut buffer = vec![(D::default(), state.clone()); self.buffer_len()];
+        self.init_in(state, dt, force, &mut buffer);
+        buffer.into_boxed_slice()
     }
-    fn step<R: Real, D: Clone + Default, S: VectorSpace<R>, F: Fn(R, S) -> (D, S)>(
+    fn step<R: Real, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>>(
         &self,
         time: R,
         state: &mut [(D, S)],
         dt: R,
         force: F,
     ) -> (D, S);
+    //like `step`, but only leaves the new state in `state[0]`, so that methods given a full
+    //buffer by `init_in` can step without allocating
+    fn step_in_place<R: Real, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>>(
+        &self,
+        time: R,
+        state: &mut [(D, S)],
+        dt: R,
+        force: F,
+    ) {
+        state[0] = self.step(time, state, dt, force);
+    }
+
+    //an iterator over the steps from `(t0, y0)`, owning a copy of this method and the state buffer
+    #[cfg(feature = "alloc")]
+    fn iter<R: Real, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>>(
+        &self,
+        t0: R,
+        y0: S,
+        dt: R,
+        force: F,
+    ) -> Steps<Self, R, D, S, F>
+    where
+        Self: Sized + Clone,
+    {
+        Steps::new(self.clone(), t0, y0, dt, force)
+    }
 }
 
-pub trait Integrates<R: Real, D: Clone + Default, S: VectorSpace<R>> {
-    fn init(&self, state: S, _dt: R, _force: Eval<R, D, S>) -> Box<[(D, S)]> {
-        Box::new([(Default::default(), state)])
+pub trait Integrates<R: Real, D: Clone + Default, S: State<R>> {
+    fn buffer_len(&self) -> usize {
+        1
+    }
+    fn init_in(&self, state: S, _dt: R, _force: Eval<R, D, S>, buffer: &mut [(D, S)]) {
+        buffer[0] = (Default::default(), state);
+    }
+    #[cfg(feature = "alloc")]
+    fn init(&self, state: S, dt: R, force: Eval<R, D, S>) -> Box<[(D, S)]> {
+        let mut buffer = vec![(D::default(), state.clone()); self.buffer_len()];
+        self.init_in(state, dt, force, &mut buffer);
+        buffer.into_boxed_slice()
     }
     fn step(&self, time: R, state: &mut [(D, S)], dt: R, force: Eval<R, D, S>) -> (D, S);
+    fn step_in_place(&self, time: R, state: &mut [(D, S)], dt: R, force: Eval<R, D, S>) {
+        state[0] = Integrates::step(self, time, state, dt, force);
+    }
 }
 
-impl<I: Integrator, R: Real, D: Clone + Default, S: VectorSpace<R>> Integrates<R, D, S> for I {
+impl<I: Integrator, R: Real, D: Clone + Default, S: State<R>> Integrates<R, D, S> for I {
+    fn buffer_len(&self) -> usize {
+        Integrator::buffer_len(self)
+    }
+    fn init_in(&self, state: S, dt: R, force: Eval<R, D, S>, buffer: &mut [(D, S)]) {
+        Integrator::init_in(self, state, dt, force, buffer)
+    }
+    #[cfg(feature = "alloc")]
     fn init(&self, state: S, dt: R, force: Eval<R, D, S>) -> Box<[(D, S)]> {
         Integrator::init(self, state, dt, force)
     }
     fn step(&self, time: R, state: &mut [(D, S)], dt: R, force: Eval<R, D, S>) -> (D, S) {
         Integrator::step(self, time, state, dt, force)
     }
+    fn step_in_place(&self, time: R, state: &mut [(D, S)], dt: R, force: Eval<R, D, S>) {
+        Integrator::step_in_place(self, time, state, dt, force)
+    }
 }
 
 pub trait VelIntegrator {
-    fn init_with_vel<
+    //the number of slots in the state buffer
+    fn buffer_len_with_vel(&self) -> usize {
+        1
+    }
+    //initializes a state buffer of at least `buffer_len_with_vel()` slots owned by the caller
+    fn init_with_vel_in<
         R: Real,
         D: Clone + Default,
-        S: VectorSpace<R>,
-        V: Fn(R, S) -> (D, S),
-        F: Fn(R, S) -> (D, S),
+        S: State<R>,
+        V: Rhs<R, D, S>,
+        F: Rhs<R, D, S>,
     >(
         &self,
         state: S,
         _dt: R,
         _vel: V,
         _force: F,
+        buffer: &mut [(D, S)],
+    ) {
+        buffer[0] = (Default::default(), state);
+    }
+    #[cfg(feature = "alloc")]
+    fn init_with_vel<R: Real, D: Clone + Default, S: State<R>, V: Rhs<R, D, S>, F: Rhs<R, D, S>>(
+        &self,
+        state: S,
+        dt: R,
+        vel: V,
+        force: F,
     ) -> Box<[(D, S)]> {
-        Box::new([(Default::default(), state)])
+        let mut buffer = vec![(D::default(), state.clone()); self.buffer_len_with_vel()];
+        self.init_with_vel_in(state, dt, vel, force, &mut buffer);
+        buffer.into_boxed_slice()
     }
 
-    fn step_with_vel<
-        R: Real,
-        D: Clone + Default,
-        S: VectorSpace<R>,
-        V: Fn(R, S) -> (D, S),
-        F: Fn(R, S) -> (D, S),
-    >(
+    fn step_with_vel<R: Real, D: Clone + Default, S: State<R>, V: Rhs<R, D, S>, F: Rhs<R, D, S>>(
         &self,
         time: R,
         state: &mut [(D, S)],
@@ -114,17 +298,49 @@ pub trait VelIntegrator {
         velocity: V,
         force: F,
     ) -> (D, S);
+
+    //an iterator over the steps from `(t0, y0)`, owning a copy of this method and the state buffer
+    #[cfg(feature = "alloc")]
+    fn iter_with_vel<R: Real, D: Clone + Default, S: State<R>, V: Rhs<R, D, S>, F: Rhs<R, D, S>>(
+        &self,
+        t0: R,
+        y0: S,
+        dt: R,
+        vel: V,
+        force: F,
+    ) -> VelSteps<Self, R, D, S, V, F>
+    where
+        Self: Sized + Clone,
+    {
+        VelSteps::new(self.clone(), t0, y0, dt, vel, force)
+    }
 }
 
-pub trait VelIntegrates<R: Real, D: Clone + Default, S: VectorSpace<R>> {
-    fn init_with_vel(
+pub trait VelIntegrates<R: Real, D: Clone + Default, S: State<R>> {
+    fn buffer_len_with_vel(&self) -> usize {
+        1
+    }
+    fn init_with_vel_in(
         &self,
         state: S,
         _dt: R,
         _vel: Eval<R, D, S>,
         _force: Eval<R, D, S>,
+        buffer: &mut [(D, S)],
+    ) {
+        buffer[0] = (Default::default(), state);
+    }
+    #[cfg(feature = "alloc")]
+    fn init_with_vel(
+        &self,
+        state: S,
+        dt: R,
+        vel: Eval<R, D, S>,
+        force: Eval<R, D, S>,
     ) -> Box<[(D, S)]> {
-        Box::new([(Default::default(), state)])
+        let mut buffer = vec![(D::default(), state.clone()); self.buffer_len_with_vel()];
+        self.init_with_vel_in(state, dt, vel, force, &mut buffer);
+        buffer.into_boxed_slice()
     }
     fn step_with_vel(
         &self,
@@ -136,9 +352,21 @@ pub trait VelIntegrates<R: Real, D: Clone + Default, S: VectorSpace<R>> {
     ) -> (D, S);
 }
 
-impl<I: VelIntegrator, R: Real, D: Clone + Default, S: VectorSpace<R>> VelIntegrates<R, D, S>
-    for I
-{
+impl<I: VelIntegrator, R: Real, D: Clone + Default, S: State<R>> VelIntegrates<R, D, S> for I {
+    fn buffer_len_with_vel(&self) -> usize {
+        VelIntegrator::buffer_len_with_vel(self)
+    }
+    fn init_with_vel_in(
+        &self,
+        state: S,
+        dt: R,
+        vel: Eval<R, D, S>,
+        force: Eval<R, D, S>,
+        buffer: &mut [(D, S)],
+    ) {
+        VelIntegrator::init_with_vel_in(self, state, dt, vel, force, buffer)
+    }
+    #[cfg(feature = "alloc")]
     fn init_with_vel(
         &self,
         state: S,
@@ -160,14 +388,9 @@ impl<I: VelIntegrator, R: Real, D: Clone + Default, S: VectorSpace<R>> VelIntegr
     }
 }
 
+#[cfg(feature = "alloc")]
 pub trait AdaptiveIntegrator {
-    fn adaptive_init<
-        R: Real,
-        D: Clone + Default,
-        S: VectorSpace<R>,
-        M: Metric<S, R>,
-        F: Fn(R, S) -> (D, S),
-    >(
+    fn adaptive_init<R: Real, D: Clone + Default, S: State<R>, M: Metric<S, R>, F: Rhs<R, D, S>>(
         &self,
         t0: R,
         state: S,
@@ -177,51 +400,270 @@ pub trait AdaptiveIntegrator {
     ) -> Box<[(R, D, S)]> {
         Box::new([(t0, Default::default(), state)])
     }
-    fn adaptive_step<
+    fn adaptive_step<R: Real, D: Clone + Default, S: State<R>, M: Metric<S, R>, F: Rhs<R, D, S>>(
+        &self,
+        state: &mut [(R, D, S)],
+        ds: R,
+        force: F,
+        d: M,
+    ) -> (R, D, S);
+
+    //like `adaptive_step`, but returns an error instead of panicking or looping forever
+    //when no acceptable step can be found within the given limits, and reports the accepted
+    //and rejected attempts to the observer (eg a `Stats` to count them)
+    fn try_adaptive_step<
         R: Real,
         D: Clone + Default,
-        S: VectorSpace<R>,
+        S: State<R>,
         M: Metric<S, R>,
-        F: Fn(R, S) -> (D, S),
+        F: Rhs<R, D, S>,
+        O: Observer<R, D, S> + ?Sized,
     >(
         &self,
         state: &mut [(R, D, S)],
         ds: R,
         force: F,
         d: M,
+        _control: &StepControl<R>,
+        observer: &mut O,
+    ) -> Result<(R, D, S), StepError<R>> {
+        //without knowing the layout of the buffer, assume that the state is in the first slot
+        let t0 = state[0].0.clone();
+        let (t, data, mut s) = self.adaptive_step(state, ds, force, d);
+        let step = StepInfo {
+            start: t0.clone(),
+            dt: t.clone() - t0,
+            error: None,
+        };
+        if observer.accepted(&step, &data, &mut s) == Control::Reset {
+            state[0].2 = s.clone();
+        }
+        Ok((t, data, s))
+    }
+
+    //an iterator over the accepted steps from `(t0, y0)`, owning a copy of this method and
+    //the state buffer
+    fn adaptive_iter<
+        R: Real,
+        D: Clone + Default,
+        S: State<R>,
+        M: Metric<S, R> + Clone,
+        F: Rhs<R, D, S>,
+    >(
+        &self,
+        t0: R,
+        y0: S,
+        ds: R,
+        force: F,
+        d: M,
+    ) -> AdaptiveSteps<Self, R, D, S, M, F>
+    where
+        Self: Sized + Clone,
+    {
+        AdaptiveSteps::new(self.clone(), t0, y0, ds, force, d)
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub trait AdaptiveIntegrates<R: Real, D: Clone + Default, S: State<R>> {
+    fn adaptive_init(
+        &self,
+        t0: R,
+        state: S,
+        _ds: R,
+        _force: Eval<R, D, S>,
+        _d: Dist<R, S>,
+    ) -> Box<[(R, D, S)]> {
+        Box::new([(t0, Default::default(), state)])
+    }
+    fn adaptive_step(
+        &self,
+        state: &mut [(R, D, S)],
+        ds: R,
+        force: Eval<R, D, S>,
+        d: Dist<R, S>,
     ) -> (R, D, S);
+    fn try_adaptive_step(
+        &self,
+        state: &mut [(R, D, S)],
+        ds: R,
+        force: Eval<R, D, S>,
+        d: Dist<R, S>,
+        _control: &StepControl<R>,
+        observer: &mut dyn Observer<R, D, S>,
+    ) -> Result<(R, D, S), StepError<R>> {
+        //without knowing the layout of the buffer, assume that the state is in the first slot
+        let t0 = state[0].0.clone();
+        let (t, data, mut s) = self.adaptive_step(state, ds, force, d);
+        let step = StepInfo {
+            start: t0.clone(),
+            dt: t.clone() - t0,
+            error: None,
+        };
+        if observer.accepted(&step, &data, &mut s) == Control::Reset {
+            state[0].2 = s.clone();
+        }
+        Ok((t, data, s))
+    }
+}
+
+//wraps a metric trait object so that it can be passed to the generic methods
+#[cfg(feature = "alloc")]
+struct DynMetric<'a, R, S>(Dist<'a, R, S>);
+
+#[cfg(feature = "alloc")]
+impl<'a, R: Real, S> Metric<S, R> for DynMetric<'a, R, S> {
+    fn distance(&self, x1: S, x2: S) -> R {
+        self.0.distance(x1, x2)
+    }
 }
 
+#[cfg(feature = "alloc")]
+impl<I: AdaptiveIntegrator, R: Real, D: Clone + Default, S: State<R>> AdaptiveIntegrates<R, D, S>
+    for I
+{
+    fn adaptive_init(
+        &self,
+        t0: R,
+        state: S,
+        ds: R,
+        force: Eval<R, D, S>,
+        d: Dist<R, S>,
+    ) -> Box<[(R, D, S)]> {
+        AdaptiveIntegrator::adaptive_init(self, t0, state, ds, force, DynMetric(d))
+    }
+    fn adaptive_step(
+        &self,
+        state: &mut [(R, D, S)],
+        ds: R,
+        force: Eval<R, D, S>,
+        d: Dist<R, S>,
+    ) -> (R, D, S) {
+        AdaptiveIntegrator::adaptive_step(self, state, ds, force, DynMetric(d))
+    }
+    fn try_adaptive_step(
+        &self,
+        state: &mut [(R, D, S)],
+        ds: R,
+        force: Eval<R, D, S>,
+        d: Dist<R, S>,
+        control: &StepControl<R>,
+        observer: &mut dyn Observer<R, D, S>,
+    ) -> Result<(R, D, S), StepError<R>> {
+        AdaptiveIntegrator::try_adaptive_step(
+            self,
+            state,
+            ds,
+            force,
+            DynMetric(d),
+            control,
+            observer,
+        )
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub use adjoint::*;
+pub use dual::*;
+#[cfg(feature = "alloc")]
+pub use estimation::*;
+#[cfg(feature = "alloc")]
+pub use jacobian::*;
+#[cfg(feature = "nalgebra")]
+pub use nalgebra_support::*;
+#[cfg(feature = "ndarray")]
+pub use ndarray_support::*;
+pub use observer::*;
+pub use rational::*;
+#[cfg(feature = "alloc")]
+pub use registry::*;
 pub use runge_kutta::*;
+#[cfg(feature = "alloc")]
+pub use sensitivity::*;
+#[cfg(feature = "alloc")]
+pub use solver::*;
+#[cfg(feature = "std")]
+pub use stability::*;
+pub use stats::*;
+pub use step_control::*;
+#[cfg(feature = "alloc")]
+pub use steps::*;
+#[cfg(feature = "alloc")]
+pub use tableau_file::*;
+#[cfg(feature = "alloc")]
+pub use trajectory::*;
+#[cfg(feature = "alloc")]
+pub use trees::*;
+pub use vector::*;
+#[cfg(feature = "alloc")]
+pub mod adjoint;
+pub mod dual;
+#[cfg(feature = "alloc")]
+pub mod estimation;
+#[cfg(feature = "alloc")]
+pub mod jacobian;
+#[cfg(feature = "alloc")]
+mod linalg;
+#[cfg(feature = "nalgebra")]
+pub mod nalgebra_support;
+#[cfg(feature = "ndarray")]
+pub mod ndarray_support;
+pub mod observer;
+pub mod rational;
+#[cfg(feature = "alloc")]
+pub mod registry;
 pub mod runge_kutta;
+#[cfg(feature = "alloc")]
+pub mod sensitivity;
+#[cfg(feature = "serde")]
+mod serde_support;
+#[cfg(feature = "alloc")]
+pub mod solver;
+#[cfg(feature = "std")]
+pub mod stability;
+pub mod stats;
+pub mod step_control;
+#[cfg(feature = "alloc")]
+pub mod steps;
+#[cfg(feature = "alloc")]
+pub mod tableau_file;
+#[cfg(feature = "alloc")]
+pub mod trajectory;
+#[cfg(feature = "alloc")]
+pub mod trees;
+pub mod vector;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VelocityVerlet;
 
 impl VelIntegrator for VelocityVerlet {
-    fn init_with_vel<
+    fn buffer_len_with_vel(&self) -> usize {
+        4
+    }
+
+    fn init_with_vel_in<
         R: Real,
         D: Clone + Default,
-        S: VectorSpace<R>,
-        V: Fn(R, S) -> (D, S),
-        F: Fn(R, S) -> (D, S),
+        S: State<R>,
+        V: Rhs<R, D, S>,
+        F: Rhs<R, D, S>,
     >(
         &self,
         state: S,
         _dt: R,
         _vel: V,
         _force: F,
-    ) -> Box<[(D, S)]> {
-        Box::new([(Default::default(), state), (Default::default(), S::zero())])
+        buffer: &mut [(D, S)],
+    ) {
+        //the state and acceleration, followed by scratch space for the midpoint and velocity
+        buffer[1] = (Default::default(), S::zero());
+        buffer[2] = (Default::default(), state.clone());
+        buffer[3] = (Default::default(), state.clone());
+        buffer[0] = (Default::default(), state);
     }
 
-    fn step_with_vel<
-        R: Real,
-        D: Clone + Default,
-        S: VectorSpace<R>,
-        V: Fn(R, S) -> (D, S),
-        F: Fn(R, S) -> (D, S),
-    >(
+    fn step_with_vel<R: Real, D: Clone + Default, S: State<R>, V: Rhs<R, D, S>, F: Rhs<R, D, S>>(
         &self,
         time: R,
         state: &mut [(D, S)],
@@ -229,16 +671,32 @@ impl VelIntegrator for VelocityVerlet {
         velocity: V,
         force: F,
     ) -> (D, S) {
+        //if the buffer doesn't have the scratch slots from `init_with_vel`, use a temporary one
+        if state.len() < 4 {
+            let mut buffer = [
+                state[0].clone(),
+                state[1].clone(),
+                state[0].clone(),
+                state[0].clone(),
+            ];
+            let result = VelIntegrator::step_with_vel(self, time, &mut buffer, dt, velocity, force);
+            state[..2].clone_from_slice(&buffer[..2]);
+            return result;
+        }
+
         let (s1, rest) = state.split_first_mut().unwrap();
-        let (a1, _) = rest.split_first_mut().unwrap();
+        let (a1, rest) = rest.split_first_mut().unwrap();
+        let (mid, rest) = rest.split_first_mut().unwrap();
+        let v = &mut rest[0];
 
-        let mid = s1.clone().1
-            + a1.clone().1 * dt.clone()
-            + velocity(time.clone(), a1.clone().1).1 * (dt.clone() * dt.clone() * R::repr(0.5));
+        velocity.eval(time.clone(), &a1.1, &mut v.1);
+        mid.1.clone_from(&s1.1);
+        mid.1 += a1.1.clone() * dt.clone();
+        mid.1 += v.1.clone() * (dt.clone() * dt.clone() * R::repr(0.5));
 
-        s1.1 += a1.clone().1 * (dt.clone() * R::repr(0.5));
-        a1.1 = force(time.clone() + dt.clone(), mid).1;
-        s1.1 += a1.clone().1 * (dt.clone() * R::repr(0.5));
+        s1.1 += a1.1.clone() * (dt.clone() * R::repr(0.5));
+        force.eval(time.clone() + dt.clone(), &mid.1, &mut a1.1);
+        s1.1 += a1.1.clone() * (dt.clone() * R::repr(0.5));
 
         s1.clone()
     }
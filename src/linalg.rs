@@ -0,0 +1,58 @@
+use super::*;
+
+fn abs<R: Real>(x: R) -> R {
+    if x < R::zero() {
+        -x
+    } else {
+        x
+    }
+}
+
+//Solves `a * x = b` for an n×n matrix stored by rows, overwriting `b` with the solution and
+//`a` with its factorization
+//
+//uses Gaussian elimination with partial pivoting, and returns false if `a` is singular
+pub(crate) fn solve<R: Real>(n: usize, a: &mut [R], b: &mut [R]) -> bool {
+    for col in 0..n {
+        //swap the largest remaining entry of the column onto the diagonal
+        let pivot = (col..n)
+            .max_by(|&i, &j| {
+                abs(a[i * n + col].clone())
+                    .partial_cmp(&abs(a[j * n + col].clone()))
+                    .unwrap_or(::core::cmp::Ordering::Equal)
+            })
+            .unwrap();
+        if a[pivot * n + col] == R::zero() || !step_control::is_finite(&a[pivot * n + col]) {
+            return false;
+        }
+        if pivot != col {
+            for k in 0..n {
+                a.swap(pivot * n + k, col * n + k);
+            }
+            b.swap(pivot, col);
+        }
+
+        for row in col + 1..n {
+            let factor = a[row * n + col].clone() / a[col * n + col].clone();
+            if factor == R::zero() {
+                continue;
+            }
+            for k in col..n {
+                let x = a[col * n + k].clone() * factor.clone();
+                a[row * n + k] -= x;
+            }
+            let x = b[col].clone() * factor;
+            b[row] -= x;
+        }
+    }
+
+    //back substitution
+    for row in (0..n).rev() {
+        let mut x = b[row].clone();
+        for k in row + 1..n {
+            x -= a[row * n + k].clone() * b[k].clone();
+        }
+        b[row] = x / a[row * n + row].clone();
+    }
+    true
+}
@@ -0,0 +1,349 @@
+use super::*;
+
+use core::ops::{Deref, DerefMut};
+use maths_traits::algebra::*;
+use nalgebra::allocator::Allocator;
+use nalgebra::{DefaultAllocator, Dim, OMatrix, OVector, Scalar, U1};
+
+//A wrapper for an owned nalgebra vector (eg `nalgebra::Vector3` or `nalgebra::DVector`)
+//so that it can be used as the state of an integrator
+//
+//It derefs to the wrapped vector, which can also be moved in and out of the wrapper for
+//free. As with `DVector`, the zero of a dynamically sized vector is empty and adding it
+//to a vector of any size gives that vector, and vectors of different lengths are added as
+//if the missing elements were zero.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "OVector<T, D>: serde::Serialize",
+        deserialize = "OVector<T, D>: serde::Deserialize<'de>"
+    ))
+)]
+pub struct NalgebraVector<T: Scalar, D: Dim>(pub OVector<T, D>)
+where
+    DefaultAllocator: Allocator<D>;
+
+//written out so that `clone_from` reuses the storage the vector already has
+impl<T: Scalar, D: Dim> Clone for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    fn clone(&self) -> Self {
+        NalgebraVector(self.0.clone())
+    }
+    fn clone_from(&mut self, source: &Self) {
+        if self.0.shape() == source.0.shape() {
+            self.0.copy_from(&source.0);
+        } else {
+            self.0 = source.0.clone();
+        }
+    }
+}
+
+impl<T: Scalar, D: Dim> From<OVector<T, D>> for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    fn from(v: OVector<T, D>) -> Self {
+        NalgebraVector(v)
+    }
+}
+
+impl<T: Scalar, D: Dim> Deref for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    type Target = OVector<T, D>;
+    fn deref(&self) -> &OVector<T, D> {
+        &self.0
+    }
+}
+
+impl<T: Scalar, D: Dim> DerefMut for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    fn deref_mut(&mut self) -> &mut OVector<T, D> {
+        &mut self.0
+    }
+}
+
+impl<T: Scalar + Zero + AddAssign, D: Dim> Zero for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    fn zero() -> Self {
+        let n = D::try_to_usize().unwrap_or(0);
+        NalgebraVector(OVector::from_element_generic(
+            D::from_usize(n),
+            U1,
+            T::zero(),
+        ))
+    }
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(T::is_zero)
+    }
+}
+
+//grows a (dynamically sized) vector with zeros to the given length if it is shorter
+fn pad<T: Scalar + Zero, D: Dim>(v: &mut OVector<T, D>, len: usize)
+where
+    DefaultAllocator: Allocator<D>,
+{
+    if v.len() < len {
+        let grown = OVector::from_fn_generic(D::from_usize(len), U1, |i, _| {
+            v.get(i).cloned().unwrap_or_else(T::zero)
+        });
+        *v = grown;
+    }
+}
+
+impl<T: Scalar + Zero + AddAssign, D: Dim> AddAssign for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    fn add_assign(&mut self, rhs: Self) {
+        if self.0.is_empty() {
+            *self = rhs;
+        } else {
+            pad(&mut self.0, rhs.0.len());
+            for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+                *a += b.clone();
+            }
+        }
+    }
+}
+
+impl<T: Scalar + Zero + SubAssign + Neg<Output = T>, D: Dim> SubAssign for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    fn sub_assign(&mut self, rhs: Self) {
+        if self.0.is_empty() {
+            *self = -rhs;
+        } else {
+            pad(&mut self.0, rhs.0.len());
+            for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+                *a -= b.clone();
+            }
+        }
+    }
+}
+
+impl<'a, T: Scalar + Zero + AddAssign, D: Dim> AddAssign<&'a Self> for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    fn add_assign(&mut self, rhs: &'a Self) {
+        if self.0.is_empty() {
+            self.0.clone_from(&rhs.0);
+        } else {
+            pad(&mut self.0, rhs.0.len());
+            for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+                *a += b.clone();
+            }
+        }
+    }
+}
+
+impl<'a, T: Scalar + Zero + SubAssign + Neg<Output = T>, D: Dim> SubAssign<&'a Self>
+    for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    fn sub_assign(&mut self, rhs: &'a Self) {
+        if self.0.is_empty() {
+            *self = -rhs.clone();
+        } else {
+            pad(&mut self.0, rhs.0.len());
+            for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+                *a -= b.clone();
+            }
+        }
+    }
+}
+
+impl<T: Scalar + Zero + AddAssign, D: Dim> Add for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    type Output = Self;
+    fn add(mut self, rhs: Self) -> Self {
+        self += rhs;
+        self
+    }
+}
+
+impl<T: Scalar + Zero + SubAssign + Neg<Output = T>, D: Dim> Sub for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    type Output = Self;
+    fn sub(mut self, rhs: Self) -> Self {
+        self -= rhs;
+        self
+    }
+}
+
+impl<T: Scalar + Neg<Output = T>, D: Dim> Neg for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    type Output = Self;
+    fn neg(mut self) -> Self {
+        for a in self.0.iter_mut() {
+            *a = -a.clone();
+        }
+        self
+    }
+}
+
+impl<T: Scalar + MulAssign, D: Dim> MulAssign<T> for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    fn mul_assign(&mut self, rhs: T) {
+        for a in self.0.iter_mut() {
+            *a *= rhs.clone();
+        }
+    }
+}
+
+impl<T: Scalar + MulAssign, D: Dim> Mul<T> for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    type Output = Self;
+    fn mul(mut self, rhs: T) -> Self {
+        self *= rhs;
+        self
+    }
+}
+
+impl<T: Scalar + DivAssign, D: Dim> DivAssign<T> for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    fn div_assign(&mut self, rhs: T) {
+        for a in self.0.iter_mut() {
+            *a /= rhs.clone();
+        }
+    }
+}
+
+impl<T: Scalar + DivAssign, D: Dim> Div<T> for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    type Output = Self;
+    fn div(mut self, rhs: T) -> Self {
+        self /= rhs;
+        self
+    }
+}
+
+impl<T: Scalar, D: Dim> AddAssociative for NalgebraVector<T, D> where DefaultAllocator: Allocator<D> {}
+impl<T: Scalar, D: Dim> AddCommutative for NalgebraVector<T, D> where DefaultAllocator: Allocator<D> {}
+impl<T: Scalar, D: Dim> Distributive<T> for NalgebraVector<T, D> where DefaultAllocator: Allocator<D>
+{}
+
+impl<T: Scalar + Real, D: Dim> InnerProductSpace<T> for NalgebraVector<T, D>
+where
+    DefaultAllocator: Allocator<D>,
+{
+    fn inner_product(self, rhs: Self) -> T {
+        self.0
+            .iter()
+            .zip(rhs.0.iter())
+            .fold(T::zero(), |sum, (a, b)| sum + a.clone() * b.clone())
+    }
+}
+
+//a square nalgebra matrix used as the Jacobian of a right-hand side
+#[derive(Clone, PartialEq, Debug)]
+pub struct NalgebraJacobian<T: Scalar, D: Dim>(pub OMatrix<T, D, D>)
+where
+    DefaultAllocator: Allocator<D, D>;
+
+impl<T: Scalar + Real, D: Dim> Jacobian<T, NalgebraVector<T, D>> for NalgebraJacobian<T, D>
+where
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
+{
+    //a shorter (dynamically sized) vector is treated as padded with zeros, as in the arithmetic
+    fn apply(&self, v: &NalgebraVector<T, D>) -> NalgebraVector<T, D> {
+        NalgebraVector(OVector::from_fn_generic(
+            self.0.shape_generic().0,
+            U1,
+            |i, _| {
+                self.0
+                    .row(i)
+                    .iter()
+                    .zip(v.0.iter())
+                    .fold(T::zero(), |sum, (a, x)| sum + a.clone() * x.clone())
+            },
+        ))
+    }
+
+    fn solve_shifted(&self, gamma: T, b: &NalgebraVector<T, D>) -> Option<NalgebraVector<T, D>> {
+        let n = self.0.nrows();
+        let mut a: Vec<T> = (0..n)
+            .flat_map(|i| (0..n).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let x = -(self.0[(i, j)].clone() * gamma.clone());
+                if i == j {
+                    x + T::one()
+                } else {
+                    x
+                }
+            })
+            .collect();
+        let mut x = OVector::from_fn_generic(self.0.shape_generic().0, U1, |i, _| {
+            b.0.get(i).cloned().unwrap_or_else(T::zero)
+        });
+        linalg::solve(n, &mut a, x.as_mut_slice()).then_some(NalgebraVector(x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{DMatrix, DVector};
+
+    #[test]
+    fn vectors_of_different_lengths_are_padded_with_zeros() {
+        let v = |x: &[f64]| NalgebraVector(DVector::from_column_slice(x));
+
+        let mut a = v(&[1.0, 2.0]);
+        a += &v(&[1.0, 1.0, 1.0]);
+        assert_eq!(a, v(&[2.0, 3.0, 1.0]));
+        a -= v(&[1.0]);
+        assert_eq!(a, v(&[1.0, 3.0, 1.0]));
+        a -= v(&[0.0, 0.0, 0.0, 2.0]);
+        assert_eq!(a, v(&[1.0, 3.0, 1.0, -2.0]));
+
+        let mut zero = NalgebraVector::zero();
+        zero -= &a;
+        assert_eq!(zero, -a);
+    }
+
+    #[test]
+    fn jacobians_pad_shorter_vectors() {
+        let v = |x: &[f64]| NalgebraVector(DVector::from_column_slice(x));
+        let jacobian = NalgebraJacobian(DMatrix::from_row_slice(2, 2, &[1.0, 2.0, 3.0, 4.0]));
+
+        assert_eq!(jacobian.apply(&v(&[1.0])), v(&[1.0, 3.0]));
+        assert_eq!(jacobian.apply(&NalgebraVector::zero()), v(&[0.0, 0.0]));
+
+        //(I - J/2) x = b
+        let x = jacobian.solve_shifted(0.5, &v(&[1.0])).unwrap();
+        assert_eq!(x.0.len(), 2);
+        assert!((x.0[0] * 0.5 - x.0[1] - 1.0).abs() < 1e-12, "{:?}", x);
+        assert!((x.0[0] * -1.5 - x.0[1]).abs() < 1e-12, "{:?}", x);
+        assert_eq!(
+            jacobian.solve_shifted(0.5, &NalgebraVector::zero()),
+            Some(v(&[0.0, 0.0]))
+        );
+    }
+}
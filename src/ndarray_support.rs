@@ -0,0 +1,275 @@
+use super::*;
+
+use core::ops::{Deref, DerefMut};
+use maths_traits::algebra::*;
+use ndarray::{Array, Array1, Array2, ArrayViewMut, Dimension, Slice, Zip};
+
+//A wrapper for an owned ndarray array of any dimension (eg a grid of values) so that it can
+//be used as the state of an integrator, with all operations done elementwise
+//
+//It derefs to the wrapped array, which can also be moved in and out of the wrapper for free.
+//The zero array is empty, and adding it to an array of any shape gives that array. As with
+//`DVector`, arrays of different shapes are added as if the missing elements were zero.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "Array<T, D>: serde::Serialize",
+        deserialize = "Array<T, D>: serde::Deserialize<'de>"
+    ))
+)]
+pub struct NdArray<T, D: Dimension>(pub Array<T, D>);
+
+//written out so that `clone_from` reuses the storage the array already has
+impl<T: Clone, D: Dimension> Clone for NdArray<T, D> {
+    fn clone(&self) -> Self {
+        NdArray(self.0.clone())
+    }
+    fn clone_from(&mut self, source: &Self) {
+        self.0.clone_from(&source.0);
+    }
+}
+
+impl<T, D: Dimension> From<Array<T, D>> for NdArray<T, D> {
+    fn from(a: Array<T, D>) -> Self {
+        NdArray(a)
+    }
+}
+
+impl<T, D: Dimension> Deref for NdArray<T, D> {
+    type Target = Array<T, D>;
+    fn deref(&self) -> &Array<T, D> {
+        &self.0
+    }
+}
+
+impl<T, D: Dimension> DerefMut for NdArray<T, D> {
+    fn deref_mut(&mut self) -> &mut Array<T, D> {
+        &mut self.0
+    }
+}
+
+impl<T: Clone + Zero + AddAssign, D: Dimension> Zero for NdArray<T, D> {
+    fn zero() -> Self {
+        NdArray(Array::zeros(D::zeros(D::NDIM.unwrap_or(1))))
+    }
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(T::is_zero)
+    }
+}
+
+//the part of the array with the given shape, after growing the array with zeros along any
+//axis where that shape is longer
+fn padded<'a, T: Clone + Zero, D: Dimension>(
+    a: &'a mut Array<T, D>,
+    shape: &D,
+) -> ArrayViewMut<'a, T, D> {
+    if a.shape().iter().zip(shape.slice()).any(|(n, m)| n < m) {
+        let old = a.raw_dim();
+        let mut dim = old.clone();
+        for (n, m) in dim.slice_mut().iter_mut().zip(shape.slice()) {
+            *n = (*n).max(*m);
+        }
+        let mut grown = Array::from_elem(dim, T::zero());
+        grown
+            .slice_each_axis_mut(|axis| Slice::from(0..old[axis.axis.index()]))
+            .assign(a);
+        *a = grown;
+    }
+    a.slice_each_axis_mut(|axis| Slice::from(0..shape[axis.axis.index()]))
+}
+
+impl<T: Clone + Zero + AddAssign, D: Dimension> AddAssign for NdArray<T, D> {
+    fn add_assign(&mut self, rhs: Self) {
+        if self.0.is_empty() {
+            *self = rhs;
+        } else {
+            Zip::from(padded(&mut self.0, &rhs.0.raw_dim()))
+                .and(&rhs.0)
+                .for_each(|a, b| *a += b.clone());
+        }
+    }
+}
+
+impl<T: Clone + Zero + SubAssign + Neg<Output = T>, D: Dimension> SubAssign for NdArray<T, D> {
+    fn sub_assign(&mut self, rhs: Self) {
+        if self.0.is_empty() {
+            *self = -rhs;
+        } else {
+            Zip::from(padded(&mut self.0, &rhs.0.raw_dim()))
+                .and(&rhs.0)
+                .for_each(|a, b| *a -= b.clone());
+        }
+    }
+}
+
+impl<'a, T: Clone + Zero + AddAssign, D: Dimension> AddAssign<&'a Self> for NdArray<T, D> {
+    fn add_assign(&mut self, rhs: &'a Self) {
+        if self.0.is_empty() {
+            self.0.clone_from(&rhs.0);
+        } else {
+            Zip::from(padded(&mut self.0, &rhs.0.raw_dim()))
+                .and(&rhs.0)
+                .for_each(|a, b| *a += b.clone());
+        }
+    }
+}
+
+impl<'a, T: Clone + Zero + SubAssign + Neg<Output = T>, D: Dimension> SubAssign<&'a Self>
+    for NdArray<T, D>
+{
+    fn sub_assign(&mut self, rhs: &'a Self) {
+        if self.0.is_empty() {
+            *self = -rhs.clone();
+        } else {
+            Zip::from(padded(&mut self.0, &rhs.0.raw_dim()))
+                .and(&rhs.0)
+                .for_each(|a, b| *a -= b.clone());
+        }
+    }
+}
+
+impl<T: Clone + Zero + AddAssign, D: Dimension> Add for NdArray<T, D> {
+    type Output = Self;
+    fn add(mut self, rhs: Self) -> Self {
+        self += rhs;
+        self
+    }
+}
+
+impl<T: Clone + Zero + SubAssign + Neg<Output = T>, D: Dimension> Sub for NdArray<T, D> {
+    type Output = Self;
+    fn sub(mut self, rhs: Self) -> Self {
+        self -= rhs;
+        self
+    }
+}
+
+impl<T: Clone + Neg<Output = T>, D: Dimension> Neg for NdArray<T, D> {
+    type Output = Self;
+    fn neg(mut self) -> Self {
+        self.0.map_inplace(|a| *a = -a.clone());
+        self
+    }
+}
+
+impl<T: Clone + MulAssign, D: Dimension> MulAssign<T> for NdArray<T, D> {
+    fn mul_assign(&mut self, rhs: T) {
+        self.0.map_inplace(|a| *a *= rhs.clone());
+    }
+}
+
+impl<T: Clone + MulAssign, D: Dimension> Mul<T> for NdArray<T, D> {
+    type Output = Self;
+    fn mul(mut self, rhs: T) -> Self {
+        self *= rhs;
+        self
+    }
+}
+
+impl<T: Clone + DivAssign, D: Dimension> DivAssign<T> for NdArray<T, D> {
+    fn div_assign(&mut self, rhs: T) {
+        self.0.map_inplace(|a| *a /= rhs.clone());
+    }
+}
+
+impl<T: Clone + DivAssign, D: Dimension> Div<T> for NdArray<T, D> {
+    type Output = Self;
+    fn div(mut self, rhs: T) -> Self {
+        self /= rhs;
+        self
+    }
+}
+
+impl<T, D: Dimension> AddAssociative for NdArray<T, D> {}
+impl<T, D: Dimension> AddCommutative for NdArray<T, D> {}
+impl<T, D: Dimension> Distributive<T> for NdArray<T, D> {}
+
+//the sum of the elementwise products, ie the dot product of the flattened arrays
+impl<T: Real, D: Dimension> InnerProductSpace<T> for NdArray<T, D> {
+    fn inner_product(self, rhs: Self) -> T {
+        self.0
+            .iter()
+            .zip(rhs.0.iter())
+            .fold(T::zero(), |sum, (a, b)| sum + a.clone() * b.clone())
+    }
+}
+
+//a square ndarray matrix used as the Jacobian of a right-hand side with a 1D state
+#[derive(Clone, PartialEq, Debug)]
+pub struct NdJacobian<T>(pub Array2<T>);
+
+impl<T: Real> Jacobian<T, NdArray<T, ndarray::Ix1>> for NdJacobian<T> {
+    fn apply(&self, v: &NdArray<T, ndarray::Ix1>) -> NdArray<T, ndarray::Ix1> {
+        let out: Array1<T> = self
+            .0
+            .rows()
+            .into_iter()
+            .map(|row| {
+                row.iter()
+                    .zip(v.0.iter())
+                    .fold(T::zero(), |sum, (a, x)| sum + a.clone() * x.clone())
+            })
+            .collect();
+        NdArray(out)
+    }
+
+    fn solve_shifted(
+        &self,
+        gamma: T,
+        b: &NdArray<T, ndarray::Ix1>,
+    ) -> Option<NdArray<T, ndarray::Ix1>> {
+        let n = self.0.nrows();
+        let mut a: Vec<T> = self
+            .0
+            .indexed_iter()
+            .map(|((i, j), x)| {
+                let x = -(x.clone() * gamma.clone());
+                if i == j {
+                    x + T::one()
+                } else {
+                    x
+                }
+            })
+            .collect();
+        let mut x = b.0.to_vec();
+        x.resize(n, T::zero());
+        linalg::solve(n, &mut a, &mut x).then(|| NdArray(Array1::from(x)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn arrays_of_different_shapes_are_padded_with_zeros() {
+        let mut a = NdArray(array![[1.0, 2.0], [3.0, 4.0]]);
+        a += &NdArray(array![[1.0, 1.0, 1.0]]);
+        assert_eq!(a, NdArray(array![[2.0, 3.0, 1.0], [3.0, 4.0, 0.0]]));
+        a -= NdArray(array![[0.0], [0.0], [1.0]]);
+        assert_eq!(
+            a,
+            NdArray(array![[2.0, 3.0, 1.0], [3.0, 4.0, 0.0], [-1.0, 0.0, 0.0]])
+        );
+
+        let mut zero = NdArray::zero();
+        zero += a.clone();
+        assert_eq!(zero, a);
+    }
+
+    #[test]
+    fn jacobians_pad_shorter_vectors() {
+        let jacobian = NdJacobian(array![[1.0, 2.0], [3.0, 4.0]]);
+        assert_eq!(
+            jacobian.apply(&NdArray(array![1.0])),
+            NdArray(array![1.0, 3.0])
+        );
+        assert_eq!(
+            jacobian.solve_shifted(0.5, &NdArray::zero()),
+            Some(NdArray(array![0.0, 0.0]))
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use super::*;
+
+//what to do after an observer has been called
+//
+//the variants are ordered so that combining observers keeps the strongest request
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Control {
+    #[default]
+    Continue,
+    //the state was modified, so the method has to restart from it
+    Reset,
+    Stop,
+}
+
+//an attempted step from `start` to `start + dt`, with the error estimate of adaptive methods
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StepInfo<R> {
+    pub start: R,
+    pub dt: R,
+    pub error: Option<R>,
+}
+
+impl<R: Real> StepInfo<R> {
+    pub fn end(&self) -> R {
+        self.start.clone() + self.dt.clone()
+    }
+}
+
+//Hooks called while integrating, for logging, plotting, custom stopping criteria, and so on
+//
+//An observer may change the state of an accepted step or event (eg to apply an impulse), in
+//which case it has to return `Control::Reset` so that the method doesn't keep using values
+//computed from the old state.
+//
+//Events are sign changes of the event functions between two accepted steps. A `Solver`
+//locates them by repeating the step with shorter step sizes and then calls `event` with
+//the state at the event, continuing from there.
+pub trait Observer<R, D, S> {
+    //called with the state at the end of each accepted step
+    fn accepted(&mut self, _step: &StepInfo<R>, _data: &D, _state: &mut S) -> Control {
+        Control::Continue
+    }
+
+    //called with the state at the start of each rejected step
+    fn rejected(&mut self, _step: &StepInfo<R>, _state: &S) {}
+
+    fn event_count(&self) -> usize {
+        0
+    }
+    fn event_value(&self, _index: usize, _t: &R, _state: &S) -> R {
+        panic!("no event functions")
+    }
+    fn event(&mut self, _index: usize, _t: &R, _data: &D, _state: &mut S) -> Control {
+        Control::Continue
+    }
+}
+
+impl<R, D, S> Observer<R, D, S> for () {}
+
+impl<R, D, S, O: Observer<R, D, S> + ?Sized> Observer<R, D, S> for &mut O {
+    fn accepted(&mut self, step: &StepInfo<R>, data: &D, state: &mut S) -> Control {
+        (**self).accepted(step, data, state)
+    }
+    fn rejected(&mut self, step: &StepInfo<R>, state: &S) {
+        (**self).rejected(step, state)
+    }
+    fn event_count(&self) -> usize {
+        (**self).event_count()
+    }
+    fn event_value(&self, index: usize, t: &R, state: &S) -> R {
+        (**self).event_value(index, t, state)
+    }
+    fn event(&mut self, index: usize, t: &R, data: &D, state: &mut S) -> Control {
+        (**self).event(index, t, data, state)
+    }
+}
+
+//calls both observers in order, with the event functions of the second numbered after those
+//of the first
+impl<R, D, S, A: Observer<R, D, S>, B: Observer<R, D, S>> Observer<R, D, S> for (A, B) {
+    fn accepted(&mut self, step: &StepInfo<R>, data: &D, state: &mut S) -> Control {
+        let a = self.0.accepted(step, data, state);
+        a.max(self.1.accepted(step, data, state))
+    }
+    fn rejected(&mut self, step: &StepInfo<R>, state: &S) {
+        self.0.rejected(step, state);
+        self.1.rejected(step, state);
+    }
+    fn event_count(&self) -> usize {
+        self.0.event_count() + self.1.event_count()
+    }
+    fn event_value(&self, index: usize, t: &R, state: &S) -> R {
+        match index.checked_sub(self.0.event_count()) {
+            Some(i) => self.1.event_value(i, t, state),
+            None => self.0.event_value(index, t, state),
+        }
+    }
+    fn event(&mut self, index: usize, t: &R, data: &D, state: &mut S) -> Control {
+        match index.checked_sub(self.0.event_count()) {
+            Some(i) => self.1.event(i, t, data, state),
+            None => self.0.event(index, t, data, state),
+        }
+    }
+}
+
+impl<R: Real, D, S> Observer<R, D, S> for Stats<R> {
+    fn accepted(&mut self, step: &StepInfo<R>, _data: &D, _state: &mut S) -> Control {
+        self.accept(step.dt.clone());
+        Control::Continue
+    }
+    fn rejected(&mut self, _step: &StepInfo<R>, _state: &S) {
+        self.reject();
+    }
+}
+
+//an observer calling a closure on each accepted step
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct OnStep<F>(pub F);
+
+impl<R, D, S, F: FnMut(&StepInfo<R>, &D, &mut S) -> Control> Observer<R, D, S> for OnStep<F> {
+    fn accepted(&mut self, step: &StepInfo<R>, data: &D, state: &mut S) -> Control {
+        (self.0)(step, data, state)
+    }
+}
+
+//an observer with a single event function, calling a closure at each of its sign changes
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct OnEvent<G, F> {
+    pub g: G,
+    pub f: F,
+}
+
+impl<R, D, S, G: Fn(&R, &S) -> R, F: FnMut(&R, &D, &mut S) -> Control> Observer<R, D, S>
+    for OnEvent<G, F>
+{
+    fn event_count(&self) -> usize {
+        1
+    }
+    fn event_value(&self, _index: usize, t: &R, state: &S) -> R {
+        (self.g)(t, state)
+    }
+    fn event(&mut self, _index: usize, t: &R, data: &D, state: &mut S) -> Control {
+        (self.f)(t, data, state)
+    }
+}
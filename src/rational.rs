@@ -0,0 +1,511 @@
+use super::*;
+
+use core::cmp::Ordering;
+use core::fmt::{Debug, Display, Formatter};
+use core::ops::{Add, Div, Mul, Neg, Sub};
+use core::str::FromStr;
+
+const fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    if a < 0 {
+        -a
+    } else {
+        a
+    }
+}
+
+//`f64::floor` for non-negative values, since it isn't available without `std` (any value of
+//at least 2^52 is already an integer)
+fn floor(x: f64) -> f64 {
+    if x >= 4503599627370496.0 {
+        x
+    } else {
+        x as u64 as f64
+    }
+}
+
+//an exact fraction, always stored in lowest terms with a positive denominator
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    num: i128,
+    den: i128,
+}
+
+impl Rational {
+    pub const ZERO: Rational = Rational { num: 0, den: 1 };
+    pub const ONE: Rational = Rational { num: 1, den: 1 };
+
+    //panics if `den` is zero
+    pub const fn new(num: i128, den: i128) -> Self {
+        assert!(den != 0, "zero denominator");
+        let g = gcd(num, den);
+        let s = if den < 0 { -1 } else { 1 };
+        Rational {
+            num: s * num / g,
+            den: s * den / g,
+        }
+    }
+
+    pub const fn integer(n: i128) -> Self {
+        Rational { num: n, den: 1 }
+    }
+
+    pub fn checked_new(num: i128, den: i128) -> Option<Self> {
+        if den == 0 || num == i128::MIN || den == i128::MIN {
+            None
+        } else {
+            Some(Self::new(num, den))
+        }
+    }
+
+    pub fn numer(&self) -> i128 {
+        self.num
+    }
+    pub fn denom(&self) -> i128 {
+        self.den
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        let g = gcd(self.den, rhs.den);
+        let den = (self.den / g).checked_mul(rhs.den)?;
+        let num = self
+            .num
+            .checked_mul(rhs.den / g)?
+            .checked_add(rhs.num.checked_mul(self.den / g)?)?;
+        Self::checked_new(num, den)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.checked_add(-rhs)
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        //cross-cancel first to keep the intermediate values small
+        let g1 = gcd(self.num, rhs.den).max(1);
+        let g2 = gcd(rhs.num, self.den).max(1);
+        let num = (self.num / g1).checked_mul(rhs.num / g2)?;
+        let den = (self.den / g2).checked_mul(rhs.den / g1)?;
+        Self::checked_new(num, den)
+    }
+
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs.num == 0 {
+            None
+        } else {
+            self.checked_mul(Rational {
+                num: rhs.den * rhs.num.signum(),
+                den: rhs.num.abs(),
+            })
+        }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.num as f64 / self.den as f64
+    }
+
+    //converts to any `Real` type, rounding only once at the final division as long
+    //as the intermediate integers are exactly representable in the given type
+    pub fn to_real<R: Real>(self) -> R {
+        //splits off 52 bits at a time so that every call to R::repr is exact
+        fn int<R: Real>(n: i128) -> R {
+            const BASE: i128 = 1 << 52;
+            if n.abs() < BASE {
+                R::repr(n as f64)
+            } else {
+                int::<R>(n / BASE) * R::repr(BASE as f64) + R::repr((n % BASE) as f64)
+            }
+        }
+        if self.den == 1 {
+            int(self.num)
+        } else {
+            int::<R>(self.num) / int::<R>(self.den)
+        }
+    }
+
+    //the simplest fraction that converts back to exactly the given float
+    pub fn from_f64(x: f64) -> Option<Self> {
+        if !x.is_finite() {
+            return None;
+        }
+        let (sign, a) = (if x < 0.0 { -1 } else { 1 }, x.abs());
+
+        //first try the continued-fraction convergents since these recover values like 1/3
+        let (mut h, mut h1, mut k, mut k1): (i128, i128, i128, i128) = (1, 0, 0, 1);
+        let mut r = a;
+        for _ in 0..64 {
+            let q = floor(r);
+            if q >= 1e30 {
+                break;
+            }
+            let q = q as i128;
+            let (h2, k2) = (
+                q.checked_mul(h)?.checked_add(h1)?,
+                q.checked_mul(k)?.checked_add(k1)?,
+            );
+            (h1, k1, h, k) = (h, k, h2, k2);
+            if h as f64 / k as f64 == a {
+                return Some(Rational::new(sign * h, k));
+            }
+            let frac = r - floor(r);
+            if frac == 0.0 {
+                break;
+            }
+            r = 1.0 / frac;
+        }
+
+        //otherwise, use the exact binary value of the float
+        let bits = a.to_bits();
+        let exp = ((bits >> 52) & 0x7ff) as i32;
+        let (mantissa, exp) = if exp == 0 {
+            ((bits & ((1 << 52) - 1)) as i128, -1074)
+        } else {
+            (((bits & ((1 << 52) - 1)) | (1 << 52)) as i128, exp - 1075)
+        };
+        if exp >= 0 {
+            (exp < 70).then(|| Rational::integer(sign * (mantissa << exp)))
+        } else {
+            (exp > -126).then(|| Rational::new(sign * mantissa, 1 << -exp))
+        }
+    }
+}
+
+impl Default for Rational {
+    fn default() -> Self {
+        Rational::ZERO
+    }
+}
+
+impl Display for Rational {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+impl Debug for Rational {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, rhs: &Self) -> Ordering {
+        if let (Some(l), Some(r)) = (self.num.checked_mul(rhs.den), rhs.num.checked_mul(self.den)) {
+            return l.cmp(&r);
+        }
+        let sign = self.num.signum().cmp(&rhs.num.signum());
+        if sign != Ordering::Equal {
+            return sign;
+        }
+        let order = cmp_fractions(
+            self.num.unsigned_abs(),
+            self.den as u128,
+            rhs.num.unsigned_abs(),
+            rhs.den as u128,
+        );
+        if self.num < 0 {
+            order.reverse()
+        } else {
+            order
+        }
+    }
+}
+
+//compares a/b with c/d exactly, for when the cross products overflow, by comparing their
+//continued fractions term by term
+fn cmp_fractions(mut a: u128, mut b: u128, mut c: u128, mut d: u128) -> Ordering {
+    let mut flipped = false;
+    let order = loop {
+        let order = (a / b).cmp(&(c / d));
+        if order != Ordering::Equal {
+            break order;
+        }
+        //with equal integer parts, the larger fraction has the smaller reciprocal remainder
+        match (a % b, c % d) {
+            (0, 0) => break Ordering::Equal,
+            (0, _) => break Ordering::Less,
+            (_, 0) => break Ordering::Greater,
+            (r1, r2) => {
+                (a, b, c, d) = (b, r1, d, r2);
+                flipped = !flipped;
+            }
+        }
+    };
+    if flipped {
+        order.reverse()
+    } else {
+        order
+    }
+}
+
+impl Neg for Rational {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Rational {
+            num: -self.num,
+            den: self.den,
+        }
+    }
+}
+
+macro_rules! impl_ops {
+    ($($tr:ident::$f:ident => $checked:ident),*) => {$(
+        impl $tr for Rational {
+            type Output = Self;
+            fn $f(self, rhs: Self) -> Self {
+                self.$checked(rhs).expect(concat!("overflow or division by zero in Rational::", stringify!($f)))
+            }
+        }
+    )*};
+}
+impl_ops!(Add::add => checked_add, Sub::sub => checked_sub, Mul::mul => checked_mul, Div::div => checked_div);
+
+impl From<i64> for Rational {
+    fn from(n: i64) -> Self {
+        Rational::integer(n as i128)
+    }
+}
+
+impl From<Rational> for f64 {
+    fn from(x: Rational) -> f64 {
+        x.to_f64()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ParseRationalError;
+
+impl Display for ParseRationalError {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        write!(f, "invalid rational number")
+    }
+}
+
+impl ::core::error::Error for ParseRationalError {}
+
+//parses "p/q" as well as decimals with an optional exponent (eg "-1.25e-3") exactly
+impl FromStr for Rational {
+    type Err = ParseRationalError;
+    fn from_str(s: &str) -> Result<Self, ParseRationalError> {
+        let s = s.trim();
+        if let Some((p, q)) = s.split_once('/') {
+            if q.contains('/') {
+                return Err(ParseRationalError);
+            }
+            let p: Rational = p.parse()?;
+            let q: Rational = q.parse()?;
+            return p.checked_div(q).ok_or(ParseRationalError);
+        }
+
+        let (mantissa, exp) = match s.find(['e', 'E']) {
+            Some(i) => (
+                &s[..i],
+                i32::from_str(&s[i + 1..]).map_err(|_| ParseRationalError)?,
+            ),
+            None => (s, 0),
+        };
+        let (sign, digits) = match mantissa.as_bytes().first() {
+            Some(b'-') => (-1, &mantissa[1..]),
+            Some(b'+') => (1, &mantissa[1..]),
+            _ => (1, mantissa),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        if (int.is_empty() && frac.is_empty())
+            || !int
+                .bytes()
+                .chain(frac.bytes())
+                .all(|b| b.is_ascii_digit() || b == b'_')
+        {
+            return Err(ParseRationalError);
+        }
+
+        let mut num: i128 = 0;
+        for b in int.bytes().chain(frac.bytes()).filter(|&b| b != b'_') {
+            num = num
+                .checked_mul(10)
+                .and_then(|n| n.checked_add((b - b'0') as i128))
+                .ok_or(ParseRationalError)?;
+        }
+        let exp = i32::try_from(frac.bytes().filter(|&b| b != b'_').count())
+            .ok()
+            .and_then(|digits| exp.checked_sub(digits))
+            .ok_or(ParseRationalError)?;
+        let pow = 10i128
+            .checked_pow(exp.unsigned_abs())
+            .ok_or(ParseRationalError)?;
+        if exp >= 0 {
+            num.checked_mul(pow)
+                .map(|n| Rational::integer(sign * n))
+                .ok_or(ParseRationalError)
+        } else {
+            Ok(Rational::new(sign * num, pow))
+        }
+    }
+}
+
+//a coefficient of a Butcher tableau
+pub trait Coefficient: Copy + Default + PartialEq + Debug + Into<f64> {
+    fn to_f64(self) -> f64;
+    fn to_real<R: Real>(self) -> R;
+    fn to_rational(self) -> Option<Rational>;
+    fn from_rational(x: Rational) -> Self;
+    fn is_zero(self) -> bool;
+}
+
+impl Coefficient for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+    fn to_real<R: Real>(self) -> R {
+        R::repr(self)
+    }
+    //floats are never treated as exact, since most tableau entries are rounded
+    fn to_rational(self) -> Option<Rational> {
+        None
+    }
+    fn from_rational(x: Rational) -> Self {
+        x.to_f64()
+    }
+    fn is_zero(self) -> bool {
+        self == 0.0
+    }
+}
+
+impl Coefficient for Rational {
+    fn to_f64(self) -> f64 {
+        Rational::to_f64(self)
+    }
+    fn to_real<R: Real>(self) -> R {
+        Rational::to_real(self)
+    }
+    fn to_rational(self) -> Option<Rational> {
+        Some(self)
+    }
+    fn from_rational(x: Rational) -> Self {
+        x
+    }
+    fn is_zero(self) -> bool {
+        self.num == 0
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_and_parse_round_trip() {
+        for (text, x) in [
+            ("0", Rational::ZERO),
+            ("-8", Rational::integer(-8)),
+            ("1932/2197", Rational::new(1932, 2197)),
+            ("-3544/2565", Rational::new(3544, -2565)),
+            ("1/3", Rational::new(2, 6)),
+        ] {
+            assert_eq!(x.to_string(), text);
+            assert_eq!(text.parse(), Ok(x));
+        }
+        for tableau in [DORMAND_PRINCE, RK_FELBERG] {
+            for i in 0..tableau.stages() {
+                for j in 0..tableau.stages() {
+                    let a = tableau.a(i, j);
+                    assert_eq!(a.to_string().parse(), Ok(a));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parses_decimals_exactly() {
+        assert_eq!("0.1".parse(), Ok(Rational::new(1, 10)));
+        assert_eq!("-1.25e-3".parse(), Ok(Rational::new(-1, 800)));
+        assert_eq!("2.5E2".parse(), Ok(Rational::integer(250)));
+        assert_eq!("1_000".parse(), Ok(Rational::integer(1000)));
+        assert_eq!("0.5/3".parse(), Ok(Rational::new(1, 6)));
+    }
+
+    #[test]
+    fn rejects_invalid_numbers() {
+        for text in [
+            "",
+            ".",
+            "-",
+            "1/0",
+            "1/",
+            "a",
+            "1e",
+            "1.2.3",
+            "1e99999",
+            "1e40",
+            "1/2/3",
+            "0.1e-2147483648",
+        ] {
+            assert_eq!(
+                text.parse::<Rational>(),
+                Err(ParseRationalError),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn checked_arithmetic_reports_overflow() {
+        let big = Rational::integer(i128::MAX);
+        assert_eq!(big.checked_add(Rational::ONE), None);
+        assert_eq!(big.checked_mul(Rational::integer(2)), None);
+        assert_eq!(
+            Rational::new(1, i128::MAX).checked_add(Rational::new(1, 3)),
+            None
+        );
+        assert_eq!(Rational::ONE.checked_div(Rational::ZERO), None);
+        assert_eq!(Rational::checked_new(1, 0), None);
+
+        //cross-cancelling keeps products of large but compatible fractions in range
+        let x = Rational::new(i128::MAX, 3);
+        assert_eq!(
+            x.checked_mul(Rational::new(3, i128::MAX)),
+            Some(Rational::ONE)
+        );
+    }
+
+    #[test]
+    fn compares_exactly_when_cross_products_overflow() {
+        //both are within 1e-38 of one, so they round to the same float
+        let m = i128::MAX;
+        let (a, b) = (Rational::new(m, m - 1), Rational::new(m - 1, m - 2));
+        assert_eq!(a.to_f64(), b.to_f64());
+        assert_eq!(a.cmp(&b), Ordering::Less);
+        assert_eq!(b.cmp(&a), Ordering::Greater);
+        assert_eq!((-a).cmp(&-b), Ordering::Greater);
+        assert_eq!(a.cmp(&a), Ordering::Equal);
+        assert_eq!((-a).cmp(&b), Ordering::Less);
+
+        //and fractions near 1/7 whose continued fractions only differ after a few terms
+        let (c, d) = (Rational::new(m / 7, m - 2), Rational::new(m / 7 + 1, m));
+        assert_eq!(c.cmp(&d), Ordering::Less);
+        assert_eq!(d.cmp(&c), Ordering::Greater);
+    }
+
+    #[test]
+    fn converts_floats() {
+        assert_eq!(Rational::from_f64(1.0 / 3.0), Some(Rational::new(1, 3)));
+        assert_eq!(Rational::from_f64(-0.75), Some(Rational::new(-3, 4)));
+        assert_eq!(Rational::from_f64(f64::NAN), None);
+        assert_eq!(Rational::new(1932, 2197).to_real::<f64>(), 1932.0 / 2197.0);
+        assert!(Rational::new(1, 3) < Rational::new(1, 2));
+    }
+}
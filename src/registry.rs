@@ -0,0 +1,312 @@
+use super::*;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Method {
+    RungeKutta(RungeKutta<'static, Rational>),
+    AdaptiveRungeKutta(AdaptiveRungeKutta<'static, Rational>),
+    OwnedRungeKutta(OwnedRungeKutta),
+    OwnedAdaptiveRungeKutta(OwnedAdaptiveRungeKutta),
+    VelocityVerlet,
+}
+
+impl Method {
+    pub fn integrator<R: Real, D: Clone + Default, S: State<R>>(
+        &self,
+    ) -> Option<Box<dyn Integrates<R, D, S>>> {
+        match self {
+            Method::RungeKutta(rk) => Some(Box::new(*rk)),
+            Method::OwnedRungeKutta(rk) => Some(Box::new(rk.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn vel_integrator<R: Real, D: Clone + Default, S: State<R>>(
+        &self,
+    ) -> Option<Box<dyn VelIntegrates<R, D, S>>> {
+        match self {
+            Method::RungeKutta(rk) => Some(Box::new(*rk)),
+            Method::OwnedRungeKutta(rk) => Some(Box::new(rk.clone())),
+            Method::VelocityVerlet => Some(Box::new(VelocityVerlet)),
+            _ => None,
+        }
+    }
+
+    pub fn adaptive_integrator<R: Real, D: Clone + Default, S: State<R>>(
+        &self,
+    ) -> Option<Box<dyn AdaptiveIntegrates<R, D, S>>> {
+        match self {
+            Method::AdaptiveRungeKutta(rk) => Some(Box::new(*rk)),
+            Method::OwnedAdaptiveRungeKutta(rk) => Some(Box::new(rk.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn stages(&self) -> usize {
+        match self {
+            Method::RungeKutta(rk) => rk.stages(),
+            Method::AdaptiveRungeKutta(rk) => rk.stages(),
+            Method::OwnedRungeKutta(rk) => rk.stages(),
+            Method::OwnedAdaptiveRungeKutta(rk) => rk.stages(),
+            Method::VelocityVerlet => 1,
+        }
+    }
+
+    pub fn order(&self) -> usize {
+        match self {
+            Method::RungeKutta(rk) => rk.order(),
+            Method::AdaptiveRungeKutta(rk) => rk.order(),
+            Method::OwnedRungeKutta(rk) => rk.order(),
+            Method::OwnedAdaptiveRungeKutta(rk) => rk.order(),
+            Method::VelocityVerlet => 2,
+        }
+    }
+
+    pub fn embedded_order(&self) -> Option<usize> {
+        match self {
+            Method::AdaptiveRungeKutta(rk) => Some(rk.embedded_order()),
+            Method::OwnedAdaptiveRungeKutta(rk) => Some(rk.embedded_order()),
+            _ => None,
+        }
+    }
+
+    pub fn is_adaptive(&self) -> bool {
+        matches!(
+            self,
+            Method::AdaptiveRungeKutta(_) | Method::OwnedAdaptiveRungeKutta(_)
+        )
+    }
+
+    pub fn is_implicit(&self) -> bool {
+        false
+    }
+
+    pub fn is_symplectic(&self) -> bool {
+        matches!(self, Method::VelocityVerlet)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MethodInfo {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub order: usize,
+    pub embedded_order: Option<usize>,
+    pub stages: usize,
+    pub implicit: bool,
+    pub adaptive: bool,
+    pub symplectic: bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entry {
+    pub info: MethodInfo,
+    pub method: Method,
+}
+
+impl Entry {
+    pub fn new(name: &str, method: Method) -> Self {
+        Entry {
+            info: MethodInfo {
+                name: normalize(name),
+                aliases: Vec::new(),
+                order: method.order(),
+                embedded_order: method.embedded_order(),
+                stages: method.stages(),
+                implicit: method.is_implicit(),
+                adaptive: method.is_adaptive(),
+                symplectic: method.is_symplectic(),
+            },
+            method,
+        }
+    }
+
+    pub fn alias(mut self, alias: &str) -> Self {
+        self.info.aliases.push(normalize(alias));
+        self
+    }
+
+    fn is_named(&self, name: &str) -> bool {
+        self.info.name == name || self.info.aliases.iter().any(|a| a == name)
+    }
+}
+
+//names are matched case-insensitively and with '_' and ' ' treated the same as '-'
+fn normalize(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| match c {
+            '_' | ' ' => '-',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+impl Registry {
+    pub fn empty() -> Self {
+        Registry {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn new() -> Self {
+        use Method::*;
+
+        let mut registry = Self::empty();
+        let builtin = [
+            Entry::new("euler", RungeKutta(EULER))
+                .alias("rk1")
+                .alias("forward-euler"),
+            Entry::new("midpoint", RungeKutta(MIDPOINT)).alias("rk2"),
+            Entry::new("heun2", RungeKutta(HEUN2)).alias("heun"),
+            Entry::new("ralston", RungeKutta(RALSTON)),
+            Entry::new("rk3", RungeKutta(RK3)).alias("kutta3"),
+            Entry::new("heun3", RungeKutta(HEUN3)),
+            Entry::new("rk4", RungeKutta(RK4))
+                .alias("classic-rk4")
+                .alias("runge-kutta"),
+            Entry::new("rk-3-8", RungeKutta(RK_3_8))
+                .alias("rk38")
+                .alias("3/8"),
+            Entry::new("euler-heun", AdaptiveRungeKutta(EULER_HEUN))
+                .alias("heun-euler")
+                .alias("rk12"),
+            Entry::new("bogacki-shampine", AdaptiveRungeKutta(BOGACKI_SHAMPINE))
+                .alias("bs23")
+                .alias("rk23"),
+            Entry::new("rk-fehlberg", AdaptiveRungeKutta(RK_FELBERG))
+                .alias("rk-felberg")
+                .alias("fehlberg")
+                .alias("rkf45"),
+            Entry::new("dormand-prince", AdaptiveRungeKutta(DORMAND_PRINCE))
+                .alias("dopri5")
+                .alias("rk45"),
+            Entry::new("velocity-verlet", VelocityVerlet).alias("verlet"),
+        ];
+        for entry in builtin {
+            registry.register(entry);
+        }
+        registry
+    }
+
+    //adds the given entry, returning any previous entry that had the same name
+    //
+    //the newest entry shadows any other entry with one of its names or aliases, whether that
+    //is the other entry's name or one of its aliases, until it is removed again
+    pub fn register(&mut self, entry: Entry) -> Option<Entry> {
+        let previous = self
+            .entries
+            .iter()
+            .position(|e| e.info.name == entry.info.name)
+            .map(|i| self.entries.remove(i));
+
+        self.entries.push(entry);
+        previous
+    }
+
+    pub fn register_tableau<T: Into<OwnedRungeKutta>>(
+        &mut self,
+        name: &str,
+        aliases: &[&str],
+        tableau: T,
+    ) -> Option<Entry> {
+        let entry = aliases.iter().fold(
+            Entry::new(name, Method::OwnedRungeKutta(tableau.into())),
+            |e, a| e.alias(a),
+        );
+        self.register(entry)
+    }
+
+    pub fn register_adaptive_tableau<T: Into<OwnedAdaptiveRungeKutta>>(
+        &mut self,
+        name: &str,
+        aliases: &[&str],
+        tableau: T,
+    ) -> Option<Entry> {
+        let entry = aliases.iter().fold(
+            Entry::new(name, Method::OwnedAdaptiveRungeKutta(tableau.into())),
+            |e, a| e.alias(a),
+        );
+        self.register(entry)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        let name = normalize(name);
+        self.entries.iter().rev().find(|e| e.is_named(&name))
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Entry> {
+        let name = normalize(name);
+        self.entries
+            .iter()
+            .rposition(|e| e.is_named(&name))
+            .map(|i| self.entries.remove(i))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|e| e.info.name.as_str())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//looks up one of the built-in methods by name or alias
+//
+//with `std` the built-in registry is only built once, and otherwise on every call
+pub fn method_by_name(name: &str) -> Option<Entry> {
+    #[cfg(feature = "std")]
+    {
+        static BUILT_IN: std::sync::OnceLock<Registry> = std::sync::OnceLock::new();
+        BUILT_IN.get_or_init(Registry::new).get(name).cloned()
+    }
+    #[cfg(not(feature = "std"))]
+    Registry::new().get(name).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newer_aliases_shadow_existing_names() {
+        let mut registry = Registry::new();
+        registry.register_tableau("mine", &["rk4", "rk38"], RK3);
+        assert_eq!(registry.get("rk4").unwrap().info.name, "mine");
+        assert_eq!(registry.get("rk38").unwrap().info.name, "mine");
+        assert_eq!(registry.get("classic-rk4").unwrap().info.name, "rk4");
+
+        //removing the newer entry makes the shadowed names visible again
+        assert_eq!(registry.remove("rk4").unwrap().info.name, "mine");
+        assert_eq!(registry.get("rk4").unwrap().info.name, "rk4");
+        assert_eq!(registry.get("rk38").unwrap().info.name, "rk-3-8");
+    }
+
+    #[test]
+    fn registering_the_same_name_replaces_the_entry() {
+        let mut registry = Registry::new();
+        let previous = registry.register_tableau("rk4", &[], RK3).unwrap();
+        assert_eq!(previous.method, Method::RungeKutta(RK4));
+        assert_eq!(registry.get("rk4").unwrap().info.order, 3);
+        assert!(registry.get("classic-rk4").is_none());
+    }
+
+    #[test]
+    fn built_in_methods_are_found_by_name() {
+        for _ in 0..2 {
+            assert_eq!(method_by_name("classic-rk4").unwrap().info.name, "rk4");
+            assert!(method_by_name("not-a-method").is_none());
+        }
+    }
+}
@@ -1,21 +1,39 @@
 use super::*;
 
-use std::fmt::{Debug, Formatter};
+use core::fmt::{Debug, Display, Formatter};
 
+//the tolerance used when checking the consistency conditions of a tableau
+pub const TABLEAU_TOLERANCE: f64 = 1e-10;
+
+//`Parse` only exists with the `alloc` feature, so the variants aren't fixed for other crates
 #[derive(Clone, Copy, PartialEq)]
+#[non_exhaustive]
 pub enum RKError {
     EmptyTableau,
-    JaggedTableau,
+    JaggedTableau(usize, usize, usize),
     TooManyColumns(usize, usize),
     NonSquareTableau(usize, usize),
     UnsupportedImplicit,
+    MismatchedStages(usize, usize),
+    #[cfg(feature = "alloc")]
+    Parse(Position, ParseError),
+    OrderMismatch(usize, usize),
+    TooManyRows(usize, usize),
+    NonFinite(usize, usize),
+    RowSum(usize, f64),
+    WeightSum(usize, f64),
+    DegenerateEmbedding(usize),
 }
 
-impl Debug for RKError {
-    fn fmt(&self, f: &mut Formatter) -> ::std::fmt::Result {
+impl Display for RKError {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
         match self {
             RKError::EmptyTableau => write!(f, "Zero-length Runge-Kutta matrix"),
-            RKError::JaggedTableau => write!(f, "Tableau is non-rectangular"),
+            RKError::JaggedTableau(i, c, l) => write!(
+                f,
+                "Tableau is non-rectangular; row {} has length {} but expected {}",
+                i, l, c
+            ),
             RKError::TooManyColumns(r, c) => write!(f, "Tableau has {} rows but {} columns", r, c),
             RKError::NonSquareTableau(r, c) => write!(
                 f,
@@ -23,20 +41,61 @@ impl Debug for RKError {
                 r, c
             ),
             RKError::UnsupportedImplicit => write!(f, "Implicit Runge-Kutta not supported"),
+            RKError::MismatchedStages(s, l) => write!(
+                f,
+                "Tableau has {} stages but a coefficient vector of length {}",
+                s, l
+            ),
+            #[cfg(feature = "alloc")]
+            RKError::Parse(p, e) => write!(f, "{}:{}: {}", p.line, p.column, e),
+            RKError::OrderMismatch(claimed, actual) => write!(
+                f,
+                "Tableau is claimed to be of order {} but is of order {}",
+                claimed, actual
+            ),
+            RKError::TooManyRows(r, max) => write!(
+                f,
+                "Tableau has {} rows but at most {} are allowed for its number of columns",
+                r, max
+            ),
+            RKError::NonFinite(i, j) => write!(f, "Entry at row {} column {} is not finite", i, j),
+            RKError::RowSum(i, tol) => write!(
+                f,
+                "Row {} of the Runge-Kutta matrix does not sum to its node c_{} (tolerance {:e})",
+                i, i, tol
+            ),
+            RKError::WeightSum(i, tol) => write!(
+                f,
+                "Weights in row {} do not sum to 1 (tolerance {:e})",
+                i, tol
+            ),
+            RKError::DegenerateEmbedding(i) => write!(
+                f,
+                "Embedded weights in row {} are the same as the main weights",
+                i
+            ),
         }
     }
 }
 
+impl Debug for RKError {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl ::core::error::Error for RKError {}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
-pub enum ButcherTableau<'a> {
-    Fixed(&'a [&'a [f64]]),
-    Adaptive(&'a [&'a [f64]]),
-    Implicit(&'a [&'a [f64]]),
-    AdaptiveImplicit(&'a [&'a [f64]]),
+pub enum ButcherTableau<'a, C = f64> {
+    Fixed(&'a [&'a [C]]),
+    Adaptive(&'a [&'a [C]]),
+    Implicit(&'a [&'a [C]]),
+    AdaptiveImplicit(&'a [&'a [C]]),
 }
 
-impl<'a> ButcherTableau<'a> {
-    fn new(table: &'a [&'a [f64]]) -> Result<Self, RKError> {
+impl<'a, C: Coefficient> ButcherTableau<'a, C> {
+    fn new(table: &'a [&'a [C]]) -> Result<Self, RKError> {
         use ButcherTableau::*;
         use RKError::*;
 
@@ -52,20 +111,64 @@ impl<'a> ButcherTableau<'a> {
                 return Err(TooManyColumns(rows, columns));
             }
 
+            //and not so many that there is more than one row of embedded weights
+            if rows > columns + 1 {
+                return Err(TooManyRows(rows, columns + 1));
+            }
+
             //check if the tableau is of an implict method and make sure we have a non-jagged array
+            //(the first column holds c and only the first columns-1 rows hold the RK matrix)
             let mut implicit = false;
-            for i in 0..rows {
-                if table[i].len() != columns {
-                    return Err(JaggedTableau);
+            for (i, row) in table.iter().enumerate() {
+                if row.len() != columns {
+                    return Err(JaggedTableau(i, columns, row.len()));
                 }
-                for j in i..columns {
-                    if table[i][j] != 0.0 {
-                        implicit = true;
-                        break;
-                    }
+                if let Some(j) = row.iter().position(|a| !a.to_f64().is_finite()) {
+                    return Err(NonFinite(i, j));
+                }
+                if i + 1 < columns && row.iter().skip(i + 1).any(|a| !a.is_zero()) {
+                    implicit = true;
                 }
             }
 
+            //make sure the tableau is consistent, ie c_i = Σa_ij, and Σb_j = 1 for every row of weights
+            //(exactly if the row is exact and its sum doesn't overflow, otherwise to within the tolerance)
+            let close =
+                |x: f64, y: f64, scale: f64| (x - y).abs() <= TABLEAU_TOLERANCE * scale.max(1.0);
+            let stages = columns - 1;
+            for (i, row) in table.iter().enumerate() {
+                let expected = if i < stages {
+                    row[0]
+                } else {
+                    C::from_rational(Rational::ONE)
+                };
+                let exact = row[1..]
+                    .iter()
+                    .try_fold(Rational::ZERO, |sum, a| sum.checked_add(a.to_rational()?));
+                let tolerance = match (expected.to_rational(), exact) {
+                    (Some(x), Some(sum)) if x == sum => continue,
+                    (Some(_), Some(_)) => 0.0,
+                    _ => {
+                        let sum: f64 = row[1..].iter().map(|a| a.to_f64()).sum();
+                        let scale: f64 = row[1..].iter().map(|a| a.to_f64().abs()).sum();
+                        if close(expected.to_f64(), sum, scale) {
+                            continue;
+                        }
+                        TABLEAU_TOLERANCE
+                    }
+                };
+                return Err(if i < stages {
+                    RowSum(i, tolerance)
+                } else {
+                    WeightSum(i, tolerance)
+                });
+            }
+
+            //the embedded weights have to differ from the main weights to give an error estimate
+            if rows > columns && table[stages][1..] == table[stages + 1][1..] {
+                return Err(DegenerateEmbedding(stages + 1));
+            }
+
             Ok(match (rows > columns, implicit) {
                 (false, false) => Fixed(table),
                 (true, false) => Adaptive(table),
@@ -77,186 +180,308 @@ impl<'a> ButcherTableau<'a> {
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
-pub struct RungeKutta<'a>(&'a [&'a [f64]]);
+pub struct RungeKutta<'a, C = f64>(&'a [&'a [C]]);
 
 #[derive(Clone, Copy, PartialEq, Debug)]
-pub struct AdaptiveRungeKutta<'a>(&'a [&'a [f64]]);
-
-pub const EULER: RungeKutta = RK1;
-pub const MIDPOINT: RungeKutta = RK2;
-pub const RK1: RungeKutta = RungeKutta(&[&[0.0, 0.0], &[0.0, 1.0]]);
-pub const RK2: RungeKutta = RungeKutta(&[&[0.0, 0.0, 0.0], &[0.5, 0.5, 0.0], &[0.0, 0.0, 1.0]]);
-pub const HEUN2: RungeKutta = RungeKutta(&[&[0.0, 0.0, 0.0], &[1.0, 1.0, 0.0], &[0.0, 0.5, 0.5]]);
-pub const RALSTON: RungeKutta = RungeKutta(&[
-    &[0.0, 0.0, 0.0],
-    &[2.0 / 3.0, 2.0 / 3.0, 0.0],
-    &[0.0, 0.25, 0.75],
+pub struct AdaptiveRungeKutta<'a, C = f64>(&'a [&'a [C]]);
+
+//an exact tableau entry, written either as `q!(n)` or `q!(n / d)`
+macro_rules! q {
+    ($n:literal / $d:literal) => {
+        Rational::new($n, $d)
+    };
+    ($n:literal) => {
+        Rational::integer($n)
+    };
+}
+
+pub const EULER: RungeKutta<Rational> = RK1;
+pub const MIDPOINT: RungeKutta<Rational> = RK2;
+pub const RK1: RungeKutta<Rational> = RungeKutta(&[&[q!(0), q!(0)], &[q!(0), q!(1)]]);
+pub const RK2: RungeKutta<Rational> = RungeKutta(&[
+    &[q!(0), q!(0), q!(0)],
+    &[q!(1 / 2), q!(1 / 2), q!(0)],
+    &[q!(0), q!(0), q!(1)],
 ]);
-pub const RK3: RungeKutta = RungeKutta(&[
-    &[0.0, 0.0, 0.0, 0.0],
-    &[0.5, 0.5, 0.0, 0.0],
-    &[1.0, -1.0, 2.0, 0.0],
-    &[0.0, 1.0 / 6.0, 2.0 / 3.0, 1.0 / 6.0],
+pub const HEUN2: RungeKutta<Rational> = RungeKutta(&[
+    &[q!(0), q!(0), q!(0)],
+    &[q!(1), q!(1), q!(0)],
+    &[q!(0), q!(1 / 2), q!(1 / 2)],
 ]);
-pub const HEUN3: RungeKutta = RungeKutta(&[
-    &[0.0, 0.0, 0.0, 0.0],
-    &[1.0 / 3.0, 1.0 / 3.0, 0.0, 0.0],
-    &[2.0 / 3.0, 0.0, 2.0 / 3.0, 0.0],
-    &[0.0, 0.25, 0.0, 0.75],
+pub const RALSTON: RungeKutta<Rational> = RungeKutta(&[
+    &[q!(0), q!(0), q!(0)],
+    &[q!(2 / 3), q!(2 / 3), q!(0)],
+    &[q!(0), q!(1 / 4), q!(3 / 4)],
 ]);
-pub const RK4: RungeKutta = RungeKutta(&[
-    &[0.0, 0.0, 0.0, 0.0, 0.0],
-    &[0.5, 0.5, 0.0, 0.0, 0.0],
-    &[0.5, 0.0, 0.5, 0.0, 0.0],
-    &[1.0, 0.0, 0.0, 1.0, 0.0],
-    &[0.0, 1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0],
+pub const RK3: RungeKutta<Rational> = RungeKutta(&[
+    &[q!(0), q!(0), q!(0), q!(0)],
+    &[q!(1 / 2), q!(1 / 2), q!(0), q!(0)],
+    &[q!(1), q!(-1), q!(2), q!(0)],
+    &[q!(0), q!(1 / 6), q!(2 / 3), q!(1 / 6)],
 ]);
-pub const RK_3_8: RungeKutta = RungeKutta(&[
-    &[0.0, 0.0, 0.0, 0.0, 0.0],
-    &[1.0 / 3.0, 1.0 / 3.0, 0.0, 0.0, 0.0],
-    &[2.0 / 3.0, -1.0 / 3.0, 1.0, 0.0, 0.0],
-    &[1.0, 1.0, -1.0, 1.0, 0.0],
-    &[0.0, 0.125, 0.375, 0.375, 0.125],
+pub const HEUN3: RungeKutta<Rational> = RungeKutta(&[
+    &[q!(0), q!(0), q!(0), q!(0)],
+    &[q!(1 / 3), q!(1 / 3), q!(0), q!(0)],
+    &[q!(2 / 3), q!(0), q!(2 / 3), q!(0)],
+    &[q!(0), q!(1 / 4), q!(0), q!(3 / 4)],
+]);
+pub const RK4: RungeKutta<Rational> = RungeKutta(&[
+    &[q!(0), q!(0), q!(0), q!(0), q!(0)],
+    &[q!(1 / 2), q!(1 / 2), q!(0), q!(0), q!(0)],
+    &[q!(1 / 2), q!(0), q!(1 / 2), q!(0), q!(0)],
+    &[q!(1), q!(0), q!(0), q!(1), q!(0)],
+    &[q!(0), q!(1 / 6), q!(1 / 3), q!(1 / 3), q!(1 / 6)],
+]);
+pub const RK_3_8: RungeKutta<Rational> = RungeKutta(&[
+    &[q!(0), q!(0), q!(0), q!(0), q!(0)],
+    &[q!(1 / 3), q!(1 / 3), q!(0), q!(0), q!(0)],
+    &[q!(2 / 3), q!(-1 / 3), q!(1), q!(0), q!(0)],
+    &[q!(1), q!(1), q!(-1), q!(1), q!(0)],
+    &[q!(0), q!(1 / 8), q!(3 / 8), q!(3 / 8), q!(1 / 8)],
 ]);
 
-pub const EULER_HEUN: AdaptiveRungeKutta = AdaptiveRungeKutta(&[
-    &[0.0, 0.0, 0.0],
-    &[1.0, 1.0, 0.0],
-    &[0.0, 0.5, 0.5],
-    &[0.0, 1.0, 0.0],
+pub const EULER_HEUN: AdaptiveRungeKutta<Rational> = AdaptiveRungeKutta(&[
+    &[q!(0), q!(0), q!(0)],
+    &[q!(1), q!(1), q!(0)],
+    &[q!(0), q!(1 / 2), q!(1 / 2)],
+    &[q!(0), q!(1), q!(0)],
 ]);
 
-pub const BOGACKI_SHAMPINE: AdaptiveRungeKutta = AdaptiveRungeKutta(&[
-    &[0.0, 0.0, 0.0, 0.0, 0.0],
-    &[0.5, 0.5, 0.0, 0.0, 0.0],
-    &[0.75, 0.0, 0.75, 0.0, 0.0],
-    &[1.0, 2.0 / 9.0, 1.0 / 3.0, 4.0 / 9.0, 0.0],
-    &[0.0, 2.0 / 9.0, 1.0 / 3.0, 4.0 / 9.0, 0.0],
-    &[0.0, 7.0 / 24.0, 0.25, 1.0 / 3.0, 0.125],
+pub const BOGACKI_SHAMPINE: AdaptiveRungeKutta<Rational> = AdaptiveRungeKutta(&[
+    &[q!(0), q!(0), q!(0), q!(0), q!(0)],
+    &[q!(1 / 2), q!(1 / 2), q!(0), q!(0), q!(0)],
+    &[q!(3 / 4), q!(0), q!(3 / 4), q!(0), q!(0)],
+    &[q!(1), q!(2 / 9), q!(1 / 3), q!(4 / 9), q!(0)],
+    &[q!(0), q!(2 / 9), q!(1 / 3), q!(4 / 9), q!(0)],
+    &[q!(0), q!(7 / 24), q!(1 / 4), q!(1 / 3), q!(1 / 8)],
 ]);
 
-pub const RK_FELBERG: AdaptiveRungeKutta = AdaptiveRungeKutta(&[
-    &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-    &[0.25, 0.25, 0.0, 0.0, 0.0, 0.0, 0.0],
-    &[0.375, 3.0 / 32.0, 9.0 / 32.0, 0.0, 0.0, 0.0, 0.0],
+pub const RK_FELBERG: AdaptiveRungeKutta<Rational> = AdaptiveRungeKutta(&[
+    &[q!(0), q!(0), q!(0), q!(0), q!(0), q!(0), q!(0)],
+    &[q!(1 / 4), q!(1 / 4), q!(0), q!(0), q!(0), q!(0), q!(0)],
     &[
-        12.0 / 13.0,
-        1932.0 / 2197.0,
-        -7200.0 / 2197.0,
-        7296.0 / 2197.0,
-        0.0,
-        0.0,
-        0.0,
+        q!(3 / 8),
+        q!(3 / 32),
+        q!(9 / 32),
+        q!(0),
+        q!(0),
+        q!(0),
+        q!(0),
     ],
     &[
-        1.0,
-        439.0 / 216.0,
-        -8.0,
-        3680.0 / 513.0,
-        -845.0 / 4104.0,
-        0.0,
-        0.0,
+        q!(12 / 13),
+        q!(1932 / 2197),
+        q!(-7200 / 2197),
+        q!(7296 / 2197),
+        q!(0),
+        q!(0),
+        q!(0),
     ],
     &[
-        0.5,
-        -8.0 / 27.0,
-        2.0,
-        -3544.0 / 2565.0,
-        1859.0 / 4104.0,
-        -11.0 / 40.0,
-        0.0,
+        q!(1),
+        q!(439 / 216),
+        q!(-8),
+        q!(3680 / 513),
+        q!(-845 / 4104),
+        q!(0),
+        q!(0),
     ],
     &[
-        0.0,
-        16.0 / 135.0,
-        0.0,
-        6656.0 / 12825.0,
-        28561.0 / 56430.0,
-        -9.0 / 50.0,
-        2.0 / 55.0,
+        q!(1 / 2),
+        q!(-8 / 27),
+        q!(2),
+        q!(-3544 / 2565),
+        q!(1859 / 4104),
+        q!(-11 / 40),
+        q!(0),
     ],
     &[
-        0.0,
-        25.0 / 216.0,
-        0.0,
-        1408.0 / 2565.0,
-        2197.0 / 4104.0,
-        -1.0 / 5.0,
-        0.0,
+        q!(0),
+        q!(16 / 135),
+        q!(0),
+        q!(6656 / 12825),
+        q!(28561 / 56430),
+        q!(-9 / 50),
+        q!(2 / 55),
+    ],
+    &[
+        q!(0),
+        q!(25 / 216),
+        q!(0),
+        q!(1408 / 2565),
+        q!(2197 / 4104),
+        q!(-1 / 5),
+        q!(0),
     ],
 ]);
 
-pub const DORMAND_PRINCE: AdaptiveRungeKutta = AdaptiveRungeKutta(&[
-    &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-    &[0.2, 0.2, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-    &[0.3, 3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+pub const DORMAND_PRINCE: AdaptiveRungeKutta<Rational> = AdaptiveRungeKutta(&[
+    &[q!(0), q!(0), q!(0), q!(0), q!(0), q!(0), q!(0), q!(0)],
     &[
-        0.4,
-        44.0 / 45.0,
-        -56.0 / 15.0,
-        32.0 / 9.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
+        q!(1 / 5),
+        q!(1 / 5),
+        q!(0),
+        q!(0),
+        q!(0),
+        q!(0),
+        q!(0),
+        q!(0),
     ],
     &[
-        8.0 / 9.0,
-        19372.0 / 6561.0,
-        -25360.0 / 2187.0,
-        64448.0 / 6561.0,
-        -212.0 / 729.0,
-        0.0,
-        0.0,
-        0.0,
+        q!(3 / 10),
+        q!(3 / 40),
+        q!(9 / 40),
+        q!(0),
+        q!(0),
+        q!(0),
+        q!(0),
+        q!(0),
     ],
     &[
-        1.0,
-        9017.0 / 3168.0,
-        -355.0 / 33.0,
-        46732.0 / 5247.0,
-        49.0 / 176.0,
-        -5103.0 / 18656.0,
-        0.0,
-        0.0,
+        q!(4 / 5),
+        q!(44 / 45),
+        q!(-56 / 15),
+        q!(32 / 9),
+        q!(0),
+        q!(0),
+        q!(0),
+        q!(0),
     ],
     &[
-        1.0,
-        35.0 / 384.0,
-        0.0,
-        500.0 / 1113.0,
-        125.0 / 192.0,
-        -2187.0 / 6784.0,
-        11.0 / 84.0,
-        0.0,
+        q!(8 / 9),
+        q!(19372 / 6561),
+        q!(-25360 / 2187),
+        q!(64448 / 6561),
+        q!(-212 / 729),
+        q!(0),
+        q!(0),
+        q!(0),
     ],
     &[
-        0.0,
-        35.0 / 384.0,
-        0.0,
-        500.0 / 1113.0,
-        125.0 / 192.0,
-        -2187.0 / 6784.0,
-        11.0 / 84.0,
-        0.0,
+        q!(1),
+        q!(9017 / 3168),
+        q!(-355 / 33),
+        q!(46732 / 5247),
+        q!(49 / 176),
+        q!(-5103 / 18656),
+        q!(0),
+        q!(0),
     ],
     &[
-        0.0,
-        5179.0 / 57600.0,
-        0.0,
-        7571.0 / 16695.0,
-        393.0 / 640.0,
-        -92097.0 / 339200.0,
-        187.0 / 2100.0,
-        1.0 / 4.0,
+        q!(1),
+        q!(35 / 384),
+        q!(0),
+        q!(500 / 1113),
+        q!(125 / 192),
+        q!(-2187 / 6784),
+        q!(11 / 84),
+        q!(0),
+    ],
+    &[
+        q!(0),
+        q!(35 / 384),
+        q!(0),
+        q!(500 / 1113),
+        q!(125 / 192),
+        q!(-2187 / 6784),
+        q!(11 / 84),
+        q!(0),
+    ],
+    &[
+        q!(0),
+        q!(5179 / 57600),
+        q!(0),
+        q!(7571 / 16695),
+        q!(393 / 640),
+        q!(-92097 / 339200),
+        q!(187 / 2100),
+        q!(1 / 40),
     ],
 ]);
 
-impl<'a> RungeKutta<'a> {
-    pub fn order(&self) -> usize {
+//uniform access to the coefficients of a tableau regardless of how it is stored
+pub trait Tableau {
+    type Coeff: Coefficient;
+
+    fn stages(&self) -> usize;
+    fn c(&self, i: usize) -> Self::Coeff;
+    fn a(&self, i: usize, j: usize) -> Self::Coeff;
+    fn b(&self, j: usize) -> Self::Coeff;
+    fn b_hat(&self, _j: usize) -> Option<Self::Coeff> {
+        None
+    }
+
+    //the classical order, which owned adaptive tableaux work out once when they're built
+    #[cfg(feature = "alloc")]
+    fn order(&self) -> usize {
+        classical_order(self)
+    }
+
+    #[cfg(feature = "std")]
+    fn stability_function(&self) -> StabilityFunction {
+        StabilityFunction::of(self)
+    }
+    #[cfg(feature = "std")]
+    fn embedded_stability_function(&self) -> Option<StabilityFunction> {
+        StabilityFunction::of_embedded(self)
+    }
+
+    //"first same as last": the last stage is evaluated at the new solution, so its
+    //derivative can be reused as the first stage of the next step
+    fn is_fsal(&self) -> bool {
+        let s = self.stages();
+        self.c(0).is_zero()
+            && self.c(s - 1) == Self::Coeff::from_rational(Rational::ONE)
+            && (0..s).all(|j| self.a(s - 1, j) == self.b(j))
+    }
+}
+
+impl<'a, C: Coefficient> Tableau for RungeKutta<'a, C> {
+    type Coeff = C;
+
+    fn stages(&self) -> usize {
         self.0.len() - 1
     }
-    pub fn from_matrix(rk_matrix: &'a [&'a [f64]]) -> Result<Self, RKError> {
+    fn c(&self, i: usize) -> C {
+        self.0[i][0]
+    }
+    fn a(&self, i: usize, j: usize) -> C {
+        self.0[i][j + 1]
+    }
+    fn b(&self, j: usize) -> C {
+        self.0[self.stages()][j + 1]
+    }
+}
+
+impl<'a, C: Coefficient> Tableau for AdaptiveRungeKutta<'a, C> {
+    type Coeff = C;
+
+    fn stages(&self) -> usize {
+        self.0[0].len() - 1
+    }
+    fn c(&self, i: usize) -> C {
+        self.0[i][0]
+    }
+    fn a(&self, i: usize, j: usize) -> C {
+        self.0[i][j + 1]
+    }
+    fn b(&self, j: usize) -> C {
+        self.0[self.stages()][j + 1]
+    }
+    fn b_hat(&self, j: usize) -> Option<C> {
+        Some(self.0[self.stages() + 1][j + 1])
+    }
+}
+
+impl<'a, C: Coefficient> RungeKutta<'a, C> {
+    #[cfg(feature = "alloc")]
+    pub fn order(&self) -> usize {
+        classical_order(self)
+    }
+    pub fn stages(&self) -> usize {
+        Tableau::stages(self)
+    }
+    pub fn from_matrix(rk_matrix: &'a [&'a [C]]) -> Result<Self, RKError> {
         match ButcherTableau::new(rk_matrix)? {
             ButcherTableau::Fixed(t) => Ok(RungeKutta(t)),
             ButcherTableau::Implicit(_) => Err(RKError::UnsupportedImplicit),
@@ -268,11 +493,19 @@ impl<'a> RungeKutta<'a> {
     }
 }
 
-impl<'a> AdaptiveRungeKutta<'a> {
+impl<'a, C: Coefficient> AdaptiveRungeKutta<'a, C> {
+    #[cfg(feature = "alloc")]
     pub fn order(&self) -> usize {
-        self.0[0].len() - 1
+        classical_order(self)
+    }
+    #[cfg(feature = "alloc")]
+    pub fn embedded_order(&self) -> usize {
+        embedded_order(self).unwrap()
     }
-    pub fn from_matrix(rk_matrix: &'a [&'a [f64]]) -> Result<Self, RKError> {
+    pub fn stages(&self) -> usize {
+        Tableau::stages(self)
+    }
+    pub fn from_matrix(rk_matrix: &'a [&'a [C]]) -> Result<Self, RKError> {
         match ButcherTableau::new(rk_matrix)? {
             ButcherTableau::Adaptive(t) => Ok(AdaptiveRungeKutta(t)),
             ButcherTableau::Fixed(t) => Err(RKError::TooManyColumns(t.len(), t[0].len())),
@@ -281,140 +514,884 @@ impl<'a> AdaptiveRungeKutta<'a> {
     }
 }
 
-impl<'a> VelIntegrator for RungeKutta<'a> {
-    fn step_with_vel<
-        R: Real,
-        D: Clone + Default,
-        S: VectorSpace<R>,
-        V: Fn(R, S) -> (D, S),
-        F: Fn(R, S) -> (D, S),
-    >(
-        &self,
-        time: R,
-        state: &mut [(D, S)],
-        dt: R,
-        _: V,
-        force: F,
-    ) -> (D, S) {
-        Integrator::step(self, time, state, dt, force)
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OwnedRungeKutta<C = f64> {
+    c: Vec<C>,
+    a: Vec<Vec<C>>,
+    b: Vec<C>,
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OwnedAdaptiveRungeKutta<C = f64> {
+    c: Vec<C>,
+    a: Vec<Vec<C>>,
+    b: Vec<C>,
+    b_hat: Vec<C>,
+    //worked out once here, since every `adaptive_init` needs it to choose the first step
+    #[cfg_attr(feature = "serde", serde(skip))]
+    order: usize,
+}
+
+//checks that c, A, and b (and b̂ if present) all agree on the number of stages
+//and then validates them in the same way as for a borrowed tableau
+#[cfg(feature = "alloc")]
+fn check_coefficients<C: Coefficient>(
+    c: &[C],
+    a: &[Vec<C>],
+    b: &[C],
+    b_hat: Option<&[C]>,
+) -> Result<(), RKError> {
+    use RKError::*;
+
+    let stages = a.len();
+    if stages == 0 {
+        return Err(EmptyTableau);
+    }
+    for row in a {
+        if row.len() != stages {
+            return Err(NonSquareTableau(stages, row.len()));
+        }
+    }
+    for v in [Some(c), Some(b), b_hat].iter().flatten() {
+        if v.len() != stages {
+            return Err(MismatchedStages(stages, v.len()));
+        }
+    }
+
+    let matrix = match b_hat {
+        Some(b_hat) => join_matrix(c, a, &[b, b_hat]),
+        None => join_matrix(c, a, &[b]),
+    };
+    let rows: Vec<&[C]> = matrix.iter().map(|row| &row[..]).collect();
+    match ButcherTableau::new(&rows)? {
+        ButcherTableau::Fixed(_) | ButcherTableau::Adaptive(_) => Ok(()),
+        _ => Err(UnsupportedImplicit),
+    }
+}
+
+//splits a tableau in the borrowed matrix layout into c, A, and the rows below A
+#[cfg(feature = "alloc")]
+#[allow(clippy::type_complexity)]
+fn split_matrix<C: Coefficient, D: From<C>>(table: &[&[C]]) -> (Vec<D>, Vec<Vec<D>>, Vec<Vec<D>>) {
+    let stages = table[0].len() - 1;
+    let row = |r: &[C]| r[1..].iter().map(|&x| D::from(x)).collect();
+    let c = table[..stages].iter().map(|r| D::from(r[0])).collect();
+    let a = table[..stages].iter().map(|r| row(r)).collect();
+    let weights = table[stages..].iter().map(|r| row(r)).collect();
+    (c, a, weights)
+}
+
+//assembles c, A, and the weight rows back into the borrowed matrix layout
+#[cfg(feature = "alloc")]
+fn join_matrix<C: Coefficient>(c: &[C], a: &[Vec<C>], weights: &[&[C]]) -> Vec<Vec<C>> {
+    let mut matrix: Vec<Vec<C>> = c
+        .iter()
+        .zip(a)
+        .map(|(&c_i, a_i)| Some(c_i).into_iter().chain(a_i.iter().copied()).collect())
+        .collect();
+    for w in weights {
+        matrix.push(
+            Some(C::default())
+                .into_iter()
+                .chain(w.iter().copied())
+                .collect(),
+        );
+    }
+    matrix
+}
+
+#[cfg(feature = "alloc")]
+impl<C: Coefficient> OwnedRungeKutta<C> {
+    pub fn new(c: Vec<C>, a: Vec<Vec<C>>, b: Vec<C>) -> Result<Self, RKError> {
+        check_coefficients(&c, &a, &b, None)?;
+        Ok(OwnedRungeKutta { c, a, b })
+    }
+    pub fn from_matrix(rk_matrix: &[&[C]]) -> Result<Self, RKError> {
+        RungeKutta::from_matrix(rk_matrix).map(|rk| rk.into())
+    }
+
+    pub fn order(&self) -> usize {
+        classical_order(self)
+    }
+    pub fn stages(&self) -> usize {
+        self.a.len()
+    }
+    pub fn c_vec(&self) -> &[C] {
+        &self.c
+    }
+    pub fn a_matrix(&self) -> &[Vec<C>] {
+        &self.a
+    }
+    pub fn b_vec(&self) -> &[C] {
+        &self.b
+    }
+
+    pub fn with_embedded(self, b_hat: Vec<C>) -> Result<OwnedAdaptiveRungeKutta<C>, RKError> {
+        OwnedAdaptiveRungeKutta::new(self.c, self.a, self.b, b_hat)
+    }
+
+    //the tableau in the same layout as is used by `RungeKutta`
+    pub fn to_matrix(&self) -> Vec<Vec<C>> {
+        join_matrix(&self.c, &self.a, &[&self.b])
+    }
+
+    //passes a borrowed view of this tableau to the given closure
+    pub fn with_borrowed<T, F: FnOnce(RungeKutta<C>) -> T>(&self, f: F) -> T {
+        let matrix = self.to_matrix();
+        let rows: Vec<&[C]> = matrix.iter().map(|row| &row[..]).collect();
+        f(RungeKutta(&rows))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<C: Coefficient> OwnedAdaptiveRungeKutta<C> {
+    pub fn new(c: Vec<C>, a: Vec<Vec<C>>, b: Vec<C>, b_hat: Vec<C>) -> Result<Self, RKError> {
+        check_coefficients(&c, &a, &b, Some(&b_hat))?;
+        Ok(Self::with_order(c, a, b, b_hat))
+    }
+    fn with_order(c: Vec<C>, a: Vec<Vec<C>>, b: Vec<C>, b_hat: Vec<C>) -> Self {
+        let mut rk = OwnedAdaptiveRungeKutta {
+            c,
+            a,
+            b,
+            b_hat,
+            order: 0,
+        };
+        rk.order = classical_order(&rk);
+        rk
+    }
+    pub fn from_matrix(rk_matrix: &[&[C]]) -> Result<Self, RKError> {
+        AdaptiveRungeKutta::from_matrix(rk_matrix).map(|rk| rk.into())
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+    pub fn embedded_order(&self) -> usize {
+        embedded_order(self).unwrap()
+    }
+    pub fn stages(&self) -> usize {
+        self.a.len()
+    }
+    pub fn c_vec(&self) -> &[C] {
+        &self.c
+    }
+    pub fn a_matrix(&self) -> &[Vec<C>] {
+        &self.a
+    }
+    pub fn b_vec(&self) -> &[C] {
+        &self.b
+    }
+    pub fn b_hat_vec(&self) -> &[C] {
+        &self.b_hat
+    }
+
+    //drops the embedded weights, leaving the fixed-step method
+    pub fn without_embedded(self) -> OwnedRungeKutta<C> {
+        OwnedRungeKutta {
+            c: self.c,
+            a: self.a,
+            b: self.b,
+        }
+    }
+
+    //the tableau in the same layout as is used by `AdaptiveRungeKutta`
+    pub fn to_matrix(&self) -> Vec<Vec<C>> {
+        join_matrix(&self.c, &self.a, &[&self.b, &self.b_hat])
+    }
+
+    //passes a borrowed view of this tableau to the given closure
+    pub fn with_borrowed<T, F: FnOnce(AdaptiveRungeKutta<C>) -> T>(&self, f: F) -> T {
+        let matrix = self.to_matrix();
+        let rows: Vec<&[C]> = matrix.iter().map(|row| &row[..]).collect();
+        f(AdaptiveRungeKutta(&rows))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, C: Coefficient> From<RungeKutta<'a, C>> for OwnedRungeKutta<C> {
+    fn from(rk: RungeKutta<'a, C>) -> Self {
+        let (c, a, mut weights) = split_matrix(rk.0);
+        OwnedRungeKutta {
+            c,
+            a,
+            b: weights.remove(0),
+        }
     }
 }
 
-fn compute_k<R: Real, D: Clone, S: VectorSpace<R>, F: Fn(R, S) -> (D, S)>(
-    tableau: &[&[f64]],
+#[cfg(feature = "alloc")]
+impl<'a, C: Coefficient> From<AdaptiveRungeKutta<'a, C>> for OwnedAdaptiveRungeKutta<C> {
+    fn from(rk: AdaptiveRungeKutta<'a, C>) -> Self {
+        let (c, a, mut weights) = split_matrix(rk.0);
+        let b_hat = weights.remove(1);
+        OwnedAdaptiveRungeKutta::with_order(c, a, weights.remove(0), b_hat)
+    }
+}
+
+//the exact built-in tableaux can also be stored as floats, eg for registering alongside user tableaux
+#[cfg(feature = "alloc")]
+impl<'a> From<RungeKutta<'a, Rational>> for OwnedRungeKutta<f64> {
+    fn from(rk: RungeKutta<'a, Rational>) -> Self {
+        let (c, a, mut weights) = split_matrix(rk.0);
+        OwnedRungeKutta {
+            c,
+            a,
+            b: weights.remove(0),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> From<AdaptiveRungeKutta<'a, Rational>> for OwnedAdaptiveRungeKutta<f64> {
+    fn from(rk: AdaptiveRungeKutta<'a, Rational>) -> Self {
+        let (c, a, mut weights) = split_matrix(rk.0);
+        let b_hat = weights.remove(1);
+        OwnedAdaptiveRungeKutta::with_order(c, a, weights.remove(0), b_hat)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<C: Coefficient> Tableau for OwnedRungeKutta<C> {
+    type Coeff = C;
+
+    fn stages(&self) -> usize {
+        self.a.len()
+    }
+    fn c(&self, i: usize) -> C {
+        self.c[i]
+    }
+    fn a(&self, i: usize, j: usize) -> C {
+        self.a[i][j]
+    }
+    fn b(&self, j: usize) -> C {
+        self.b[j]
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<C: Coefficient> Tableau for OwnedAdaptiveRungeKutta<C> {
+    type Coeff = C;
+
+    fn stages(&self) -> usize {
+        self.a.len()
+    }
+    fn c(&self, i: usize) -> C {
+        self.c[i]
+    }
+    fn a(&self, i: usize, j: usize) -> C {
+        self.a[i][j]
+    }
+    fn b(&self, j: usize) -> C {
+        self.b[j]
+    }
+    fn b_hat(&self, j: usize) -> Option<C> {
+        Some(self.b_hat[j])
+    }
+    fn order(&self) -> usize {
+        self.order
+    }
+}
+
+//a slot of the state buffer that holds the result of one stage
+trait StageSlot<R, D, S> {
+    fn k(&self) -> &S;
+    fn k_mut(&mut self) -> &mut S;
+    fn store(&mut self, t: R, data: D);
+}
+
+impl<R, D, S> StageSlot<R, D, S> for (D, S) {
+    fn k(&self) -> &S {
+        &self.1
+    }
+    fn k_mut(&mut self) -> &mut S {
+        &mut self.1
+    }
+    fn store(&mut self, _t: R, data: D) {
+        self.0 = data;
+    }
+}
+
+impl<R, D, S> StageSlot<R, D, S> for (R, D, S) {
+    fn k(&self) -> &S {
+        &self.2
+    }
+    fn k_mut(&mut self) -> &mut S {
+        &mut self.2
+    }
+    fn store(&mut self, t: R, data: D) {
+        self.0 = t;
+        self.1 = data;
+    }
+}
+
+//evaluates the stages of the tableau from `first` onwards, storing the results in the first
+//`stages()` slots of `k` and using the slot after them to hold the input of each stage
+//
+//each term a_ij k_j is scaled in the slot of stage i before that stage is evaluated, so the
+//stages are combined without cloning or allocating anything
+fn compute_k<
+    T: Tableau + ?Sized,
+    R: Real,
+    D: Clone,
+    S: State<R>,
+    K: StageSlot<R, D, S>,
+    F: Rhs<R, D, S>,
+>(
+    tableau: &T,
     time: R,
     state: &S,
     dt: R,
-    force: F,
-) -> Vec<(D, S)> {
-    let order = tableau[0].len() - 1;
-    let mut k: Vec<(D, S)> = Vec::with_capacity(order);
-
-    for i in 0..order {
-        let t = time.clone() + dt.clone() * R::repr(tableau[i][0]);
-        let mut y_i = state.clone();
-        for j in 1..=i {
-            if tableau[i][j] != 0.0 {
-                y_i += k[j - 1].1.clone() * (dt.clone() * R::repr(tableau[i][j]));
+    k: &mut [K],
+    first: usize,
+    force: &F,
+) {
+    let stages = tableau.stages();
+    let (k, scratch) = k.split_at_mut(stages);
+    let y_i = scratch[0].k_mut();
+
+    for i in first..stages {
+        let t = time.clone() + dt.clone() * tableau.c(i).to_real::<R>();
+        let (k, k_i) = k.split_at_mut(i);
+        let k_i = &mut k_i[0];
+        y_i.clone_from(state);
+        for (j, k_j) in k.iter().enumerate() {
+            let a_ij = tableau.a(i, j);
+            if !a_ij.is_zero() {
+                add_scaled(y_i, k_j.k(), dt.clone() * a_ij.to_real::<R>(), k_i.k_mut());
             }
         }
-        k.push(force(t, y_i));
+        let data = force.eval(t.clone(), y_i, k_i.k_mut());
+        k_i.store(t, data);
     }
+}
+
+//adds `x * a` to `y`, using `scratch` to hold the scaled term
+fn add_scaled<R: Real, S: State<R>>(y: &mut S, x: &S, a: R, scratch: &mut S) {
+    scratch.clone_from(x);
+    *scratch *= a;
+    *y += &*scratch;
+}
 
-    k
+fn check_buffer(needed: usize, len: usize) {
+    assert!(
+        len >= needed,
+        "the state buffer needs {} slots but has {}",
+        needed,
+        len
+    );
 }
 
-impl<'a> Integrator for RungeKutta<'a> {
-    fn step<R: Real, D: Clone + Default, S: VectorSpace<R>, F: Fn(R, S) -> (D, S)>(
-        &self,
-        time: R,
-        state: &mut [(D, S)],
-        dt: R,
-        force: F,
-    ) -> (D, S) {
-        let order = self.order();
-        let k: Vec<(D, S)> = compute_k(self.0, time, &state[0].1, dt.clone(), force);
+//the state buffer for a fixed-step method: the state, one slot for each stage, and a slot
+//for the input of each stage
+fn rk_init_in<R: Real, D: Clone + Default, S: State<R>>(
+    stages: usize,
+    state: S,
+    buffer: &mut [(D, S)],
+) {
+    //a shorter buffer still works, but stepping it needs a temporary one
+    for slot in buffer.iter_mut().skip(1).take(stages + 1) {
+        *slot = (D::default(), state.clone());
+    }
+    buffer[0] = (D::default(), state);
+}
+
+fn rk_step<T: Tableau + ?Sized, R: Real, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>>(
+    tableau: &T,
+    time: R,
+    state: &mut [(D, S)],
+    dt: R,
+    force: &F,
+) -> (D, S) {
+    rk_step_in_place(tableau, time, state, dt, force);
+    state[0].clone()
+}
+
+//steps the state in `state[0]` without allocating, given the full buffer from `init_in`
+fn rk_step_in_place<
+    T: Tableau + ?Sized,
+    R: Real,
+    D: Clone + Default,
+    S: State<R>,
+    F: Rhs<R, D, S>,
+>(
+    tableau: &T,
+    time: R,
+    state: &mut [(D, S)],
+    dt: R,
+    force: &F,
+) {
+    let stages = tableau.stages();
+
+    //buffers made before the stages were kept in them only hold the state
+    #[cfg(feature = "alloc")]
+    if state.len() < stages + 2 {
+        let mut buffer = vec![state[0].clone(); stages + 2];
+        rk_step_in_place(tableau, time, &mut buffer, dt, force);
+        state[0] = buffer.swap_remove(0);
+        return;
+    }
+    check_buffer(stages + 2, state.len());
+
+    let (y, k) = state.split_at_mut(1);
+    compute_k(tableau, time, &y[0].1, dt.clone(), k, 0, force);
+
+    //the input of the last stage isn't needed anymore, so its slot holds the scaled terms
+    let (k, scratch) = k.split_at_mut(stages);
+    for (j, k_j) in k.iter().enumerate() {
+        let b_j = tableau.b(j);
+        if !b_j.is_zero() {
+            add_scaled(
+                &mut y[0].1,
+                &k_j.1,
+                dt.clone() * b_j.to_real::<R>(),
+                &mut scratch[0].1,
+            );
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn rk_adaptive_step<
+    T: Tableau + ?Sized,
+    R: Real,
+    D: Clone + Default,
+    S: State<R>,
+    M: Metric<S, R>,
+    F: Rhs<R, D, S>,
+    O: Observer<R, D, S> + ?Sized,
+>(
+    tableau: &T,
+    state: &mut [(R, D, S)],
+    ds: R,
+    force: &F,
+    d: M,
+    control: &StepControl<R>,
+    observer: &mut O,
+) -> Result<(R, D, S), StepError<R>> {
+    let stages = tableau.stages();
+
+    //buffers made before the stages were kept in them only hold the state and the step size
+    if state.len() < stages + 4 {
+        let (t, dt) = (state[0].0.clone(), state[1].0.clone());
+        let mut buffer = rk_adaptive_init(tableau, t, state[0].2.clone(), dt, force);
+        let result = rk_adaptive_step(tableau, &mut buffer, ds, force, d, control, observer);
+        state.clone_from_slice(&buffer[..state.len()]);
+        return result;
+    }
+
+    let mut dt = control.limit(state[1].0.clone());
+    let time = state[0].0.clone();
+    let (head, k) = state.split_at_mut(2);
+    let (y, est) = head.split_at_mut(1);
+    let (y, est) = (&mut y[0], &mut est[0]);
+
+    //for FSAL methods the first stage is already in the buffer and stays valid across rejections
+    let first = if tableau.is_fsal() { 1 } else { 0 };
+
+    let mut rejections = 0;
+    let mut non_finite = false;
+    loop {
+        if time.clone() + dt.clone() == time {
+            return Err(if non_finite {
+                StepError::NonFinite(time)
+            } else {
+                StepError::StepSizeUnderflow(time, dt)
+            });
+        }
 
-        let mut j = 1;
-        for k_j in k {
-            if self.0[order][j] != 0.0 {
-                state[0].1 += k_j.1 * (dt.clone() * R::repr(self.0[order][j]));
+        compute_k(tableau, time.clone(), &y.2, dt.clone(), k, first, force);
+
+        //the higher-order solution (kept in the slot of the last stage input), and the embedded
+        //one (kept in the second slot of the buffer)
+        let (stage, rest) = k.split_at_mut(stages);
+        let (est1, scratch) = rest.split_at_mut(1);
+        let (est1, scratch) = (&mut est1[0].2, &mut scratch[0].2);
+        est1.clone_from(&y.2);
+        est.2.clone_from(&y.2);
+
+        for (j, k_j) in stage.iter().enumerate() {
+            let b_j = tableau.b(j);
+            let b_hat_j = tableau.b_hat(j).unwrap();
+            if !b_j.is_zero() {
+                add_scaled(est1, &k_j.2, dt.clone() * b_j.to_real::<R>(), scratch);
+            }
+            if !b_hat_j.is_zero() {
+                add_scaled(
+                    &mut est.2,
+                    &k_j.2,
+                    dt.clone() * b_hat_j.to_real::<R>(),
+                    scratch,
+                );
             }
-            j += 1;
         }
 
-        state[0].clone()
-    }
-}
-
-impl<'a> AdaptiveIntegrator for AdaptiveRungeKutta<'a> {
-    fn adaptive_init<
-        R: Real,
-        D: Clone + Default,
-        S: VectorSpace<R>,
-        M: Metric<S, R>,
-        F: Fn(R, S) -> (D, S),
-    >(
-        &self,
-        t0: R,
-        state: S,
-        ds: R,
-        _force: F,
-        _d: M,
-    ) -> Box<[(R, D, S)]> {
-        Box::new([
-            (t0, Default::default(), state.clone()),
-            (ds, Default::default(), state.clone()),
-        ])
-    }
-
-    fn adaptive_step<
-        R: Real,
-        D: Clone + Default,
-        S: VectorSpace<R>,
-        M: Metric<S, R>,
-        F: Fn(R, S) -> (D, S),
-    >(
-        &self,
-        state: &mut [(R, D, S)],
-        ds: R,
-        force: F,
-        d: M,
-    ) -> (R, D, S) {
-        let order = self.order();
-        let mut dt = state[1].0.clone();
-        let time = state[0].0.clone();
-
-        loop {
-            let k: Vec<(D, S)> = compute_k(self.0, time.clone(), &state[0].2, dt.clone(), &force);
-
-            let mut est1 = state[0].2.clone();
-            let mut est2 = state[0].2.clone();
-
-            let mut j = 1;
-            let last_data = k.last().unwrap().0.clone();
-            for k_j in k {
-                if self.0[order][j] != 0.0 {
-                    est1 += k_j.1.clone() * (dt.clone() * R::repr(self.0[order][j]));
-                }
-                if self.0[order + 1][j] != 0.0 {
-                    est2 += k_j.1 * (dt.clone() * R::repr(self.0[order + 1][j]));
-                }
-                j += 1;
+        //a NaN or infinity anywhere in either estimate ends up in the error
+        let err = d.distance(est1.clone(), est.2.clone());
+        non_finite = !is_finite(&err);
+
+        if !non_finite && err < ds {
+            let next_dt = control.limit(dt.clone() * R::repr(1.5)); // TODO: make this proportional to ds-err ?
+            let step = StepInfo {
+                start: time,
+                dt: dt.clone(),
+                error: Some(err),
+            };
+            y.0 += dt;
+            y.1 = stage[stages - 1].1.clone();
+            ::core::mem::swap(&mut y.2, est1);
+            est.0 = next_dt;
+            est.1 = Default::default();
+            if first == 1 {
+                k.swap(0, stages - 1);
             }
 
-            let err = d.distance(est1.clone(), est2.clone());
+            //the derivative kept for FSAL methods is stale if the observer changed the state
+            if observer.accepted(&step, &y.1, &mut y.2) == Control::Reset && first == 1 {
+                k[0].1 = force.eval(y.0.clone(), &y.2, &mut k[0].2);
+            }
+            return Ok(y.clone());
+        }
 
-            if err < ds {
-                let next_dt = dt.clone() * R::repr(1.5); // TODO: make this proportional to ds-err ?
-                state[0].0 += dt;
-                state[0].1 = last_data;
-                state[0].2 = est1;
-                state[1] = (next_dt, Default::default(), est2);
-                return state[0].clone();
+        //a smaller step can't help if the current state is already non-finite
+        if non_finite && !is_finite(&d.distance(y.2.clone(), S::zero())) {
+            return Err(StepError::NonFinite(time));
+        }
+
+        let step = StepInfo {
+            start: time.clone(),
+            dt: dt.clone(),
+            error: Some(err),
+        };
+        observer.rejected(&step, &y.2);
+
+        rejections += 1;
+        if rejections > control.max_rejections {
+            return Err(if non_finite {
+                StepError::NonFinite(time)
+            } else {
+                StepError::TooManyRejections(time, rejections)
+            });
+        }
+
+        //the step can only be shortened as far as `min_dt`
+        if dt <= control.min_dt {
+            return Err(if non_finite {
+                StepError::NonFinite(time)
             } else {
-                dt *= R::repr(0.5); // TODO: make this proportional to ds-err ?
+                StepError::StepSizeUnderflow(time, dt)
+            });
+        }
+        dt = control.limit(dt * R::repr(0.5)); // TODO: make this proportional to ds-err ?
+    }
+}
+
+//the state buffer for an adaptive method: the state, the next step size with the embedded
+//estimate, one slot for each stage, a slot for the input of each stage (and then the new
+//state), and a slot to scale the stages in
+//
+//for FSAL methods, the first stage slot always holds the derivative at the current state
+#[cfg(feature = "alloc")]
+fn rk_adaptive_init<
+    T: Tableau + ?Sized,
+    R: Real,
+    D: Clone + Default,
+    S: State<R>,
+    F: Rhs<R, D, S>,
+>(
+    tableau: &T,
+    t0: R,
+    state: S,
+    ds: R,
+    force: &F,
+) -> Box<[(R, D, S)]> {
+    let mut buffer = vec![(t0.clone(), D::default(), state); tableau.stages() + 4];
+    buffer[1].0 = ds;
+    if tableau.is_fsal() {
+        let (y, k) = buffer.split_at_mut(2);
+        k[0].1 = force.eval(t0, &y[0].2, &mut k[0].2);
+    }
+    buffer.into_boxed_slice()
+}
+
+//the buffer returned by `adaptive_init`, starting with an automatically chosen step size
+#[cfg(feature = "alloc")]
+fn rk_adaptive_start<
+    T: Tableau + ?Sized,
+    R: Real,
+    D: Clone + Default,
+    S: State<R>,
+    M: Metric<S, R>,
+    F: Rhs<R, D, S>,
+>(
+    tableau: &T,
+    t0: R,
+    state: S,
+    ds: R,
+    force: &F,
+    d: &M,
+) -> Box<[(R, D, S)]> {
+    let mut buffer = rk_adaptive_init(tableau, t0.clone(), state, ds.clone(), force);
+    let (head, k) = buffer.split_at_mut(2);
+    if !tableau.is_fsal() {
+        force.eval(t0.clone(), &head[0].2, &mut k[0].2);
+    }
+    let order = tableau.order();
+    head[1].0 = initial_step(t0, &head[0].2, &k[0].2, order, ds, force, d);
+    buffer
+}
+
+macro_rules! impl_integrator {
+    ($($ty:ty),*) => {$(
+        impl<'a, C: Coefficient> Integrator for $ty {
+            fn buffer_len(&self) -> usize {
+                Tableau::stages(self) + 2
+            }
+
+            fn init_in<R: Real, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>>(
+                &self,
+                state: S,
+                _dt: R,
+                _force: F,
+                buffer: &mut [(D, S)],
+            ) {
+                rk_init_in(Tableau::stages(self), state, buffer)
+            }
+
+            fn step<R: Real, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>>(
+                &self,
+                time: R,
+                state: &mut [(D, S)],
+                dt: R,
+                force: F,
+            ) -> (D, S) {
+                rk_step(self, time, state, dt, &force)
+            }
+
+            fn step_in_place<R: Real, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>>(
+                &self,
+                time: R,
+                state: &mut [(D, S)],
+                dt: R,
+                force: F,
+            ) {
+                rk_step_in_place(self, time, state, dt, &force)
             }
         }
+
+        impl<'a, C: Coefficient> VelIntegrator for $ty {
+            fn buffer_len_with_vel(&self) -> usize {
+                Integrator::buffer_len(self)
+            }
+
+            fn init_with_vel_in<
+                R: Real,
+                D: Clone + Default,
+                S: State<R>,
+                V: Rhs<R, D, S>,
+                F: Rhs<R, D, S>,
+            >(
+                &self,
+                state: S,
+                dt: R,
+                _: V,
+                force: F,
+                buffer: &mut [(D, S)],
+            ) {
+                Integrator::init_in(self, state, dt, force, buffer)
+            }
+
+            fn step_with_vel<
+                R: Real,
+                D: Clone + Default,
+                S: State<R>,
+                V: Rhs<R, D, S>,
+                F: Rhs<R, D, S>,
+            >(
+                &self,
+                time: R,
+                state: &mut [(D, S)],
+                dt: R,
+                _: V,
+                force: F,
+            ) -> (D, S) {
+                Integrator::step(self, time, state, dt, force)
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "alloc")]
+macro_rules! impl_adaptive_integrator {
+    ($($ty:ty),*) => {$(
+        impl<'a, C: Coefficient> AdaptiveIntegrator for $ty {
+            fn adaptive_init<
+                R: Real,
+                D: Clone + Default,
+                S: State<R>,
+                M: Metric<S, R>,
+                F: Rhs<R, D, S>,
+            >(
+                &self,
+                t0: R,
+                state: S,
+                ds: R,
+                force: F,
+                d: M,
+            ) -> Box<[(R, D, S)]> {
+                rk_adaptive_start(self, t0, state, ds, &force, &d)
+            }
+
+            fn adaptive_step<
+                R: Real,
+                D: Clone + Default,
+                S: State<R>,
+                M: Metric<S, R>,
+                F: Rhs<R, D, S>,
+            >(
+                &self,
+                state: &mut [(R, D, S)],
+                ds: R,
+                force: F,
+                d: M,
+            ) -> (R, D, S) {
+                match rk_adaptive_step(self, state, ds, &force, d, &StepControl::default(), &mut ()) {
+                    Ok(result) => result,
+                    Err(e) => panic!("{}", e),
+                }
+            }
+
+            fn try_adaptive_step<
+                R: Real,
+                D: Clone + Default,
+                S: State<R>,
+                M: Metric<S, R>,
+                F: Rhs<R, D, S>,
+                O: Observer<R, D, S> + ?Sized,
+            >(
+                &self,
+                state: &mut [(R, D, S)],
+                ds: R,
+                force: F,
+                d: M,
+                control: &StepControl<R>,
+                observer: &mut O,
+            ) -> Result<(R, D, S), StepError<R>> {
+                rk_adaptive_step(self, state, ds, &force, d, control, observer)
+            }
+        }
+    )*};
+}
+
+impl_integrator!(RungeKutta<'a, C>);
+#[cfg(feature = "alloc")]
+impl_integrator!(OwnedRungeKutta<C>);
+#[cfg(feature = "alloc")]
+impl_adaptive_integrator!(AdaptiveRungeKutta<'a, C>, OwnedAdaptiveRungeKutta<C>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_tableaux_are_checked_exactly() {
+        let tiny = Rational::new(1, 100_000_000_000_000);
+        let half = q!(1 / 2);
+        let off = half.checked_add(tiny).unwrap();
+
+        let rows: [&[Rational]; 3] = [
+            &[q!(0), q!(0), q!(0)],
+            &[half, off, q!(0)],
+            &[q!(0), half, half],
+        ];
+        assert_eq!(RungeKutta::from_matrix(&rows), Err(RKError::RowSum(1, 0.0)));
+        let rows: [&[Rational]; 3] = [
+            &[q!(0), q!(0), q!(0)],
+            &[half, half, q!(0)],
+            &[q!(0), half, off],
+        ];
+        assert_eq!(
+            RungeKutta::from_matrix(&rows),
+            Err(RKError::WeightSum(2, 0.0))
+        );
+
+        //the same tableau is close enough in floating point
+        let rows: [&[f64]; 3] = [
+            &[0.0, 0.0, 0.0],
+            &[0.5, off.to_f64(), 0.0],
+            &[0.0, 0.5, 0.5],
+        ];
+        assert!(RungeKutta::from_matrix(&rows).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn buffers_holding_only_the_state_still_step() {
+        use maths_traits::analysis::metric::InnerProductMetric;
+
+        let f = |_t: f64, y: f64| ((), y);
+        let mut full = Integrator::init(&RK4, 1.0, 0.1, f);
+        let mut short = [((), 1.0)];
+        for i in 0..10 {
+            let t = i as f64 * 0.1;
+            assert_eq!(
+                Integrator::step(&RK4, t, &mut short, 0.1, f),
+                Integrator::step(&RK4, t, &mut full, 0.1, f)
+            );
+        }
+
+        for method in [&DORMAND_PRINCE, &RK_FELBERG] {
+            let mut full =
+                AdaptiveIntegrator::adaptive_init(method, 0.0, 1.0, 0.01, f, InnerProductMetric);
+            let mut short = [full[0], full[1]];
+            for _ in 0..10 {
+                assert_eq!(
+                    AdaptiveIntegrator::adaptive_step(
+                        method,
+                        &mut short,
+                        1e-6,
+                        f,
+                        InnerProductMetric
+                    ),
+                    AdaptiveIntegrator::adaptive_step(
+                        method,
+                        &mut full,
+                        1e-6,
+                        f,
+                        InnerProductMetric
+                    )
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn fsal_methods_reuse_the_last_stage() {
+        use maths_traits::analysis::metric::InnerProductMetric;
+
+        //the evaluations taken by five accepted steps, after initialization
+        fn evaluations<T: AdaptiveIntegrator>(method: &T) -> usize {
+            let count = core::cell::Cell::new(0);
+            let f = |_t: f64, y: f64| {
+                count.set(count.get() + 1);
+                ((), y)
+            };
+            let mut buffer = method.adaptive_init(0.0, 1.0, 1.0, f, InnerProductMetric);
+            buffer[1].0 = 0.01;
+            count.set(0);
+            for _ in 0..5 {
+                method.adaptive_step(&mut buffer, 1.0, f, InnerProductMetric);
+            }
+            count.get()
+        }
+
+        assert_eq!(evaluations(&DORMAND_PRINCE), 5 * 6);
+        assert_eq!(evaluations(&BOGACKI_SHAMPINE), 5 * 3);
+        assert_eq!(evaluations(&RK_FELBERG), 5 * 6);
     }
 }
@@ -0,0 +1,450 @@
+use super::*;
+
+use maths_traits::algebra::*;
+
+//Forward sensitivity analysis
+//
+//The sensitivity `s_i = ∂y/∂p_i` of the solution to the `i`th parameter of the right-hand side
+//satisfies `s_i' = J_y*s_i + ∂f/∂p_i`, where `J_y` is the Jacobian with respect to the state.
+//Integrating these along with the state (as a `SensitivityState`, with `Sensitivity` as the
+//right-hand side) works with every method in the crate, and with `SensitivityMetric` the
+//adaptive ones can control the error of the state alone or of the sensitivities as well.
+
+//A right-hand side `f(t, y, p)` that depends on parameters, along with its derivatives
+pub trait ParamRhs<R, S> {
+    fn params(&self) -> &[R];
+    fn params_mut(&mut self) -> &mut [R];
+    fn eval(&self, t: R, y: &S) -> S;
+    //writes `J_y*s[i] + ∂f/∂p_i` into `ds[i]` for each parameter
+    fn sensitivities(&self, t: R, y: &S, s: &[S], ds: &mut [S]);
+    //returns `J_y*v`, which is how the sensitivities to the initial conditions change
+    fn jvp(&self, t: R, y: &S, v: &S) -> S;
+}
+
+//a parameterized right-hand side with its Jacobians written out by hand
+//
+//`jac_y` gives the Jacobian with respect to the state (eg a `DenseJacobian`) and `jac_p` the
+//derivatives of `f` with respect to each parameter. `vjp_p` can also give the product of an
+//adjoint with the derivatives to the parameters directly, for `Adjoint` (see `vjp_p()`).
+#[derive(Clone, Debug)]
+pub struct AnalyticJacobians<F, Jy, Jp, R, Vp = ()> {
+    pub f: F,
+    pub jac_y: Jy,
+    pub jac_p: Jp,
+    pub params: Vec<R>,
+    pub vjp_p: Vp,
+}
+
+impl<F, Jy, Jp, R> AnalyticJacobians<F, Jy, Jp, R> {
+    pub fn new(f: F, jac_y: Jy, jac_p: Jp, params: Vec<R>) -> Self {
+        AnalyticJacobians {
+            f,
+            jac_y,
+            jac_p,
+            params,
+            vjp_p: (),
+        }
+    }
+}
+
+impl<F, Jy, Jp, R, Vp> AnalyticJacobians<F, Jy, Jp, R, Vp> {
+    //`vjp_p(t, y, p, λ, dp)` adds `λ ∂f/∂p` to `dp`, which `Adjoint` then uses instead of
+    //building the derivative to each parameter with `jac_p`, so that its backward pass costs the
+    //same whatever the number of parameters
+    pub fn vjp_p<V>(self, vjp_p: V) -> AnalyticJacobians<F, Jy, Jp, R, V> {
+        AnalyticJacobians {
+            f: self.f,
+            jac_y: self.jac_y,
+            jac_p: self.jac_p,
+            params: self.params,
+            vjp_p,
+        }
+    }
+}
+
+impl<R, S, J, F, Jy, Jp, Vp> ParamRhs<R, S> for AnalyticJacobians<F, Jy, Jp, R, Vp>
+where
+    R: Real,
+    S: State<R>,
+    J: Jacobian<R, S>,
+    F: Fn(R, &S, &[R]) -> S,
+    Jy: Fn(R, &S, &[R]) -> J,
+    Jp: Fn(R, &S, &[R]) -> Vec<S>,
+{
+    fn params(&self) -> &[R] {
+        &self.params
+    }
+    fn params_mut(&mut self) -> &mut [R] {
+        &mut self.params
+    }
+    fn eval(&self, t: R, y: &S) -> S {
+        (self.f)(t, y, &self.params)
+    }
+    fn sensitivities(&self, t: R, y: &S, s: &[S], ds: &mut [S]) {
+        let jac_y = (self.jac_y)(t.clone(), y, &self.params);
+        let jac_p = (self.jac_p)(t, y, &self.params);
+        assert_eq!(
+            jac_p.len(),
+            self.params.len(),
+            "there must be a derivative for each parameter"
+        );
+        for ((ds, s), df_dp) in ds.iter_mut().zip(s).zip(jac_p) {
+            *ds = jac_y.apply(s) + df_dp;
+        }
+    }
+    fn jvp(&self, t: R, y: &S, v: &S) -> S {
+        (self.jac_y)(t, y, &self.params).apply(v)
+    }
+}
+
+//a parameterized right-hand side differentiated by central differences
+//
+//each sensitivity is found from two evaluations of `f`, moving both the state (along the
+//sensitivity) and the parameter by `step` (relative to the parameter if it's larger than one)
+#[derive(Clone, Debug)]
+pub struct FiniteDifferenceJacobians<F, R> {
+    pub f: F,
+    pub params: Vec<R>,
+    pub step: R,
+}
+
+impl<F, R: Real> FiniteDifferenceJacobians<F, R> {
+    pub fn new(f: F, params: Vec<R>) -> Self {
+        FiniteDifferenceJacobians {
+            f,
+            params,
+            //about the cube root of the machine epsilon, which balances the truncation error
+            //of central differences against rounding
+            step: R::repr(6e-6),
+        }
+    }
+
+    pub fn step(self, step: R) -> Self {
+        FiniteDifferenceJacobians { step, ..self }
+    }
+}
+
+impl<R: Real, S: State<R>, F: Fn(R, &S, &[R]) -> S> ParamRhs<R, S>
+    for FiniteDifferenceJacobians<F, R>
+{
+    fn params(&self) -> &[R] {
+        &self.params
+    }
+    fn params_mut(&mut self) -> &mut [R] {
+        &mut self.params
+    }
+    fn eval(&self, t: R, y: &S) -> S {
+        (self.f)(t, y, &self.params)
+    }
+    fn sensitivities(&self, t: R, y: &S, s: &[S], ds: &mut [S]) {
+        let mut p = self.params.clone();
+        for (i, (ds, s)) in ds.iter_mut().zip(s).enumerate() {
+            let p_i = self.params[i].clone();
+            let scale = p_i.clone().abs();
+            let h = self.step.clone() * if scale > R::one() { scale } else { R::one() };
+
+            p[i] = p_i.clone() + h.clone();
+            let forward = (self.f)(t.clone(), &(y.clone() + s.clone() * h.clone()), &p);
+            p[i] = p_i.clone() - h.clone();
+            let backward = (self.f)(t.clone(), &(y.clone() - s.clone() * h.clone()), &p);
+            p[i] = p_i;
+
+            *ds = (forward - backward) / (h.clone() + h);
+        }
+    }
+    fn jvp(&self, t: R, y: &S, v: &S) -> S {
+        let h = self.step.clone();
+        let forward = (self.f)(
+            t.clone(),
+            &(y.clone() + v.clone() * h.clone()),
+            &self.params,
+        );
+        let backward = (self.f)(t, &(y.clone() - v.clone() * h.clone()), &self.params);
+        (forward - backward) / (h.clone() + h)
+    }
+}
+
+//a parameterized right-hand side differentiated exactly with dual numbers
+//
+//`f` is evaluated on dual numbers, so it is usually a function written generically over the
+//real number type, and each sensitivity takes one evaluation
+#[derive(Clone, Debug)]
+pub struct AutoDiffJacobians<F, R> {
+    pub f: F,
+    pub params: Vec<R>,
+}
+
+impl<F, R> AutoDiffJacobians<F, R> {
+    pub fn new(f: F, params: Vec<R>) -> Self {
+        AutoDiffJacobians { f, params }
+    }
+}
+
+impl<R, S, F> ParamRhs<R, S> for AutoDiffJacobians<F, R>
+where
+    R: Real,
+    S: State<R> + DualState<R>,
+    F: Fn(Dual<R>, &S::Dual, &[Dual<R>]) -> S::Dual,
+{
+    fn params(&self) -> &[R] {
+        &self.params
+    }
+    fn params_mut(&mut self) -> &mut [R] {
+        &mut self.params
+    }
+    fn eval(&self, t: R, y: &S) -> S {
+        let p: Vec<_> = self.params.iter().cloned().map(Dual::constant).collect();
+        S::split((self.f)(Dual::constant(t), &y.lift(&S::zero()), &p)).0
+    }
+    fn sensitivities(&self, t: R, y: &S, s: &[S], ds: &mut [S]) {
+        let mut p: Vec<_> = self.params.iter().cloned().map(Dual::constant).collect();
+        for (i, (ds, s)) in ds.iter_mut().zip(s).enumerate() {
+            p[i].deriv = R::one();
+            *ds = S::split((self.f)(Dual::constant(t.clone()), &y.lift(s), &p)).1;
+            p[i].deriv = R::zero();
+        }
+    }
+    fn jvp(&self, t: R, y: &S, v: &S) -> S {
+        let p: Vec<_> = self.params.iter().cloned().map(Dual::constant).collect();
+        S::split((self.f)(Dual::constant(t), &y.lift(v), &p)).1
+    }
+}
+
+//A state together with its sensitivity to each parameter
+//
+//Like `DVector`, missing sensitivities count as zero, since `zero()` can't know how many
+//parameters there are.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SensitivityState<S> {
+    pub y: S,
+    pub s: Vec<S>,
+}
+
+impl<S> SensitivityState<S> {
+    pub fn new(y: S, s: Vec<S>) -> Self {
+        SensitivityState { y, s }
+    }
+}
+
+impl<S: Zero> SensitivityState<S> {
+    //the usual initial state, where the initial conditions don't depend on any of the `params`
+    pub fn zeros(y: S, params: usize) -> Self {
+        SensitivityState::new(y, (0..params).map(|_| S::zero()).collect())
+    }
+}
+
+macro_rules! impl_sensitivity_ops {
+    ($($Op:ident::$op:ident $Assign:ident::$assign:ident),*) => {$(
+        impl<S: Zero + $Assign> $Assign for SensitivityState<S> {
+            fn $assign(&mut self, rhs: Self) {
+                $Assign::$assign(&mut self.y, rhs.y);
+                if self.s.len() < rhs.s.len() {
+                    self.s.resize_with(rhs.s.len(), S::zero);
+                }
+                for (a, b) in self.s.iter_mut().zip(rhs.s) {
+                    $Assign::$assign(a, b);
+                }
+            }
+        }
+
+        impl<'a, S: Zero + $Assign<&'a S>> $Assign<&'a Self> for SensitivityState<S> {
+            fn $assign(&mut self, rhs: &'a Self) {
+                $Assign::$assign(&mut self.y, &rhs.y);
+                if self.s.len() < rhs.s.len() {
+                    self.s.resize_with(rhs.s.len(), S::zero);
+                }
+                for (a, b) in self.s.iter_mut().zip(&rhs.s) {
+                    $Assign::$assign(a, b);
+                }
+            }
+        }
+
+        impl<S: Zero + $Assign> $Op for SensitivityState<S> {
+            type Output = Self;
+            fn $op(mut self, rhs: Self) -> Self {
+                $Assign::$assign(&mut self, rhs);
+                self
+            }
+        }
+    )*};
+
+    ($(scalar $Op:ident::$op:ident $Assign:ident::$assign:ident),*) => {$(
+        impl<R: Clone, S: $Assign<R>> $Assign<R> for SensitivityState<S> {
+            fn $assign(&mut self, rhs: R) {
+                $Assign::$assign(&mut self.y, rhs.clone());
+                for a in self.s.iter_mut() {
+                    $Assign::$assign(a, rhs.clone());
+                }
+            }
+        }
+
+        impl<R: Clone, S: $Assign<R>> $Op<R> for SensitivityState<S> {
+            type Output = Self;
+            fn $op(mut self, rhs: R) -> Self {
+                $Assign::$assign(&mut self, rhs);
+                self
+            }
+        }
+    )*};
+}
+
+impl_sensitivity_ops!(Add::add AddAssign::add_assign, Sub::sub SubAssign::sub_assign);
+impl_sensitivity_ops!(
+    scalar Mul::mul MulAssign::mul_assign,
+    scalar Div::div DivAssign::div_assign
+);
+
+impl<S: Neg<Output = S>> Neg for SensitivityState<S> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        SensitivityState::new(-self.y, self.s.into_iter().map(Neg::neg).collect())
+    }
+}
+
+impl<S: Zero + AddAssign> Zero for SensitivityState<S> {
+    fn zero() -> Self {
+        SensitivityState::new(S::zero(), Vec::new())
+    }
+    fn is_zero(&self) -> bool {
+        self.y.is_zero() && self.s.iter().all(S::is_zero)
+    }
+}
+
+impl<S> AddAssociative for SensitivityState<S> {}
+impl<S> AddCommutative for SensitivityState<S> {}
+impl<R, S> Distributive<R> for SensitivityState<S> {}
+
+impl<R: Real, S: InnerProductSpace<R>> InnerProductSpace<R> for SensitivityState<S> {
+    fn inner_product(self, rhs: Self) -> R {
+        self.s
+            .into_iter()
+            .zip(rhs.s)
+            .fold(self.y.inner_product(rhs.y), |sum, (a, b)| {
+                sum + a.inner_product(b)
+            })
+    }
+}
+
+//measures a `SensitivityState` with another metric, either on the state alone or as the
+//largest of the distances of the state and each of its sensitivities
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct SensitivityMetric<M> {
+    pub metric: M,
+    pub include_sensitivities: bool,
+}
+
+impl<M> SensitivityMetric<M> {
+    pub fn new(metric: M, include_sensitivities: bool) -> Self {
+        SensitivityMetric {
+            metric,
+            include_sensitivities,
+        }
+    }
+}
+
+impl<R: Real, S: Zero, M: Metric<S, R>> Metric<SensitivityState<S>, R> for SensitivityMetric<M> {
+    fn distance(&self, x1: SensitivityState<S>, x2: SensitivityState<S>) -> R {
+        let mut dist = self.metric.distance(x1.y, x2.y);
+        if self.include_sensitivities {
+            let n = x1.s.len().max(x2.s.len());
+            let (mut s1, mut s2) = (x1.s.into_iter(), x2.s.into_iter());
+            for _ in 0..n {
+                let d = self.metric.distance(
+                    s1.next().unwrap_or_else(S::zero),
+                    s2.next().unwrap_or_else(S::zero),
+                );
+                if d > dist {
+                    dist = d;
+                }
+            }
+        }
+        dist
+    }
+}
+
+//the right-hand side of the system made of a state and its sensitivities
+//
+//the first sensitivities are to each of the parameters, and any after those are to the initial
+//conditions (or to anything else that the right-hand side doesn't depend on directly)
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct Sensitivity<P>(pub P);
+
+impl<R: Real, S: State<R>, P: ParamRhs<R, S>> Rhs<R, (), SensitivityState<S>> for Sensitivity<P> {
+    fn eval(&self, t: R, y: &SensitivityState<S>, dy: &mut SensitivityState<S>) {
+        let params = self.0.params().len();
+        assert!(
+            y.s.len() >= params,
+            "there must be a sensitivity for each parameter"
+        );
+        dy.y = self.0.eval(t.clone(), &y.y);
+        dy.s.resize_with(y.s.len(), S::zero);
+        let (ds, ds_initial) = dy.s.split_at_mut(params);
+        self.0.sensitivities(t.clone(), &y.y, &y.s[..params], ds);
+        for (ds, s) in ds_initial.iter_mut().zip(&y.s[params..]) {
+            *ds = self.0.jvp(t.clone(), &y.y, s);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn decay<T: Real>(_t: T, y: &T, p: &[T]) -> T {
+        -p[0].clone() * y.clone()
+    }
+
+    //checks the sensitivities of `y' = -py` to `p` and `y0` at the end of a fixed-step and of
+    //two adaptive integrations against the exact ones
+    fn check<P: ParamRhs<f64, f64> + Clone>(problem: P) {
+        let (p, y0, t1) = (0.7, 1.5, 2.0);
+        let initial = SensitivityState::new(y0, vec![0.0, 1.0]);
+        let solver = Solver::new(0.0, t1).unwrap();
+
+        let mut ends =
+            vec![solver.solve(&RK4, initial.clone(), 0.01, Sensitivity(problem.clone()))];
+        for include_sensitivities in [false, true] {
+            let metric = SensitivityMetric::new(InnerProductMetric, include_sensitivities);
+            ends.push(
+                solver
+                    .solve_adaptive(
+                        &DORMAND_PRINCE,
+                        initial.clone(),
+                        1e-10,
+                        Sensitivity(problem.clone()),
+                        metric,
+                    )
+                    .unwrap(),
+            );
+        }
+
+        let e = (-p * t1).exp();
+        for trajectory in ends {
+            let end = trajectory.states.last().unwrap();
+            assert!((end.y - y0 * e).abs() < 1e-7, "{end:?}");
+            assert!((end.s[0] + t1 * y0 * e).abs() < 1e-7, "{end:?}");
+            assert!((end.s[1] - e).abs() < 1e-7, "{end:?}");
+        }
+    }
+
+    #[test]
+    fn analytic_sensitivities_are_exact() {
+        check(AnalyticJacobians::new(
+            decay::<f64>,
+            |_t, _y: &f64, p: &[f64]| DenseJacobian::from_rows(vec![vec![-p[0]]]),
+            |_t, y: &f64, _p: &[f64]| vec![-y],
+            vec![0.7],
+        ));
+    }
+
+    #[test]
+    fn finite_difference_sensitivities_are_exact() {
+        check(FiniteDifferenceJacobians::new(decay::<f64>, vec![0.7]));
+    }
+
+    #[test]
+    fn autodiff_sensitivities_are_exact() {
+        check(AutoDiffJacobians::new(decay::<Dual<f64>>, vec![0.7]));
+    }
+}
@@ -0,0 +1,104 @@
+use super::*;
+
+use core::fmt::Formatter;
+use core::marker::PhantomData;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeTuple, Serializer};
+use serde::{Deserialize, Serialize};
+
+//The implementations that can't just be derived
+//
+//Tableaux are checked again when they are loaded, and borrowed tableaux (like the constants)
+//are written in the same form as owned ones so that they can be loaded as those. Rationals
+//are written as strings (eg "1/3") to keep them exact.
+
+impl<T: Serialize, const N: usize> Serialize for SVector<T, N> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for x in self.0.iter() {
+            tuple.serialize_element(x)?;
+        }
+        tuple.end()
+    }
+}
+
+impl<'de, T: Deserialize<'de>, const N: usize> Deserialize<'de> for SVector<T, N> {
+    fn deserialize<Z: Deserializer<'de>>(deserializer: Z) -> Result<Self, Z::Error> {
+        struct SVectorVisitor<T, const N: usize>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>, const N: usize> Visitor<'de> for SVectorVisitor<T, N> {
+            type Value = SVector<T, N>;
+
+            fn expecting(&self, f: &mut Formatter) -> ::core::fmt::Result {
+                write!(f, "a sequence of {} components", N)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<SVector<T, N>, A::Error> {
+                let mut components = Vec::with_capacity(N);
+                while let Some(x) = seq.next_element()? {
+                    if components.len() == N {
+                        return Err(de::Error::invalid_length(N + 1, &self));
+                    }
+                    components.push(x);
+                }
+                match components.try_into() {
+                    Ok(components) => Ok(SVector(components)),
+                    Err(components) => Err(de::Error::invalid_length(components.len(), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_tuple(N, SVectorVisitor(PhantomData))
+    }
+}
+
+impl Serialize for Rational {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rational {
+    fn deserialize<Z: Deserializer<'de>>(deserializer: Z) -> Result<Self, Z::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Deserialize)]
+struct Coefficients<C> {
+    c: Vec<C>,
+    a: Vec<Vec<C>>,
+    b: Vec<C>,
+    b_hat: Option<Vec<C>>,
+}
+
+impl<'de, C: Coefficient + Deserialize<'de>> Deserialize<'de> for OwnedRungeKutta<C> {
+    fn deserialize<Z: Deserializer<'de>>(deserializer: Z) -> Result<Self, Z::Error> {
+        let Coefficients { c, a, b, b_hat } = Coefficients::deserialize(deserializer)?;
+        if b_hat.is_some() {
+            return Err(de::Error::unknown_field("b_hat", &["c", "a", "b"]));
+        }
+        OwnedRungeKutta::new(c, a, b).map_err(de::Error::custom)
+    }
+}
+
+impl<'de, C: Coefficient + Deserialize<'de>> Deserialize<'de> for OwnedAdaptiveRungeKutta<C> {
+    fn deserialize<Z: Deserializer<'de>>(deserializer: Z) -> Result<Self, Z::Error> {
+        let Coefficients { c, a, b, b_hat } = Coefficients::deserialize(deserializer)?;
+        let b_hat = b_hat.ok_or_else(|| de::Error::missing_field("b_hat"))?;
+        OwnedAdaptiveRungeKutta::new(c, a, b, b_hat).map_err(de::Error::custom)
+    }
+}
+
+impl<'a, C: Coefficient + Serialize> Serialize for RungeKutta<'a, C> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        OwnedRungeKutta::from(*self).serialize(serializer)
+    }
+}
+
+impl<'a, C: Coefficient + Serialize> Serialize for AdaptiveRungeKutta<'a, C> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        OwnedAdaptiveRungeKutta::from(*self).serialize(serializer)
+    }
+}
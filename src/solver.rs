@@ -0,0 +1,740 @@
+use super::*;
+
+use core::cmp::Ordering;
+use core::fmt::{Debug, Display, Formatter};
+
+//Integrates from `t0` to exactly `t1` with any of the integrators, shortening steps as
+//needed so that they land on `t1` and on each of the output times
+//
+//Without output times, every step is recorded (along with the initial state). With them,
+//only the states at those times are, and any outside of `[t0, t1]` are ignored.
+//
+//The trajectory also holds the statistics of the integration, counting the evaluations made
+//for dense output too.
+//
+//With dense output, the derivative is also recorded at each point so that the trajectory
+//can be interpolated accurately between them, at the cost of one more evaluation per point.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Solver<R> {
+    t0: R,
+    t1: R,
+    t_eval: Option<Vec<R>>,
+    control: StepControl<R>,
+    dense: bool,
+}
+
+//the time span given to a `Solver` ends before it starts (or isn't a number), since only
+//forward integration is supported
+#[derive(Clone, Copy, PartialEq)]
+pub struct SpanError<R> {
+    pub t0: R,
+    pub t1: R,
+}
+
+impl<R: Real> Display for SpanError<R> {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        write!(
+            f,
+            "The time span ends at t={} before it starts at t={}",
+            self.t1.clone().approx(),
+            self.t0.clone().approx()
+        )
+    }
+}
+
+impl<R: Real> Debug for SpanError<R> {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl<R: Real> ::core::error::Error for SpanError<R> {}
+
+impl<R: Real> Solver<R> {
+    pub fn new(t0: R, t1: R) -> Result<Self, SpanError<R>> {
+        match t1.partial_cmp(&t0) {
+            Some(Ordering::Greater | Ordering::Equal) => Ok(Solver {
+                t0,
+                t1,
+                t_eval: None,
+                control: Default::default(),
+                dense: false,
+            }),
+            _ => Err(SpanError { t0, t1 }),
+        }
+    }
+
+    //the times at which to record the solution, which are sorted with any repeats removed
+    pub fn t_eval(mut self, mut times: Vec<R>) -> Self {
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        times.dedup();
+        self.t_eval = Some(times);
+        self
+    }
+
+    //the limits for each adaptive step
+    pub fn control(mut self, control: StepControl<R>) -> Self {
+        self.control = control;
+        self
+    }
+
+    //whether to record the derivative at each point for interpolation
+    pub fn dense(mut self, dense: bool) -> Self {
+        self.dense = dense;
+        self
+    }
+
+    pub fn t0(&self) -> R {
+        self.t0.clone()
+    }
+    pub fn t1(&self) -> R {
+        self.t1.clone()
+    }
+
+    //the times at which steps have to end, in order, ending with t1
+    fn stops(&self) -> Vec<R> {
+        let mut stops: Vec<R> = match &self.t_eval {
+            Some(times) => times
+                .iter()
+                .filter(|t| **t > self.t0 && **t < self.t1)
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        stops.push(self.t1.clone());
+        stops
+    }
+
+    //whether a step ending at the given stop should be recorded
+    fn records(&self, t: &R) -> bool {
+        match &self.t_eval {
+            Some(times) => times.iter().any(|s| s == t),
+            None => true,
+        }
+    }
+
+    //adds the slopes to a finished trajectory if dense output was requested
+    fn slopes<D, S: Clone, F: Rhs<R, D, S>>(
+        &self,
+        mut trajectory: Trajectory<R, D, S>,
+        force: &F,
+    ) -> Trajectory<R, D, S> {
+        if self.dense {
+            let slopes = trajectory
+                .times
+                .iter()
+                .zip(trajectory.states.iter())
+                .map(|(t, s)| {
+                    let mut slope = s.clone();
+                    force.eval(t.clone(), s, &mut slope);
+                    slope
+                })
+                .collect();
+            trajectory.slopes = Some(slopes);
+        }
+        trajectory
+    }
+
+    //the loop shared by all of the methods, stopping at each output time and locating events
+    //
+    //`advance` takes a step from the given time without passing the given stop and reports
+    //it to the observer, and `reset` restarts the method from a new time and state
+    fn run<B, D, S, O, A, Z>(
+        &self,
+        mut buffer: B,
+        y0: S,
+        observer: &mut O,
+        advance: A,
+        reset: Z,
+    ) -> Result<Trajectory<R, D, S>, StepError<R>>
+    where
+        B: Clone,
+        D: Clone + Default,
+        S: State<R>,
+        O: Observer<R, D, S> + ?Sized,
+        A: Fn(&mut B, &R, &R, &mut dyn Observer<R, D, S>) -> Result<(R, D, S, bool), StepError<R>>,
+        Z: Fn(&mut B, &R, &S),
+    {
+        let mut trajectory = Trajectory::new();
+        let mut stats = Stats::new();
+        if self.records(&self.t0) {
+            trajectory.push(self.t0.clone(), Default::default(), y0.clone());
+        }
+
+        let events = observer.event_count();
+        let mut values = event_values(observer, &self.t0, &y0);
+        let mut t = self.t0.clone();
+
+        'stops: for stop in self.stops() {
+            while t < stop {
+                let (t1, data, s, landed, control) = if events == 0 {
+                    let mut watch = Watch {
+                        observer: (&mut stats, &mut *observer),
+                        control: Control::Continue,
+                    };
+                    let (t1, data, s, landed) = advance(&mut buffer, &t, &stop, &mut watch)?;
+                    (t1, data, s, landed, watch.control)
+                } else {
+                    //the step is only a trial until it is known not to pass an event, so keep
+                    //the buffer from before it to repeat it up to the event if it does
+                    let saved = buffer.clone();
+                    let mut trial = Trial {
+                        observer: &mut stats,
+                        step: None,
+                    };
+                    let (mut t1, mut data, mut s, mut landed) =
+                        advance(&mut buffer, &t, &stop, &mut trial)?;
+                    let mut step = trial.step.expect("a step was accepted");
+
+                    let next = event_values(observer, &t1, &s);
+                    let event = first_crossing(&t, &values, &t1, &next);
+                    if let Some(i) = event {
+                        (t1, data, s, buffer, step) = locate(
+                            &saved,
+                            &t,
+                            values[i].clone(),
+                            (t1, data, s, buffer, step),
+                            &advance,
+                            |t, s| observer.event_value(i, t, s),
+                            &mut stats,
+                        )?;
+                        landed = false;
+                    }
+
+                    //only now is the step that is kept reported, followed by the event
+                    let mut control = (&mut stats, &mut *observer).accepted(&step, &data, &mut s);
+                    if let Some(i) = event {
+                        control = control.max(observer.event(i, &t1, &data, &mut s));
+                    }
+                    if control == Control::Reset {
+                        reset(&mut buffer, &t1, &s);
+                    }
+                    values = event_values(observer, &t1, &s);
+                    (t1, data, s, landed, control)
+                };
+
+                t = t1;
+                if self.t_eval.is_none() || landed && self.records(&t) {
+                    trajectory.push(t.clone(), data, s);
+                }
+                if control == Control::Stop {
+                    break 'stops;
+                }
+            }
+        }
+
+        trajectory.stats = stats;
+        Ok(trajectory)
+    }
+
+    pub fn solve<I: Integrator + ?Sized, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>>(
+        &self,
+        method: &I,
+        y0: S,
+        dt: R,
+        force: F,
+    ) -> Trajectory<R, D, S> {
+        self.solve_observed(method, y0, dt, force, &mut ())
+    }
+
+    pub fn solve_observed<
+        I: Integrator + ?Sized,
+        D: Clone + Default,
+        S: State<R>,
+        F: Rhs<R, D, S>,
+        O: Observer<R, D, S> + ?Sized,
+    >(
+        &self,
+        method: &I,
+        y0: S,
+        dt: R,
+        force: F,
+        observer: &mut O,
+    ) -> Trajectory<R, D, S> {
+        let force = Counted::new(force);
+        let buffer = method.init(y0.clone(), dt.clone(), RhsRef(&force));
+
+        let step = |b: &mut Box<[(D, S)]>, t: R, h: R| method.step(t, b, h, RhsRef(&force));
+        let reset = &|b: &mut Box<[(D, S)]>, _t: &R, s: &S| {
+            *b = method.init(s.clone(), dt.clone(), RhsRef(&force));
+        };
+        let advance = |b: &mut Box<[(D, S)]>, t: &R, stop: &R, o: &mut dyn Observer<R, D, S>| {
+            Ok(fixed_advance(b, t, stop, &dt, o, &step, reset))
+        };
+
+        let trajectory = match self.run(buffer, y0, observer, advance, reset) {
+            Ok(trajectory) => trajectory,
+            Err(_) => unreachable!("fixed steps can't fail"),
+        };
+        let mut trajectory = self.slopes(trajectory, &force);
+        trajectory.stats.evaluations = force.count();
+        trajectory
+    }
+
+    pub fn solve_with_vel<
+        I: VelIntegrator + ?Sized,
+        D: Clone + Default,
+        S: State<R>,
+        V: Rhs<R, D, S>,
+        F: Rhs<R, D, S>,
+    >(
+        &self,
+        method: &I,
+        y0: S,
+        dt: R,
+        vel: V,
+        force: F,
+    ) -> Trajectory<R, D, S> {
+        self.solve_with_vel_observed(method, y0, dt, vel, force, &mut ())
+    }
+
+    pub fn solve_with_vel_observed<
+        I: VelIntegrator + ?Sized,
+        D: Clone + Default,
+        S: State<R>,
+        V: Rhs<R, D, S>,
+        F: Rhs<R, D, S>,
+        O: Observer<R, D, S> + ?Sized,
+    >(
+        &self,
+        method: &I,
+        y0: S,
+        dt: R,
+        vel: V,
+        force: F,
+        observer: &mut O,
+    ) -> Trajectory<R, D, S> {
+        let (vel, force) = (Counted::new(vel), Counted::new(force));
+        let buffer = method.init_with_vel(y0.clone(), dt.clone(), RhsRef(&vel), RhsRef(&force));
+
+        let step = |b: &mut Box<[(D, S)]>, t: R, h: R| {
+            method.step_with_vel(t, b, h, RhsRef(&vel), RhsRef(&force))
+        };
+        let reset = &|b: &mut Box<[(D, S)]>, _t: &R, s: &S| {
+            *b = method.init_with_vel(s.clone(), dt.clone(), RhsRef(&vel), RhsRef(&force));
+        };
+        let advance = |b: &mut Box<[(D, S)]>, t: &R, stop: &R, o: &mut dyn Observer<R, D, S>| {
+            Ok(fixed_advance(b, t, stop, &dt, o, &step, reset))
+        };
+
+        let trajectory = match self.run(buffer, y0, observer, advance, reset) {
+            Ok(trajectory) => trajectory,
+            Err(_) => unreachable!("fixed steps can't fail"),
+        };
+        let mut trajectory = self.slopes(trajectory, &force);
+        trajectory.stats.evaluations = force.count();
+        trajectory.stats.vel_evaluations = vel.count();
+        trajectory
+    }
+
+    pub fn solve_adaptive<
+        I: AdaptiveIntegrator + ?Sized,
+        D: Clone + Default,
+        S: State<R>,
+        M: Metric<S, R> + Clone,
+        F: Rhs<R, D, S>,
+    >(
+        &self,
+        method: &I,
+        y0: S,
+        ds: R,
+        force: F,
+        d: M,
+    ) -> Result<Trajectory<R, D, S>, StepError<R>> {
+        self.solve_adaptive_observed(method, y0, ds, force, d, &mut ())
+    }
+
+    pub fn solve_adaptive_observed<
+        I: AdaptiveIntegrator + ?Sized,
+        D: Clone + Default,
+        S: State<R>,
+        M: Metric<S, R> + Clone,
+        F: Rhs<R, D, S>,
+        O: Observer<R, D, S> + ?Sized,
+    >(
+        &self,
+        method: &I,
+        y0: S,
+        ds: R,
+        force: F,
+        d: M,
+        observer: &mut O,
+    ) -> Result<Trajectory<R, D, S>, StepError<R>> {
+        let force = Counted::new(force);
+        let init = |t: &R, s: &S| {
+            method.adaptive_init(t.clone(), s.clone(), ds.clone(), RhsRef(&force), d.clone())
+        };
+
+        let reset = |b: &mut Box<[(R, D, S)]>, t: &R, s: &S| *b = init(t, s);
+        let advance =
+            |b: &mut Box<[(R, D, S)]>, _t: &R, stop: &R, o: &mut dyn Observer<R, D, S>| {
+                adaptive_step_to(
+                    method,
+                    b,
+                    Some(stop),
+                    ds.clone(),
+                    RhsRef(&force),
+                    d.clone(),
+                    &self.control,
+                    o,
+                )
+            };
+
+        let trajectory = self.run(init(&self.t0, &y0), y0, observer, advance, reset)?;
+        let mut trajectory = self.slopes(trajectory, &force);
+        trajectory.stats.evaluations = force.count();
+        Ok(trajectory)
+    }
+}
+
+//passes the steps on to an observer, keeping the strongest control it returned
+struct Watch<O> {
+    observer: O,
+    control: Control,
+}
+
+impl<R, D, S, O: Observer<R, D, S>> Observer<R, D, S> for Watch<O> {
+    fn accepted(&mut self, step: &StepInfo<R>, data: &D, state: &mut S) -> Control {
+        let control = self.observer.accepted(step, data, state);
+        self.control = self.control.max(control);
+        control
+    }
+    fn rejected(&mut self, step: &StepInfo<R>, state: &S) {
+        self.observer.rejected(step, state)
+    }
+}
+
+//records the accepted step of a trial instead of reporting it, passing on only rejections
+struct Trial<R, O> {
+    observer: O,
+    step: Option<StepInfo<R>>,
+}
+
+impl<R: Real, D, S, O: Observer<R, D, S>> Observer<R, D, S> for Trial<R, O> {
+    fn accepted(&mut self, step: &StepInfo<R>, _data: &D, _state: &mut S) -> Control {
+        //a step that had to be taken in several parts is recorded as one
+        self.step = Some(match self.step.take() {
+            Some(first) => StepInfo {
+                dt: step.end() - first.start.clone(),
+                start: first.start,
+                error: step.error.clone(),
+            },
+            None => step.clone(),
+        });
+        Control::Continue
+    }
+    fn rejected(&mut self, step: &StepInfo<R>, state: &S) {
+        self.observer.rejected(step, state)
+    }
+}
+
+fn event_values<R, D, S, O: Observer<R, D, S> + ?Sized>(observer: &O, t: &R, s: &S) -> Vec<R> {
+    (0..observer.event_count())
+        .map(|i| observer.event_value(i, t, s))
+        .collect()
+}
+
+//whether an event function has crossed zero, not counting leaving zero as a crossing
+//so that an event isn't found again right after it was located
+fn crossed<R: Real>(g0: &R, g1: &R) -> bool {
+    let zero = R::zero();
+    (*g0 < zero && *g1 >= zero) || (*g0 > zero && *g1 <= zero)
+}
+
+//the event that changed sign earliest in a step, estimating the times linearly
+fn first_crossing<R: Real>(t0: &R, g0: &[R], t1: &R, g1: &[R]) -> Option<usize> {
+    let mut first: Option<(usize, R)> = None;
+    for (i, (a, b)) in g0.iter().zip(g1.iter()).enumerate() {
+        if crossed(a, b) {
+            let t = t0.clone() + (t1.clone() - t0.clone()) * (a.clone() / (a.clone() - b.clone()));
+            if first.as_ref().is_none_or(|(_, first)| t < *first) {
+                first = Some((i, t));
+            }
+        }
+    }
+    first.map(|(i, _)| i)
+}
+
+//finds the first point past the crossing of an event function within a step, by repeating
+//the step from the saved buffer with shorter step sizes chosen by the Illinois method
+//
+//returns the point along with the (possibly shortened) step that reached it, counting every
+//other repeat of the step, and any step rejected along the way, as rejected in `stats`
+#[allow(clippy::type_complexity)]
+fn locate<R: Real, B: Clone, D, S, A, G>(
+    saved: &B,
+    t0: &R,
+    g0: R,
+    end: (R, D, S, B, StepInfo<R>),
+    advance: &A,
+    g: G,
+    stats: &mut Stats<R>,
+) -> Result<(R, D, S, B, StepInfo<R>), StepError<R>>
+where
+    A: Fn(&mut B, &R, &R, &mut dyn Observer<R, D, S>) -> Result<(R, D, S, bool), StepError<R>>,
+    G: Fn(&R, &S) -> R,
+{
+    let tolerance = (end.0.clone() - t0.clone()) * R::repr(1e-12);
+    let (mut lo, mut g_lo) = (t0.clone(), g0.clone());
+    let mut g_hi = g(&end.0, &end.2);
+    let mut best = end;
+    let mut last_hi = None;
+
+    for _ in 0..100 {
+        let hi = best.0.clone();
+        if hi.clone() - lo.clone() <= tolerance || g_hi == R::zero() {
+            break;
+        }
+        let mut t =
+            hi.clone() - g_hi.clone() * (hi.clone() - lo.clone()) / (g_hi.clone() - g_lo.clone());
+
+        //a crossing estimated to be within the tolerance of the end is close enough, while one
+        //estimated right at the start still has to be stepped past
+        if hi - t.clone() <= tolerance {
+            break;
+        }
+        let min = lo.clone() + tolerance.clone();
+        if t.partial_cmp(&min) != Some(Ordering::Greater) {
+            t = min;
+        }
+
+        //repeat the step, without reporting anything to the observer
+        let mut buffer = saved.clone();
+        let mut trial = Trial {
+            observer: &mut *stats,
+            step: None,
+        };
+        let mut point = advance(&mut buffer, t0, &t, &mut trial)?;
+        while !point.3 && point.0 < t {
+            point = advance(&mut buffer, &point.0.clone(), &t, &mut trial)?;
+        }
+        let (_, data, s, _) = point;
+        let step = trial.step.expect("a step was accepted");
+
+        //whichever end of the bracket moves, one of the two steps is thrown away
+        stats.reject();
+        let g_t = g(&t, &s);
+        if crossed(&g0, &g_t) {
+            if last_hi == Some(true) {
+                g_lo *= R::repr(0.5);
+            }
+            g_hi = g_t;
+            best = (t, data, s, buffer, step);
+            last_hi = Some(true);
+        } else {
+            if last_hi == Some(false) {
+                g_hi *= R::repr(0.5);
+            }
+            lo = t;
+            g_lo = g_t;
+            last_hi = Some(false);
+        }
+    }
+    Ok(best)
+}
+
+//takes a fixed step without passing the stop, restarting the method if the observer
+//changed the state
+fn fixed_advance<R: Real, B, D, S, T, Z>(
+    buffer: &mut B,
+    t: &R,
+    stop: &R,
+    dt: &R,
+    observer: &mut dyn Observer<R, D, S>,
+    step: &T,
+    reset: &Z,
+) -> (R, D, S, bool)
+where
+    T: Fn(&mut B, R, R) -> (D, S),
+    Z: Fn(&mut B, &R, &S),
+{
+    let (h, landed) = fixed_step_to(t, dt, Some(stop));
+    let (data, mut s) = step(buffer, t.clone(), h.clone());
+    let t1 = if landed {
+        stop.clone()
+    } else {
+        t.clone() + h.clone()
+    };
+
+    let info = StepInfo {
+        start: t.clone(),
+        dt: h,
+        error: None,
+    };
+    if observer.accepted(&info, &data, &mut s) == Control::Reset {
+        reset(buffer, &t1, &s);
+    }
+    (t1, data, s, landed)
+}
+
+//the size of the next fixed step from `t`, and whether it lands on the given stop
+//
+//steps that are within a hair of the stop are stretched to land on it instead of
+//leaving a tiny step to be taken afterwards
+pub(crate) fn fixed_step_to<R: Real>(t: &R, dt: &R, stop: Option<&R>) -> (R, bool) {
+    if let Some(stop) = stop {
+        let remaining = stop.clone() - t.clone();
+        if remaining <= dt.clone() * (R::one() + R::repr(1e-10)) {
+            return (remaining, true);
+        }
+    }
+    (dt.clone(), false)
+}
+
+//takes an adaptive step, shortened if needed so as not to pass the given stop, and
+//returns whether it landed on the stop
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn adaptive_step_to<
+    I: AdaptiveIntegrator + ?Sized,
+    R: Real,
+    D: Clone + Default,
+    S: State<R>,
+    M: Metric<S, R>,
+    F: Rhs<R, D, S>,
+    O: Observer<R, D, S> + ?Sized,
+>(
+    method: &I,
+    state: &mut [(R, D, S)],
+    stop: Option<&R>,
+    ds: R,
+    force: F,
+    d: M,
+    control: &StepControl<R>,
+    observer: &mut O,
+) -> Result<(R, D, S, bool), StepError<R>> {
+    //shorten the step if it would pass the stop, but keep the proposed size for afterwards
+    let proposed = state[1].0.clone();
+    let remaining = stop.map(|stop| stop.clone() - state[0].0.clone());
+    let shortened = match &remaining {
+        Some(remaining) if *remaining <= proposed => {
+            state[1].0 = remaining.clone();
+            true
+        }
+        _ => false,
+    };
+
+    //a step cut short to land on the stop may be shorter than `min_dt`
+    let control = match &remaining {
+        Some(remaining) if shortened && *remaining < control.min_dt => &StepControl {
+            min_dt: remaining.clone(),
+            ..control.clone()
+        },
+        _ => control,
+    };
+    let (t, data, s) = method.try_adaptive_step(state, ds, force, d, control, observer)?;
+
+    //a rejection may have cut the step short of the stop, otherwise snap to it exactly
+    if let (true, Some(stop), Some(remaining)) = (shortened, stop, remaining) {
+        let gap = stop.clone() - t.clone();
+        if gap.clone() * R::repr(1e8) <= remaining || t.clone() + gap * R::repr(0.5) == t {
+            state[0].0 = stop.clone();
+            state[1].0 = proposed;
+            return Ok((stop.clone(), data, s, true));
+        }
+    }
+    Ok((t, data, s, false))
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_located_before_the_step_is_reported() {
+        let mut steps = Vec::new();
+        let mut observer = (
+            OnEvent {
+                g: |_t: &f64, y: &f64| y - 0.5,
+                f: |_t: &f64, _data: &(), y: &mut f64| {
+                    *y += 10.0;
+                    Control::Reset
+                },
+            },
+            OnStep(|step: &StepInfo<f64>, _data: &(), _y: &mut f64| {
+                steps.push((step.start, step.end()));
+                Control::Continue
+            }),
+        );
+        let trajectory = Solver::new(0.0, 1.1).unwrap().solve_observed(
+            &RK4,
+            0.0,
+            0.3,
+            |_t: f64, _y: f64| ((), 1.0),
+            &mut observer,
+        );
+
+        //the impulse applied at the event is kept
+        let last = *trajectory.states.last().unwrap();
+        assert!((last - 11.1).abs() < 1e-9, "{last}");
+
+        //each kept step is reported once, the one ending at the event included
+        let expected = [(0.0, 0.3), (0.3, 0.5), (0.5, 0.8), (0.8, 1.1)];
+        assert_eq!(steps.len(), expected.len(), "{steps:?}");
+        for ((start, end), (a, b)) in steps.iter().zip(expected) {
+            assert!(
+                (start - a).abs() < 1e-9 && (end - b).abs() < 1e-9,
+                "{steps:?}"
+            );
+        }
+
+        let stats = &trajectory.stats;
+        assert_eq!(stats.accepted, 4);
+        assert!((stats.min_dt.unwrap() - 0.2).abs() < 1e-9, "{stats}");
+        assert!((stats.max_dt.unwrap() - 0.3).abs() < 1e-9, "{stats}");
+
+        //thrown away are the step that first went past the event, and the repeat of it that
+        //rounding left just short of the event
+        assert_eq!(stats.rejected, 2, "{stats}");
+    }
+
+    #[test]
+    fn adaptive_events_keep_the_change_to_the_state() {
+        let mut events = 0;
+        let mut observer = OnEvent {
+            g: |_t: &f64, y: &f64| y - 0.5,
+            f: |_t: &f64, _data: &(), y: &mut f64| {
+                events += 1;
+                *y = 2.0;
+                Control::Reset
+            },
+        };
+        let trajectory = Solver::new(0.0, 1.0)
+            .unwrap()
+            .solve_adaptive_observed(
+                &DORMAND_PRINCE,
+                0.0,
+                1e-9,
+                |_t: f64, _y: f64| ((), 1.0),
+                maths_traits::analysis::metric::InnerProductMetric,
+                &mut observer,
+            )
+            .unwrap();
+
+        assert_eq!(events, 1);
+        let last = *trajectory.states.last().unwrap();
+        assert!((last - 2.5).abs() < 1e-9, "{last}");
+    }
+
+    #[test]
+    fn spans_have_to_go_forward() {
+        assert_eq!(
+            Solver::new(1.0, 0.0).unwrap_err(),
+            SpanError { t0: 1.0, t1: 0.0 }
+        );
+        assert!(Solver::new(0.0, f64::NAN).is_err());
+        assert!(Solver::new(1.0, 1.0).is_ok());
+    }
+
+    #[test]
+    fn output_times_are_sorted_and_deduplicated() {
+        let trajectory = Solver::new(0.0, 1.0)
+            .unwrap()
+            .t_eval(vec![0.75, 0.25, 0.5, 0.25, 1.0])
+            .solve(&RK4, 1.0, 0.1, |_t: f64, y: f64| ((), y));
+        assert_eq!(trajectory.times, [0.25, 0.5, 0.75, 1.0]);
+        for (t, y) in trajectory.times.iter().zip(trajectory.states.iter()) {
+            assert!((y - t.exp()).abs() < 1e-5, "{t} {y}");
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use super::*;
+
+use core::fmt::Write;
+
+//complex numbers are just passed around as (re, im) pairs
+type C = (f64, f64);
+
+fn c_add(x: C, y: C) -> C {
+    (x.0 + y.0, x.1 + y.1)
+}
+fn c_sub(x: C, y: C) -> C {
+    (x.0 - y.0, x.1 - y.1)
+}
+fn c_mul(x: C, y: C) -> C {
+    (x.0 * y.0 - x.1 * y.1, x.0 * y.1 + x.1 * y.0)
+}
+fn c_div(x: C, y: C) -> C {
+    let d = y.0 * y.0 + y.1 * y.1;
+    ((x.0 * y.0 + x.1 * y.1) / d, (x.1 * y.0 - x.0 * y.1) / d)
+}
+fn c_abs(x: C) -> f64 {
+    x.0.hypot(x.1)
+}
+
+//evaluates the polynomial with the given coefficients (lowest degree first) using Horner's method
+fn horner(p: &[f64], z: C) -> C {
+    p.iter()
+        .rev()
+        .fold((0.0, 0.0), |acc, &c| c_add(c_mul(acc, z), (c, 0.0)))
+}
+
+//the coefficients of det(I - zM) = 1 + c_1 z + ... + c_n z^n using the Faddeev-LeVerrier algorithm
+fn det_coefficients(m: &[Vec<f64>]) -> Vec<f64> {
+    let n = m.len();
+    let mut coeffs = vec![1.0];
+    let mut m_k = vec![vec![0.0; n]; n];
+    for k in 1..=n {
+        //M_k = M*M_(k-1) + c_(k-1)*I
+        let mut next = vec![vec![0.0; n]; n];
+        for i in 0..n {
+            for j in 0..n {
+                next[i][j] = (0..n).map(|l| m[i][l] * m_k[l][j]).sum::<f64>();
+            }
+            next[i][i] += coeffs[k - 1];
+        }
+        m_k = next;
+
+        //c_k = -tr(M*M_k)/k
+        let trace: f64 = (0..n)
+            .map(|i| (0..n).map(|l| m[i][l] * m_k[l][i]).sum::<f64>())
+            .sum();
+        coeffs.push(-trace / k as f64);
+    }
+    trim(coeffs)
+}
+
+//removes the (numerically) vanishing highest-degree coefficients
+fn trim(mut p: Vec<f64>) -> Vec<f64> {
+    let scale = p.iter().fold(0.0f64, |m, c| m.max(c.abs()));
+    while p.len() > 1 && p.last().unwrap().abs() <= 1e-13 * scale {
+        p.pop();
+    }
+    p
+}
+
+//the roots of a polynomial using the Durand-Kerner method
+fn roots(p: &[f64]) -> Vec<C> {
+    let n = p.len() - 1;
+    if n == 0 {
+        return Vec::new();
+    }
+    let lead = p[n];
+    let monic: Vec<f64> = p.iter().map(|c| c / lead).collect();
+
+    let mut z: Vec<C> = (0..n)
+        .map(|k| {
+            let (s, c) = (0.4 + 0.9 * k as f64).sin_cos();
+            (c, s)
+        })
+        .collect();
+    for _ in 0..500 {
+        let mut change = 0.0f64;
+        for i in 0..n {
+            let denom = (0..n)
+                .filter(|&j| j != i)
+                .fold((1.0, 0.0), |acc, j| c_mul(acc, c_sub(z[i], z[j])));
+            let delta = c_div(horner(&monic, z[i]), denom);
+            z[i] = c_sub(z[i], delta);
+            change = change.max(c_abs(delta));
+        }
+        if change < 1e-14 {
+            break;
+        }
+    }
+    z
+}
+
+//the linear stability function R(z) = P(z)/Q(z) of a Runge-Kutta method, so that
+//one step of the method applied to y' = λy gives y_(n+1) = R(hλ)y_n
+#[derive(Clone, PartialEq, Debug)]
+pub struct StabilityFunction {
+    pub numerator: Vec<f64>,
+    pub denominator: Vec<f64>,
+}
+
+impl StabilityFunction {
+    fn with_weights<T: Tableau + ?Sized, B: Fn(usize) -> f64>(tableau: &T, b: B) -> Self {
+        //R(z) = det(I - zA + z1b^T) / det(I - zA)
+        let s = tableau.stages();
+        let a: Vec<Vec<f64>> = (0..s)
+            .map(|i| (0..s).map(|j| tableau.a(i, j).to_f64()).collect())
+            .collect();
+        let a_minus_b: Vec<Vec<f64>> = (0..s)
+            .map(|i| (0..s).map(|j| a[i][j] - b(j)).collect())
+            .collect();
+        StabilityFunction {
+            numerator: det_coefficients(&a_minus_b),
+            denominator: det_coefficients(&a),
+        }
+    }
+
+    pub fn of<T: Tableau + ?Sized>(tableau: &T) -> Self {
+        Self::with_weights(tableau, |j| tableau.b(j).to_f64())
+    }
+
+    pub fn of_embedded<T: Tableau + ?Sized>(tableau: &T) -> Option<Self> {
+        tableau.b_hat(0)?;
+        Some(Self::with_weights(tableau, |j| {
+            tableau.b_hat(j).unwrap().to_f64()
+        }))
+    }
+
+    pub fn is_polynomial(&self) -> bool {
+        self.denominator.len() == 1
+    }
+
+    pub fn eval(&self, re: f64, im: f64) -> (f64, f64) {
+        c_div(
+            horner(&self.numerator, (re, im)),
+            horner(&self.denominator, (re, im)),
+        )
+    }
+
+    pub fn abs(&self, re: f64, im: f64) -> f64 {
+        c_abs(self.eval(re, im))
+    }
+
+    pub fn is_stable_at(&self, re: f64, im: f64) -> bool {
+        self.abs(re, im) <= 1.0 + 1e-12
+    }
+
+    pub fn poles(&self) -> Vec<(f64, f64)> {
+        roots(&self.denominator)
+    }
+
+    //the limit of |R(z)| as z goes to infinity
+    pub fn abs_at_infinity(&self) -> f64 {
+        let (p, q) = (self.numerator.len(), self.denominator.len());
+        if p > q {
+            f64::INFINITY
+        } else if p < q {
+            0.0
+        } else {
+            (self.numerator[p - 1] / self.denominator[q - 1]).abs()
+        }
+    }
+
+    //the interval of the real axis containing 0 on which |R(x)| <= 1
+    //
+    //an endpoint is infinite if the method is stable up to at least ±1e6
+    pub fn real_interval(&self) -> (f64, f64) {
+        let edge = |dir: f64| {
+            //march outward until the method becomes unstable, then bisect
+            let mut x = 0.0;
+            let mut step = 1e-3;
+            loop {
+                let next = x + dir * step;
+                if !self.is_stable_at(next, 0.0) {
+                    let (mut lo, mut hi) = (x, next);
+                    for _ in 0..100 {
+                        let mid = 0.5 * (lo + hi);
+                        if self.is_stable_at(mid, 0.0) {
+                            lo = mid;
+                        } else {
+                            hi = mid;
+                        }
+                    }
+                    //don't report the slack in the comparison as part of the interval
+                    return if lo.abs() < 1e-9 { 0.0 } else { lo };
+                }
+                if next.abs() > 1e6 {
+                    return dir * f64::INFINITY;
+                }
+                x = next;
+                if x.abs() >= 10.0 * step {
+                    step *= 2.0;
+                }
+            }
+        };
+        (edge(-1.0), edge(1.0))
+    }
+
+    //whether |R(z)| <= 1 on the entire left half-plane
+    pub fn is_a_stable(&self) -> bool {
+        if self.abs_at_infinity() > 1.0 + 1e-12 {
+            return false;
+        }
+        //by the maximum principle, it is enough to have no poles in the left half-plane
+        //and to be bounded by 1 on the imaginary axis
+        if self.poles().iter().any(|p| p.0 <= 0.0) {
+            return false;
+        }
+        let mut y = 1e-4;
+        while y < 1e8 {
+            if !self.is_stable_at(0.0, y) || !self.is_stable_at(0.0, -y) {
+                return false;
+            }
+            y *= 1.01;
+        }
+        true
+    }
+
+    //whether the method is A-stable and R(z) -> 0 as z -> ∞
+    pub fn is_l_stable(&self) -> bool {
+        self.is_a_stable() && self.abs_at_infinity() <= 1e-12
+    }
+
+    pub fn region(&self, re: (f64, f64), im: (f64, f64), nx: usize, ny: usize) -> StabilityRegion {
+        StabilityRegion::sample(self, re, im, nx, ny)
+    }
+}
+
+//|R(z)| sampled on a rectangular grid of the complex plane
+#[derive(Clone, PartialEq, Debug)]
+pub struct StabilityRegion {
+    pub re: (f64, f64),
+    pub im: (f64, f64),
+    pub nx: usize,
+    pub ny: usize,
+    //row-major, with values[j*nx + i] at re.0 + i*dx, im.0 + j*dy
+    pub values: Vec<f64>,
+}
+
+impl StabilityRegion {
+    pub fn sample(
+        r: &StabilityFunction,
+        re: (f64, f64),
+        im: (f64, f64),
+        nx: usize,
+        ny: usize,
+    ) -> Self {
+        let mut region = StabilityRegion {
+            re,
+            im,
+            nx: nx.max(2),
+            ny: ny.max(2),
+            values: Vec::new(),
+        };
+        for j in 0..region.ny {
+            for i in 0..region.nx {
+                let (x, y) = region.point(i, j);
+                region.values.push(r.abs(x, y));
+            }
+        }
+        region
+    }
+
+    pub fn point(&self, i: usize, j: usize) -> (f64, f64) {
+        let dx = (self.re.1 - self.re.0) / (self.nx - 1) as f64;
+        let dy = (self.im.1 - self.im.0) / (self.ny - 1) as f64;
+        (self.re.0 + i as f64 * dx, self.im.0 + j as f64 * dy)
+    }
+
+    pub fn value(&self, i: usize, j: usize) -> f64 {
+        self.values[j * self.nx + i]
+    }
+
+    //the points where |R(z)| = 1 along the edges of the grid, found by linear interpolation
+    pub fn boundary(&self) -> Vec<(f64, f64)> {
+        let mut points = Vec::new();
+        let mut crossing = |(i0, j0): (usize, usize), (i1, j1): (usize, usize)| {
+            let (v0, v1) = (self.value(i0, j0) - 1.0, self.value(i1, j1) - 1.0);
+            if (v0 <= 0.0) != (v1 <= 0.0) && v0.is_finite() && v1.is_finite() {
+                let t = v0 / (v0 - v1);
+                let (p0, p1) = (self.point(i0, j0), self.point(i1, j1));
+                points.push((p0.0 + t * (p1.0 - p0.0), p0.1 + t * (p1.1 - p0.1)));
+            }
+        };
+        for j in 0..self.ny {
+            for i in 0..self.nx {
+                if i + 1 < self.nx {
+                    crossing((i, j), (i + 1, j));
+                }
+                if j + 1 < self.ny {
+                    crossing((i, j), (i, j + 1));
+                }
+            }
+        }
+        points
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("re,im\n");
+        for (x, y) in self.boundary() {
+            writeln!(out, "{},{}", x, y).unwrap();
+        }
+        out
+    }
+
+    //draws the stable cells of the grid along with the axes and the boundary
+    pub fn to_svg(&self, width: usize, height: usize) -> String {
+        let (w, h) = (width as f64, height as f64);
+        let sx = |x: f64| (x - self.re.0) / (self.re.1 - self.re.0) * w;
+        let sy = |y: f64| (self.im.1 - y) / (self.im.1 - self.im.0) * h;
+        let (cw, ch) = (w / (self.nx - 1) as f64, h / (self.ny - 1) as f64);
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+            width, height, width, height
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+            width, height
+        )
+        .unwrap();
+        for j in 0..self.ny {
+            for i in 0..self.nx {
+                if self.value(i, j) <= 1.0 {
+                    let (x, y) = self.point(i, j);
+                    writeln!(
+                        out,
+                        "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"#9ecae1\"/>",
+                        sx(x) - cw / 2.0,
+                        sy(y) - ch / 2.0,
+                        cw,
+                        ch
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        if self.re.0 <= 0.0 && self.re.1 >= 0.0 {
+            writeln!(
+                out,
+                "<line x1=\"{:.2}\" y1=\"0\" x2=\"{:.2}\" y2=\"{}\" stroke=\"gray\"/>",
+                sx(0.0),
+                sx(0.0),
+                height
+            )
+            .unwrap();
+        }
+        if self.im.0 <= 0.0 && self.im.1 >= 0.0 {
+            writeln!(
+                out,
+                "<line x1=\"0\" y1=\"{:.2}\" x2=\"{}\" y2=\"{:.2}\" stroke=\"gray\"/>",
+                sy(0.0),
+                width,
+                sy(0.0)
+            )
+            .unwrap();
+        }
+        for (x, y) in self.boundary() {
+            writeln!(
+                out,
+                "<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"1\" fill=\"#08519c\"/>",
+                sx(x),
+                sy(y)
+            )
+            .unwrap();
+        }
+        writeln!(out, "</svg>").unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn real_intervals_of_explicit_methods() {
+        let (lo, hi) = StabilityFunction::of(&EULER).real_interval();
+        assert!((lo + 2.0).abs() < 1e-9 && hi == 0.0, "{lo} {hi}");
+
+        //the real root of 1 + x + x²/2 + x³/6 + x⁴/24 = 1 besides 0
+        let (lo, hi) = StabilityFunction::of(&RK4).real_interval();
+        assert!(
+            (lo + 2.785293563405282).abs() < 1e-9 && hi == 0.0,
+            "{lo} {hi}"
+        );
+    }
+
+    #[test]
+    fn explicit_methods_are_not_a_stable() {
+        for r in [
+            StabilityFunction::of(&EULER),
+            StabilityFunction::of(&RK4),
+            StabilityFunction::of(&DORMAND_PRINCE),
+        ] {
+            assert!(r.is_polynomial());
+            assert!(!r.is_a_stable() && !r.is_l_stable(), "{r:?}");
+        }
+    }
+
+    #[test]
+    fn implicit_stability_functions_can_be_a_and_l_stable() {
+        //backward Euler, which vanishes at infinity
+        let r = StabilityFunction {
+            numerator: vec![1.0],
+            denominator: vec![1.0, -1.0],
+        };
+        assert!(r.is_a_stable() && r.is_l_stable(), "{r:?}");
+        assert_eq!(r.real_interval(), (f64::NEG_INFINITY, 0.0));
+
+        //the implicit midpoint method, which goes to -1 at infinity
+        let r = StabilityFunction {
+            numerator: vec![1.0, 0.5],
+            denominator: vec![1.0, -0.5],
+        };
+        assert!(r.is_a_stable() && !r.is_l_stable(), "{r:?}");
+    }
+
+    #[test]
+    fn regions_are_written_as_csv_and_svg() {
+        let region = StabilityFunction::of(&EULER).region((-3.0, 1.0), (-2.0, 2.0), 41, 41);
+
+        //the boundary of Euler's region is the unit circle around -1
+        let csv = region.to_csv();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("re,im"));
+        let points: Vec<(f64, f64)> = lines
+            .map(|line| {
+                let (x, y) = line.split_once(',').unwrap();
+                (x.parse().unwrap(), y.parse().unwrap())
+            })
+            .collect();
+        assert_eq!(points.len(), region.boundary().len());
+        assert!(!points.is_empty());
+        for (x, y) in points {
+            assert!(((x + 1.0).hypot(y) - 1.0).abs() < 0.05, "{x},{y}");
+        }
+
+        let svg = region.to_svg(200, 100);
+        assert!(svg
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"200\" height=\"100\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        let stable = region.values.iter().filter(|&&v| v <= 1.0).count();
+        assert_eq!(svg.matches("fill=\"#9ecae1\"").count(), stable);
+        assert_eq!(svg.matches("<circle").count(), region.boundary().len());
+        assert_eq!(svg.matches("<line").count(), 2);
+    }
+}
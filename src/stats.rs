@@ -0,0 +1,117 @@
+use super::*;
+
+use core::cell::Cell;
+use core::fmt::{Display, Formatter};
+
+//Counts of the work done by an integration, for comparing methods
+//
+//The step sizes are those of accepted steps.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats<R> {
+    pub evaluations: usize,
+    pub vel_evaluations: usize,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub min_dt: Option<R>,
+    pub max_dt: Option<R>,
+    pub last_dt: Option<R>,
+}
+
+impl<R> Default for Stats<R> {
+    fn default() -> Self {
+        Stats {
+            evaluations: 0,
+            vel_evaluations: 0,
+            accepted: 0,
+            rejected: 0,
+            min_dt: None,
+            max_dt: None,
+            last_dt: None,
+        }
+    }
+}
+
+impl<R: Real> Stats<R> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    //records an accepted step of the given size
+    pub fn accept(&mut self, dt: R) {
+        self.accepted += 1;
+        if self.min_dt.as_ref().is_none_or(|min| dt < *min) {
+            self.min_dt = Some(dt.clone());
+        }
+        if self.max_dt.as_ref().is_none_or(|max| dt > *max) {
+            self.max_dt = Some(dt.clone());
+        }
+        self.last_dt = Some(dt);
+    }
+
+    //records a rejected attempt at a step
+    pub fn reject(&mut self) {
+        self.rejected += 1;
+    }
+
+    //the total number of attempted steps
+    pub fn steps(&self) -> usize {
+        self.accepted + self.rejected
+    }
+}
+
+impl<R: Real> Display for Stats<R> {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        let dt = |dt: &Option<R>| dt.clone().map_or(f64::NAN, |dt| dt.approx());
+        write!(
+            f,
+            "{} evaluations ({} velocity), {} accepted and {} rejected steps, \
+             dt in [{:e}, {:e}] (last {:e})",
+            self.evaluations,
+            self.vel_evaluations,
+            self.accepted,
+            self.rejected,
+            dt(&self.min_dt),
+            dt(&self.max_dt),
+            dt(&self.last_dt)
+        )
+    }
+}
+
+//a right-hand side that counts how many times it has been evaluated
+pub struct Counted<F> {
+    pub rhs: F,
+    count: Cell<usize>,
+}
+
+impl<F> Counted<F> {
+    pub fn new(rhs: F) -> Self {
+        Counted {
+            rhs,
+            count: Cell::new(0),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.get()
+    }
+
+    //returns the count so far and starts again from zero
+    pub fn reset(&self) -> usize {
+        self.count.replace(0)
+    }
+}
+
+impl<R, D, S, F: Rhs<R, D, S>> Rhs<R, D, S> for Counted<F> {
+    fn eval(&self, t: R, y: &S, dy: &mut S) -> D {
+        self.count.set(self.count.get() + 1);
+        self.rhs.eval(t, y, dy)
+    }
+}
+
+//so that the count can still be read after passing the right-hand side to a stepper
+impl<R, D, S, F: Rhs<R, D, S>> Rhs<R, D, S> for &Counted<F> {
+    fn eval(&self, t: R, y: &S, dy: &mut S) -> D {
+        (**self).eval(t, y, dy)
+    }
+}
@@ -0,0 +1,204 @@
+use super::*;
+
+use core::fmt::{Debug, Display, Formatter};
+
+fn max<R: Real>(x: R, y: R) -> R {
+    if x > y {
+        x
+    } else {
+        y
+    }
+}
+
+fn min<R: Real>(x: R, y: R) -> R {
+    if x < y {
+        x
+    } else {
+        y
+    }
+}
+
+//Estimates a good initial step size for a method of the given order, following the
+//algorithm in Hairer, Nørsett & Wanner, "Solving Ordinary Differential Equations I", II.4
+//
+//`f0` must be the derivative at `(t0, y0)`, and `tolerance` is the error allowed per step
+//as measured by the metric. The right-hand side is evaluated once more, a short explicit
+//Euler step away from `t0`.
+pub fn initial_step<R: Real, D, S: State<R>, M: Metric<S, R>, F: Rhs<R, D, S>>(
+    t0: R,
+    y0: &S,
+    f0: &S,
+    order: usize,
+    tolerance: R,
+    force: &F,
+    d: &M,
+) -> R {
+    let norm = |x: &S| d.distance(x.clone(), S::zero()) / tolerance.clone();
+
+    //a first guess making the Euler increment small compared to the state
+    let d0 = norm(y0);
+    let d1 = norm(f0);
+    let h0 = if d0 < R::repr(1e-5) || d1 < R::repr(1e-5) {
+        R::repr(1e-6)
+    } else {
+        R::repr(0.01) * d0 / d1.clone()
+    };
+
+    //an estimate of the second derivative from an explicit Euler step
+    let y1 = y0.clone() + f0.clone() * h0.clone();
+    let mut f1 = f0.clone();
+    force.eval(t0 + h0.clone(), &y1, &mut f1);
+    let d2 = d.distance(f1, f0.clone()) / tolerance / h0.clone();
+
+    //the step such that the local error h^(p+1) * max(d1, d2) is about 0.01
+    let d12 = max(d1, d2);
+    let h1 = if d12 <= R::repr(1e-15) {
+        max(R::repr(1e-6), h0.clone() * R::repr(1e-3))
+    } else {
+        (R::repr(0.01) / d12).root(R::repr((order + 1) as f64))
+    };
+
+    min(h0 * R::repr(100.0), h1)
+}
+
+//whether a real number is neither infinite nor NaN (for which x - x is NaN instead of zero)
+#[cfg(feature = "alloc")]
+pub(crate) fn is_finite<R: Real>(x: &R) -> bool {
+    x.clone() - x.clone() == R::zero()
+}
+
+//limits on a single adaptive step
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StepControl<R> {
+    //the smallest step size that may be attempted
+    pub min_dt: R,
+    //the largest step size that may be taken, if any
+    pub max_dt: Option<R>,
+    //how many attempts may be rejected before giving up on the step
+    pub max_rejections: usize,
+}
+
+impl<R: Real> Default for StepControl<R> {
+    fn default() -> Self {
+        StepControl {
+            min_dt: R::zero(),
+            max_dt: None,
+            max_rejections: 100,
+        }
+    }
+}
+
+impl<R: Real> StepControl<R> {
+    //clamps a proposed step size to `min_dt` and `max_dt`
+    pub fn limit(&self, dt: R) -> R {
+        let dt = max(dt, self.min_dt.clone());
+        match &self.max_dt {
+            Some(max_dt) => min(dt, max_dt.clone()),
+            None => dt,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StepError<R> {
+    StepSizeUnderflow(R, R),
+    NonFinite(R),
+    TooManyRejections(R, usize),
+}
+
+impl<R: Real> Display for StepError<R> {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        match self {
+            StepError::StepSizeUnderflow(t, dt) => write!(
+                f,
+                "Step size underflow at t={}; the step size {:e} is too small",
+                t.clone().approx(),
+                dt.clone().approx()
+            ),
+            StepError::NonFinite(t) => {
+                write!(
+                    f,
+                    "Non-finite state or error estimate at t={}",
+                    t.clone().approx()
+                )
+            }
+            StepError::TooManyRejections(t, n) => write!(
+                f,
+                "Step at t={} was rejected {} times",
+                t.clone().approx(),
+                n
+            ),
+        }
+    }
+}
+
+impl<R: Real> Debug for StepError<R> {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl<R: Real> ::core::error::Error for StepError<R> {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use maths_traits::analysis::metric::InnerProductMetric;
+
+    fn control(min_dt: f64, max_dt: Option<f64>) -> StepControl<f64> {
+        StepControl {
+            min_dt,
+            max_dt,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn limits_clamp_both_ways() {
+        let control = control(0.5, Some(2.0));
+        assert_eq!(control.limit(0.147), 0.5);
+        assert_eq!(control.limit(1.0), 1.0);
+        assert_eq!(control.limit(3.0), 2.0);
+        assert_eq!(StepControl::default().limit(1e-300), 1e-300);
+    }
+
+    //takes a single Dormand-Prince step of y' = y from (0, 1), starting with the given proposal
+    fn step(proposed: f64, ds: f64, control: StepControl<f64>) -> Result<f64, StepError<f64>> {
+        let f = |_t: f64, y: f64| ((), y);
+        let method = &DORMAND_PRINCE;
+        let mut buffer =
+            AdaptiveIntegrator::adaptive_init(method, 0.0, 1.0, ds, f, InnerProductMetric);
+        buffer[1].0 = proposed;
+        let d = InnerProductMetric;
+        AdaptiveIntegrator::try_adaptive_step(method, &mut buffer, ds, f, d, &control, &mut ())
+            .map(|(t, _, _)| t)
+    }
+
+    #[test]
+    fn small_proposals_are_raised_to_the_minimum() {
+        assert_eq!(step(0.147, 1.0, control(0.5, None)), Ok(0.5));
+        assert_eq!(step(0.147, 1.0, control(0.0, None)), Ok(0.147));
+    }
+
+    #[test]
+    fn underflow_is_only_reported_below_the_minimum() {
+        assert_eq!(
+            step(2.0, 1e-12, control(0.5, None)),
+            Err(StepError::StepSizeUnderflow(0.0, 0.5))
+        );
+        assert!(step(2.0, 1e-6, control(0.01, None)).unwrap() >= 0.01);
+    }
+
+    #[test]
+    fn steps_to_a_stop_may_be_shorter_than_the_minimum() {
+        let f = |_t: f64, y: f64| ((), y);
+        let trajectory = Solver::new(0.0, 0.6)
+            .unwrap()
+            .control(control(0.5, None))
+            .solve_adaptive(&DORMAND_PRINCE, 1.0, 1e-3, f, InnerProductMetric)
+            .unwrap();
+        assert_eq!(trajectory.times, [0.0, 0.5, 0.6]);
+        assert!(trajectory.stats.min_dt.unwrap() < 0.5);
+    }
+}
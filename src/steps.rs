@@ -0,0 +1,588 @@
+use super::*;
+
+//keeps track of the current time and of where the iterators have to stop or sample
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Clock<R> {
+    t0: R,
+    t: R,
+    end: Option<R>,
+    interval: Option<R>,
+    samples: usize,
+    done: bool,
+}
+
+impl<R: Real> Clock<R> {
+    fn new(t0: R) -> Self {
+        Clock {
+            t0: t0.clone(),
+            t: t0,
+            end: None,
+            interval: None,
+            samples: 1,
+            done: false,
+        }
+    }
+
+    //the next sample time, computed from t0 so that rounding errors don't accumulate
+    fn next_sample(&self) -> Option<R> {
+        let interval = self.interval.clone()?;
+        Some(self.t0.clone() + interval * R::repr(self.samples as f64))
+    }
+
+    //the next time that a step has to land on exactly
+    fn stop(&self) -> Option<R> {
+        match (self.next_sample(), self.end.clone()) {
+            (Some(sample), Some(end)) => Some(if sample < end { sample } else { end }),
+            (sample, end) => sample.or(end),
+        }
+    }
+
+    //moves to the end of a step and returns whether it should be yielded
+    fn advance(&mut self, t: R, landed: bool) -> bool {
+        if !landed {
+            self.t = t;
+            return self.interval.is_none();
+        }
+        self.t = self.stop().unwrap();
+        if self.end.as_ref() == Some(&self.t) {
+            self.done = true;
+        }
+        if self.next_sample().as_ref() == Some(&self.t) {
+            self.samples += 1;
+        }
+        true
+    }
+}
+
+//An iterator over the steps of an `Integrator`, yielding the time, data, and state after each one
+//
+//It owns a copy of the method, the right-hand side, and the buffer returned by `init`.
+pub struct Steps<I, R, D, S, F> {
+    method: I,
+    force: Counted<F>,
+    buffer: Box<[(D, S)]>,
+    dt: R,
+    clock: Clock<R>,
+    stats: Stats<R>,
+}
+
+//An iterator over the steps of a `VelIntegrator`, yielding the time, data, and state after each one
+pub struct VelSteps<I, R, D, S, V, F> {
+    method: I,
+    vel: Counted<V>,
+    force: Counted<F>,
+    buffer: Box<[(D, S)]>,
+    dt: R,
+    clock: Clock<R>,
+    stats: Stats<R>,
+}
+
+//An iterator over the steps of an `AdaptiveIntegrator`, yielding the time, data, and state after each one
+//
+//Iteration ends early if a step fails, in which case the error is available from `error()`.
+pub struct AdaptiveSteps<I, R, D, S, M, F> {
+    method: I,
+    force: Counted<F>,
+    d: M,
+    ds: R,
+    buffer: Box<[(R, D, S)]>,
+    control: StepControl<R>,
+    clock: Clock<R>,
+    stats: Stats<R>,
+    error: Option<StepError<R>>,
+}
+
+//A snapshot of one of the step iterators, from which it can be resumed with `resume()`
+//
+//It holds everything but the method and the functions: the state buffer (so the step size
+//and any history of an adaptive method too), the time step or tolerance, the stopping and
+//sampling times, the step limits and the statistics so far. Resuming from it continues with
+//exactly the same steps as if the iteration had never been interrupted, and with the `serde`
+//feature it can be saved to and loaded from disk in between.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint<R, B> {
+    buffer: Box<[B]>,
+    step: R,
+    control: StepControl<R>,
+    clock: Clock<R>,
+    stats: Stats<R>,
+}
+
+impl<R: Real, B> Checkpoint<R, B> {
+    //the time after the last step
+    pub fn time(&self) -> R {
+        self.clock.t.clone()
+    }
+
+    //the work done up to the checkpoint
+    pub fn stats(&self) -> &Stats<R> {
+        &self.stats
+    }
+}
+
+macro_rules! impl_clock_adapters {
+    ($($ty:ident<$($param:ident),*>),*) => {$(
+        impl<$($param),*> $ty<$($param),*> where R: Real {
+            //ends the iteration at exactly the given time, shortening the last step to land on it
+            pub fn until(mut self, t_end: R) -> Self {
+                self.clock.end = Some(t_end);
+                self
+            }
+
+            //only yields the solution every `interval` from the initial time, shortening the
+            //steps as needed to land on each of those times exactly
+            pub fn sample(mut self, interval: R) -> Self {
+                self.clock.interval = Some(interval);
+                self
+            }
+
+            //the time after the last step
+            pub fn time(&self) -> R {
+                self.clock.t.clone()
+            }
+        }
+    )*};
+}
+
+impl_clock_adapters!(
+    Steps<I, R, D, S, F>,
+    VelSteps<I, R, D, S, V, F>,
+    AdaptiveSteps<I, R, D, S, M, F>
+);
+
+impl<I: Integrator, R: Real, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>>
+    Steps<I, R, D, S, F>
+{
+    pub fn new(method: I, t0: R, y0: S, dt: R, force: F) -> Self {
+        let force = Counted::new(force);
+        let buffer = method.init(y0, dt.clone(), RhsRef(&force));
+        Steps {
+            method,
+            force,
+            buffer,
+            dt,
+            clock: Clock::new(t0),
+            stats: Stats::new(),
+        }
+    }
+
+    //continues an iteration from a checkpoint, with the same method and right-hand side
+    pub fn resume(method: I, checkpoint: Checkpoint<R, (D, S)>, force: F) -> Self {
+        Steps {
+            method,
+            force: Counted::new(force),
+            buffer: checkpoint.buffer,
+            dt: checkpoint.step,
+            clock: checkpoint.clock,
+            stats: checkpoint.stats,
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint<R, (D, S)> {
+        Checkpoint {
+            buffer: self.buffer.clone(),
+            step: self.dt.clone(),
+            control: Default::default(),
+            clock: self.clock.clone(),
+            stats: self.stats(),
+        }
+    }
+
+    //the work done so far, including steps that weren't yielded because of sampling
+    pub fn stats(&self) -> Stats<R> {
+        Stats {
+            evaluations: self.stats.evaluations + self.force.count(),
+            ..self.stats.clone()
+        }
+    }
+}
+
+impl<I: Integrator, R: Real, D: Clone + Default, S: State<R>, F: Rhs<R, D, S>> Iterator
+    for Steps<I, R, D, S, F>
+{
+    type Item = (R, D, S);
+    fn next(&mut self) -> Option<(R, D, S)> {
+        while !self.clock.done {
+            let t = self.clock.t.clone();
+            let (h, landed) = fixed_step_to(&t, &self.dt, self.clock.stop().as_ref());
+            let (d, s) =
+                self.method
+                    .step(t.clone(), &mut self.buffer, h.clone(), RhsRef(&self.force));
+            self.stats.accept(h.clone());
+            if self.clock.advance(t + h, landed) {
+                return Some((self.clock.t.clone(), d, s));
+            }
+        }
+        None
+    }
+}
+
+impl<
+        I: VelIntegrator,
+        R: Real,
+        D: Clone + Default,
+        S: State<R>,
+        V: Rhs<R, D, S>,
+        F: Rhs<R, D, S>,
+    > VelSteps<I, R, D, S, V, F>
+{
+    pub fn new(method: I, t0: R, y0: S, dt: R, vel: V, force: F) -> Self {
+        let (vel, force) = (Counted::new(vel), Counted::new(force));
+        let buffer = method.init_with_vel(y0, dt.clone(), RhsRef(&vel), RhsRef(&force));
+        VelSteps {
+            method,
+            vel,
+            force,
+            buffer,
+            dt,
+            clock: Clock::new(t0),
+            stats: Stats::new(),
+        }
+    }
+
+    //continues an iteration from a checkpoint, with the same method and functions
+    pub fn resume(method: I, checkpoint: Checkpoint<R, (D, S)>, vel: V, force: F) -> Self {
+        VelSteps {
+            method,
+            vel: Counted::new(vel),
+            force: Counted::new(force),
+            buffer: checkpoint.buffer,
+            dt: checkpoint.step,
+            clock: checkpoint.clock,
+            stats: checkpoint.stats,
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint<R, (D, S)> {
+        Checkpoint {
+            buffer: self.buffer.clone(),
+            step: self.dt.clone(),
+            control: Default::default(),
+            clock: self.clock.clone(),
+            stats: self.stats(),
+        }
+    }
+
+    //the work done so far, including steps that weren't yielded because of sampling
+    pub fn stats(&self) -> Stats<R> {
+        Stats {
+            evaluations: self.stats.evaluations + self.force.count(),
+            vel_evaluations: self.stats.vel_evaluations + self.vel.count(),
+            ..self.stats.clone()
+        }
+    }
+}
+
+impl<
+        I: VelIntegrator,
+        R: Real,
+        D: Clone + Default,
+        S: State<R>,
+        V: Rhs<R, D, S>,
+        F: Rhs<R, D, S>,
+    > Iterator for VelSteps<I, R, D, S, V, F>
+{
+    type Item = (R, D, S);
+    fn next(&mut self) -> Option<(R, D, S)> {
+        while !self.clock.done {
+            let t = self.clock.t.clone();
+            let (h, landed) = fixed_step_to(&t, &self.dt, self.clock.stop().as_ref());
+            let (d, s) = self.method.step_with_vel(
+                t.clone(),
+                &mut self.buffer,
+                h.clone(),
+                RhsRef(&self.vel),
+                RhsRef(&self.force),
+            );
+            self.stats.accept(h.clone());
+            if self.clock.advance(t + h, landed) {
+                return Some((self.clock.t.clone(), d, s));
+            }
+        }
+        None
+    }
+}
+
+impl<
+        I: AdaptiveIntegrator,
+        R: Real,
+        D: Clone + Default,
+        S: State<R>,
+        M: Metric<S, R> + Clone,
+        F: Rhs<R, D, S>,
+    > AdaptiveSteps<I, R, D, S, M, F>
+{
+    pub fn new(method: I, t0: R, y0: S, ds: R, force: F, d: M) -> Self {
+        let force = Counted::new(force);
+        let buffer = method.adaptive_init(t0.clone(), y0, ds.clone(), RhsRef(&force), d.clone());
+        AdaptiveSteps {
+            method,
+            force,
+            d,
+            ds,
+            buffer,
+            control: Default::default(),
+            clock: Clock::new(t0),
+            stats: Stats::new(),
+            error: None,
+        }
+    }
+
+    //the limits for each step
+    pub fn control(mut self, control: StepControl<R>) -> Self {
+        self.control = control;
+        self
+    }
+
+    //the error that ended the iteration, if any
+    pub fn error(&self) -> Option<&StepError<R>> {
+        self.error.as_ref()
+    }
+
+    //continues an iteration from a checkpoint, with the same method, right-hand side and metric
+    pub fn resume(method: I, checkpoint: Checkpoint<R, (R, D, S)>, force: F, d: M) -> Self {
+        AdaptiveSteps {
+            method,
+            force: Counted::new(force),
+            d,
+            ds: checkpoint.step,
+            buffer: checkpoint.buffer,
+            control: checkpoint.control,
+            clock: checkpoint.clock,
+            stats: checkpoint.stats,
+            error: None,
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint<R, (R, D, S)> {
+        Checkpoint {
+            buffer: self.buffer.clone(),
+            step: self.ds.clone(),
+            control: self.control.clone(),
+            clock: self.clock.clone(),
+            stats: self.stats(),
+        }
+    }
+
+    //the work done so far, including steps that weren't yielded because of sampling
+    pub fn stats(&self) -> Stats<R> {
+        Stats {
+            evaluations: self.stats.evaluations + self.force.count(),
+            ..self.stats.clone()
+        }
+    }
+}
+
+impl<
+        I: AdaptiveIntegrator,
+        R: Real,
+        D: Clone + Default,
+        S: State<R>,
+        M: Metric<S, R> + Clone,
+        F: Rhs<R, D, S>,
+    > Iterator for AdaptiveSteps<I, R, D, S, M, F>
+{
+    type Item = (R, D, S);
+    fn next(&mut self) -> Option<(R, D, S)> {
+        while !self.clock.done {
+            let step = adaptive_step_to(
+                &self.method,
+                &mut self.buffer,
+                self.clock.stop().as_ref(),
+                self.ds.clone(),
+                RhsRef(&self.force),
+                self.d.clone(),
+                &self.control,
+                &mut self.stats,
+            );
+            match step {
+                Ok((t, d, s, landed)) => {
+                    if self.clock.advance(t, landed) {
+                        return Some((self.clock.t.clone(), d, s));
+                    }
+                }
+                Err(e) => {
+                    self.error = Some(e);
+                    self.clock.done = true;
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use maths_traits::analysis::metric::InnerProductMetric;
+
+    //runs an iterator to the end, and again with a checkpoint saved to JSON and loaded back
+    //halfway through, expecting exactly the same steps and statistics from both
+    #[cfg(feature = "serde")]
+    macro_rules! check_resume {
+        ($iter:expr, |$checkpoint:ident| $resume:expr) => {{
+            let mut whole = $iter;
+            let expected: Vec<_> = whole.by_ref().collect();
+
+            let mut first = $iter;
+            let mut steps: Vec<_> = first.by_ref().take(expected.len() / 2).collect();
+            let saved = serde_json::to_string(&first.checkpoint()).unwrap();
+            let $checkpoint = serde_json::from_str(&saved).unwrap();
+            let mut rest = $resume;
+            steps.extend(rest.by_ref());
+
+            assert!(expected.len() > 2);
+            assert_eq!(expected, steps);
+            assert_eq!(whole.stats(), rest.stats());
+        }};
+    }
+
+    fn oscillator(_t: f64, y: SVector<f64, 2>) -> ((), SVector<f64, 2>) {
+        ((), SVector([y[1], -y[0]]))
+    }
+
+    fn position(_t: f64, y: f64) -> ((), f64) {
+        ((), -y)
+    }
+
+    fn velocity(_t: f64, y: f64) -> ((), f64) {
+        ((), y)
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn resumed_steps_are_identical() {
+        //the higher order method of Bogacki-Shampine on its own, whose last stage is its first
+        //for the next step
+        let rows: [&[f64]; 5] = [
+            &[0.0, 0.0, 0.0, 0.0, 0.0],
+            &[0.5, 0.5, 0.0, 0.0, 0.0],
+            &[0.75, 0.0, 0.75, 0.0, 0.0],
+            &[1.0, 2.0 / 9.0, 1.0 / 3.0, 4.0 / 9.0, 0.0],
+            &[0.0, 2.0 / 9.0, 1.0 / 3.0, 4.0 / 9.0, 0.0],
+        ];
+        let fsal = RungeKutta::from_matrix(&rows).unwrap();
+        assert!(fsal.is_fsal() && !RK4.is_fsal());
+
+        let y0 = SVector([1.0, 0.0]);
+        check_resume!(
+            RK4.iter(0.0, y0, 0.03, oscillator).until(2.0),
+            |checkpoint| Steps::resume(RK4, checkpoint, oscillator)
+        );
+        check_resume!(
+            fsal.iter(0.0, y0, 0.03, oscillator).until(2.0).sample(0.25),
+            |checkpoint| Steps::resume(fsal, checkpoint, oscillator)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn resumed_steps_with_velocity_are_identical() {
+        check_resume!(
+            RK4.iter_with_vel(0.0, 1.0, 0.03, velocity, position)
+                .until(2.0),
+            |checkpoint| VelSteps::resume(RK4, checkpoint, velocity, position)
+        );
+        check_resume!(
+            VelocityVerlet
+                .iter_with_vel(0.0, 1.0, 0.03, velocity, position)
+                .until(2.0)
+                .sample(0.25),
+            |checkpoint| VelSteps::resume(VelocityVerlet, checkpoint, velocity, position)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn resumed_adaptive_steps_are_identical() {
+        let y0 = SVector([1.0, 0.0]);
+        for method in [RK_FELBERG, DORMAND_PRINCE] {
+            assert_eq!(method.is_fsal(), method == DORMAND_PRINCE);
+            check_resume!(
+                method
+                    .adaptive_iter(0.0, y0, 1e-8, oscillator, InnerProductMetric)
+                    .until(2.0),
+                |checkpoint| AdaptiveSteps::resume(
+                    method,
+                    checkpoint,
+                    oscillator,
+                    InnerProductMetric
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn steps_land_exactly_on_the_end() {
+        //three steps of 0.1 add up to just over 0.3, so the last one is stretched to land on it
+        let times: Vec<f64> = RK4
+            .iter(0.0, 1.0, 0.1, |_t: f64, y: f64| ((), y))
+            .until(0.3)
+            .map(|(t, _, _)| t)
+            .collect();
+        assert_eq!(times, [0.1, 0.2, 0.3]);
+
+        //and a step that would pass the end is shortened, while the others just add up
+        let mut steps = RK4
+            .iter(0.0, 1.0, 0.3, |_t: f64, y: f64| ((), y))
+            .until(1.0);
+        let times: Vec<f64> = steps.by_ref().map(|(t, _, _)| t).collect();
+        assert_eq!(times, [0.3, 0.6, 0.8999999999999999, 1.0]);
+        assert_eq!(steps.next(), None);
+        assert_eq!(steps.stats().accepted, 4);
+
+        let mut steps = VelocityVerlet
+            .iter_with_vel(0.0, 1.0, 0.3, velocity, position)
+            .until(1.0);
+        assert_eq!(steps.by_ref().last().unwrap().0, 1.0);
+        assert_eq!(steps.next(), None);
+    }
+
+    #[test]
+    fn samples_land_exactly_on_multiples_of_the_interval() {
+        let t0 = 0.1;
+        let samples: Vec<(f64, (), SVector<f64, 2>)> = RK4
+            .iter(t0, SVector([1.0, 0.0]), 0.03, oscillator)
+            .sample(0.1)
+            .take(50)
+            .collect();
+        for (k, (t, _, y)) in samples.iter().enumerate() {
+            //measured from t0 rather than added up, so that rounding doesn't build up
+            assert_eq!(*t, t0 + 0.1 * (k + 1) as f64);
+            assert!((y[0] - (t - t0).cos()).abs() < 1e-6, "{t} {y:?}");
+        }
+
+        //the end is yielded even if it isn't a sample
+        let times: Vec<f64> = RK4
+            .iter(0.0, SVector([1.0, 0.0]), 0.03, oscillator)
+            .until(0.6)
+            .sample(0.25)
+            .map(|(t, _, _)| t)
+            .collect();
+        assert_eq!(times, [0.25, 0.5, 0.6]);
+    }
+
+    #[test]
+    fn adaptive_steps_land_exactly_on_samples_and_the_end() {
+        let mut steps = DORMAND_PRINCE
+            .adaptive_iter(
+                0.0,
+                SVector([1.0, 0.0]),
+                1e-10,
+                oscillator,
+                InnerProductMetric,
+            )
+            .until(2.0)
+            .sample(0.5);
+        let samples: Vec<(f64, (), SVector<f64, 2>)> = steps.by_ref().collect();
+        let times: Vec<f64> = samples.iter().map(|(t, _, _)| *t).collect();
+        assert_eq!(times, [0.5, 1.0, 1.5, 2.0]);
+        for (t, _, y) in samples {
+            assert!((y[0] - t.cos()).abs() < 1e-8, "{t} {y:?}");
+        }
+        assert!(steps.error().is_none());
+        assert!(steps.stats().accepted > 4);
+    }
+}
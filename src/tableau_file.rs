@@ -0,0 +1,918 @@
+use super::*;
+
+use core::fmt::{Debug, Display, Formatter, Write};
+use core::iter::Peekable;
+use core::str::{Chars, FromStr};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ParseError {
+    UnexpectedChar(char),
+    UnexpectedEnd,
+    UnterminatedString,
+    InvalidNumber,
+    ZeroDenominator,
+    UnknownKey,
+    DuplicateKey,
+    MissingKey(&'static str),
+    ExpectedNumber,
+    ExpectedInteger,
+    ExpectedString,
+    ExpectedArray,
+    WrongLength(usize, usize),
+    ImplicitCoefficient(usize, usize),
+    RowSum(usize, f64),
+    WeightSum(&'static str, f64),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        match self {
+            ParseError::UnexpectedChar(c) => write!(f, "Unexpected character {:?}", c),
+            ParseError::UnexpectedEnd => write!(f, "Unexpected end of input"),
+            ParseError::UnterminatedString => write!(f, "Unterminated string"),
+            ParseError::InvalidNumber => write!(f, "Invalid number"),
+            ParseError::ZeroDenominator => write!(f, "Rational coefficient has a zero denominator"),
+            ParseError::UnknownKey => write!(f, "Unknown key"),
+            ParseError::DuplicateKey => write!(f, "Duplicate key"),
+            ParseError::MissingKey(k) => write!(f, "Missing key `{}`", k),
+            ParseError::ExpectedNumber => write!(f, "Expected a number"),
+            ParseError::ExpectedInteger => write!(f, "Expected a non-negative integer"),
+            ParseError::ExpectedString => write!(f, "Expected a string"),
+            ParseError::ExpectedArray => write!(f, "Expected an array"),
+            ParseError::WrongLength(e, l) => {
+                write!(f, "Expected an array of length {} but found length {}", e, l)
+            }
+            ParseError::ImplicitCoefficient(i, j) => write!(
+                f,
+                "Non-zero coefficient at a[{}][{}] on or above the diagonal; implicit Runge-Kutta not supported",
+                i, j
+            ),
+            ParseError::RowSum(i, tol) => write!(
+                f,
+                "Row {} of `a` does not sum to c[{}] (tolerance {:e})",
+                i, i, tol
+            ),
+            ParseError::WeightSum(key, tol) => {
+                write!(f, "`{}` does not sum to 1 (tolerance {:e})", key, tol)
+            }
+        }
+    }
+}
+
+impl Debug for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+//a coefficient as written in a tableau file
+//
+//fractions ("p/q") and integers are kept exact and written back the same way, while decimals
+//are kept as floats
+#[derive(Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(untagged)
+)]
+pub enum FileCoefficient {
+    Exact(Rational),
+    Float(f64),
+}
+
+impl FileCoefficient {
+    pub fn from_coefficient<C: Coefficient>(x: C) -> Self {
+        match x.to_rational() {
+            Some(x) => FileCoefficient::Exact(x),
+            None => FileCoefficient::Float(x.to_f64()),
+        }
+    }
+
+    //the sum of the given coefficients, which is exact if they all are and it doesn't overflow
+    fn sum(xs: &[Self]) -> Self {
+        xs.iter()
+            .try_fold(Rational::ZERO, |sum, x| sum.checked_add(x.to_rational()?))
+            .map_or_else(
+                || FileCoefficient::Float(xs.iter().map(|x| x.to_f64()).sum()),
+                FileCoefficient::Exact,
+            )
+    }
+}
+
+impl Default for FileCoefficient {
+    fn default() -> Self {
+        FileCoefficient::Exact(Rational::ZERO)
+    }
+}
+
+//exact coefficients are compared exactly, and any others by value
+impl PartialEq for FileCoefficient {
+    fn eq(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (FileCoefficient::Exact(x), FileCoefficient::Exact(y)) => x == y,
+            _ => self.to_f64() == rhs.to_f64(),
+        }
+    }
+}
+
+impl Display for FileCoefficient {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        match self {
+            FileCoefficient::Exact(x) => write!(f, "{}", x),
+            FileCoefficient::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+impl Debug for FileCoefficient {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl From<Rational> for FileCoefficient {
+    fn from(x: Rational) -> Self {
+        FileCoefficient::Exact(x)
+    }
+}
+
+impl From<f64> for FileCoefficient {
+    fn from(x: f64) -> Self {
+        FileCoefficient::Float(x)
+    }
+}
+
+impl From<FileCoefficient> for f64 {
+    fn from(x: FileCoefficient) -> f64 {
+        x.to_f64()
+    }
+}
+
+impl Coefficient for FileCoefficient {
+    fn to_f64(self) -> f64 {
+        match self {
+            FileCoefficient::Exact(x) => x.to_f64(),
+            FileCoefficient::Float(x) => x,
+        }
+    }
+    fn to_real<R: Real>(self) -> R {
+        match self {
+            FileCoefficient::Exact(x) => x.to_real(),
+            FileCoefficient::Float(x) => R::repr(x),
+        }
+    }
+    fn to_rational(self) -> Option<Rational> {
+        match self {
+            FileCoefficient::Exact(x) => Some(x),
+            FileCoefficient::Float(_) => None,
+        }
+    }
+    fn from_rational(x: Rational) -> Self {
+        FileCoefficient::Exact(x)
+    }
+    fn is_zero(self) -> bool {
+        match self {
+            FileCoefficient::Exact(x) => x.is_zero(),
+            FileCoefficient::Float(x) => x == 0.0,
+        }
+    }
+}
+
+//the contents of a tableau file
+//
+//`dense` holds the dense-output coefficients: `dense[i][k]` is the coefficient of θ^(k+1) in
+//b_i(θ), with as many coefficients for each stage as the degree of the interpolant.
+//`position` is where the table starts in the file it was read from, if any.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TableauFile {
+    pub name: Option<String>,
+    pub order: Option<usize>,
+    pub embedded_order: Option<usize>,
+    pub c: Vec<FileCoefficient>,
+    pub a: Vec<Vec<FileCoefficient>>,
+    pub b: Vec<FileCoefficient>,
+    pub b_hat: Option<Vec<FileCoefficient>>,
+    pub dense: Option<Vec<Vec<FileCoefficient>>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub position: Position,
+}
+
+enum Value {
+    Str(String),
+    Num(String),
+    Array(Vec<(Position, Value)>),
+}
+
+type Entries = Vec<(Position, String, (Position, Value))>;
+
+fn err<T>(pos: Position, e: ParseError) -> Result<T, RKError> {
+    Err(RKError::Parse(pos, e))
+}
+
+struct Parser<'s> {
+    chars: Peekable<Chars<'s>>,
+    pos: Position,
+}
+
+impl<'s> Parser<'s> {
+    fn new(src: &'s str) -> Self {
+        Parser {
+            chars: src.chars().peekable(),
+            pos: Position { line: 1, column: 1 },
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        match c {
+            Some('\n') => {
+                self.pos.line += 1;
+                self.pos.column = 1;
+            }
+            Some(_) => self.pos.column += 1,
+            None => (),
+        }
+        c
+    }
+
+    //skips whitespace and comments, but stops at newlines unless told otherwise
+    fn skip(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                '#' => {
+                    while !matches!(self.peek(), Some('\n') | None) {
+                        self.bump();
+                    }
+                }
+                '\n' if !newlines => return,
+                c if c.is_whitespace() => {
+                    self.bump();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), RKError> {
+        let pos = self.pos;
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => err(pos, ParseError::UnexpectedChar(c)),
+            None => err(pos, ParseError::UnexpectedEnd),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, RKError> {
+        let start = self.pos;
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some(c @ ('"' | '\\' | '/')) => s.push(c),
+                    Some(u @ ('u' | 'U')) => {
+                        let pos = self.pos;
+                        let digits = if u == 'u' { 4 } else { 8 };
+                        let mut code = 0;
+                        for _ in 0..digits {
+                            match self.bump().and_then(|c| c.to_digit(16)) {
+                                Some(d) => code = code * 16 + d,
+                                None => return err(pos, ParseError::UnexpectedChar(u)),
+                            }
+                        }
+                        match char::from_u32(code) {
+                            Some(c) => s.push(c),
+                            None => return err(pos, ParseError::UnexpectedChar(u)),
+                        }
+                    }
+                    Some(c) => return err(self.pos, ParseError::UnexpectedChar(c)),
+                    None => return err(start, ParseError::UnterminatedString),
+                },
+                Some('\n') | None => return err(start, ParseError::UnterminatedString),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn bare(&mut self) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || "+-._/".contains(c) {
+                s.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        s
+    }
+
+    fn value(&mut self) -> Result<(Position, Value), RKError> {
+        self.skip(true);
+        let pos = self.pos;
+        match self.peek() {
+            Some('"') => Ok((pos, Value::Str(self.string()?))),
+            Some('[') => {
+                self.bump();
+                let mut items = Vec::new();
+                loop {
+                    self.skip(true);
+                    if self.peek() == Some(']') {
+                        self.bump();
+                        return Ok((pos, Value::Array(items)));
+                    }
+                    items.push(self.value()?);
+                    self.skip(true);
+                    match self.peek() {
+                        Some(',') => {
+                            self.bump();
+                        }
+                        Some(']') => (),
+                        Some(c) => return err(self.pos, ParseError::UnexpectedChar(c)),
+                        None => return err(self.pos, ParseError::UnexpectedEnd),
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_digit() || "+-.".contains(c) => {
+                Ok((pos, Value::Num(self.bare())))
+            }
+            Some(c) => err(pos, ParseError::UnexpectedChar(c)),
+            None => err(pos, ParseError::UnexpectedEnd),
+        }
+    }
+
+    //reads `key = value` lines, or a single JSON object of `"key": value` pairs, along with
+    //where they start
+    fn entries(&mut self) -> Result<(Position, Entries), RKError> {
+        let mut entries = Vec::new();
+        self.skip(true);
+        let start = self.pos;
+        if self.peek() == Some('{') {
+            self.bump();
+            self.skip(true);
+            if self.peek() == Some('}') {
+                self.bump();
+            } else {
+                loop {
+                    self.skip(true);
+                    let pos = self.pos;
+                    let key = self.string()?;
+                    self.skip(true);
+                    self.expect(':')?;
+                    entries.push((pos, key, self.value()?));
+                    self.skip(true);
+                    let pos = self.pos;
+                    match self.bump() {
+                        Some(',') => (),
+                        Some('}') => break,
+                        Some(c) => return err(pos, ParseError::UnexpectedChar(c)),
+                        None => return err(pos, ParseError::UnexpectedEnd),
+                    }
+                }
+            }
+        } else {
+            while self.peek().is_some() {
+                let pos = self.pos;
+                let key = match self.peek() {
+                    Some('"') => self.string()?,
+                    _ => self.bare(),
+                };
+                if key.is_empty() {
+                    return err(pos, ParseError::UnexpectedChar(self.peek().unwrap()));
+                }
+                self.skip(false);
+                self.expect('=')?;
+                entries.push((pos, key, self.value()?));
+                self.skip(false);
+                let pos = self.pos;
+                match self.bump() {
+                    Some('\n') | None => (),
+                    Some(c) => return err(pos, ParseError::UnexpectedChar(c)),
+                }
+                self.skip(true);
+            }
+        }
+
+        self.skip(true);
+        match self.peek() {
+            Some(c) => err(self.pos, ParseError::UnexpectedChar(c)),
+            None => Ok((start, entries)),
+        }
+    }
+}
+
+//parses a coefficient, exactly if it is a fraction "p/q" or an integer and as a float otherwise
+fn coefficient(pos: Position, text: &str) -> Result<FileCoefficient, RKError> {
+    let text = text.trim();
+    let exact = |text: &str| Rational::from_str(text).or(err(pos, ParseError::InvalidNumber));
+    let integer = |text: &str| {
+        let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+        !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+    };
+
+    match text.split_once('/') {
+        Some((p, q)) => {
+            let (p, q) = (exact(p)?, exact(q)?);
+            if q.is_zero() {
+                err(pos, ParseError::ZeroDenominator)
+            } else {
+                p.checked_div(q)
+                    .map(FileCoefficient::Exact)
+                    .ok_or(RKError::Parse(pos, ParseError::InvalidNumber))
+            }
+        }
+        None if integer(text) => exact(text).map(FileCoefficient::Exact),
+        None => match f64::from_str(text) {
+            Ok(x) if x.is_finite() => Ok(FileCoefficient::Float(x)),
+            _ => err(pos, ParseError::InvalidNumber),
+        },
+    }
+}
+
+fn number((pos, v): (Position, Value)) -> Result<FileCoefficient, RKError> {
+    match v {
+        Value::Num(s) | Value::Str(s) => coefficient(pos, &s),
+        Value::Array(_) => err(pos, ParseError::ExpectedNumber),
+    }
+}
+
+fn integer((pos, v): (Position, Value)) -> Result<usize, RKError> {
+    match v {
+        Value::Num(s) => usize::from_str(&s).or(err(pos, ParseError::ExpectedInteger)),
+        _ => err(pos, ParseError::ExpectedInteger),
+    }
+}
+
+fn string((pos, v): (Position, Value)) -> Result<String, RKError> {
+    match v {
+        Value::Str(s) => Ok(s),
+        _ => err(pos, ParseError::ExpectedString),
+    }
+}
+
+fn array(
+    (pos, v): (Position, Value),
+    len: Option<usize>,
+) -> Result<(Position, Vec<(Position, Value)>), RKError> {
+    match v {
+        Value::Array(items) => match len {
+            Some(l) if l != items.len() => err(pos, ParseError::WrongLength(l, items.len())),
+            _ => Ok((pos, items)),
+        },
+        _ => err(pos, ParseError::ExpectedArray),
+    }
+}
+
+fn vector(v: (Position, Value), len: Option<usize>) -> Result<Vec<FileCoefficient>, RKError> {
+    array(v, len)?.1.into_iter().map(number).collect()
+}
+
+//reads a matrix with the given number of rows and columns, or with every row as long as the
+//first if the number of columns isn't given
+fn matrix(
+    v: (Position, Value),
+    rows: Option<usize>,
+    columns: Option<usize>,
+) -> Result<Vec<Vec<FileCoefficient>>, RKError> {
+    let (_, items) = array(v, rows)?;
+    let mut columns = columns;
+    items
+        .into_iter()
+        .map(|row| {
+            let row = vector(row, columns)?;
+            columns = Some(row.len());
+            Ok(row)
+        })
+        .collect()
+}
+
+impl TableauFile {
+    pub fn parse(src: &str) -> Result<Self, RKError> {
+        const KEYS: [&str; 8] = [
+            "name",
+            "order",
+            "embedded_order",
+            "c",
+            "a",
+            "b",
+            "b_hat",
+            "dense",
+        ];
+
+        let mut parser = Parser::new(src);
+
+        //collect the values by key first so that the shape of A is known before checking the rest
+        let mut values: [Option<(Position, Value)>; 8] = Default::default();
+        let (start, entries) = parser.entries()?;
+        for (pos, key, value) in entries {
+            match KEYS.iter().position(|k| *k == key) {
+                Some(i) if values[i].is_some() => return err(pos, ParseError::DuplicateKey),
+                Some(i) => values[i] = Some(value),
+                None => return err(pos, ParseError::UnknownKey),
+            }
+        }
+        let [name, order, embedded_order, c, a, b, b_hat, dense] = values;
+
+        //A must be a square, strictly lower-triangular matrix
+        let a = a.ok_or(RKError::Parse(start, ParseError::MissingKey("a")))?;
+        let stages = match &a.1 {
+            Value::Array(rows) => rows.len(),
+            _ => return err(a.0, ParseError::ExpectedArray),
+        };
+        if stages == 0 {
+            return Err(RKError::EmptyTableau);
+        }
+        let positions: Vec<Vec<Position>> = match &a.1 {
+            Value::Array(rows) => rows
+                .iter()
+                .map(|(p, r)| match r {
+                    Value::Array(items) => items.iter().map(|(p, _)| *p).collect(),
+                    _ => vec![*p],
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let a = matrix(a, Some(stages), Some(stages))?;
+        for (i, row) in a.iter().enumerate() {
+            for j in i..stages {
+                if !row[j].is_zero() {
+                    return err(positions[i][j], ParseError::ImplicitCoefficient(i, j));
+                }
+            }
+        }
+
+        let b = b.ok_or(RKError::Parse(start, ParseError::MissingKey("b")))?;
+        Ok(TableauFile {
+            name: name.map(string).transpose()?,
+            order: order.map(integer).transpose()?,
+            embedded_order: embedded_order.map(integer).transpose()?,
+            //if c is left out, use the row-sums of A
+            c: match c {
+                Some(c) => vector(c, Some(stages))?,
+                None => a.iter().map(|row| FileCoefficient::sum(row)).collect(),
+            },
+            b: vector(b, Some(stages))?,
+            b_hat: b_hat.map(|v| vector(v, Some(stages))).transpose()?,
+            dense: dense.map(|v| matrix(v, Some(stages), None)).transpose()?,
+            a,
+            position: start,
+        })
+    }
+
+    //the coefficients of the given tableau, which are written exactly if they are exact
+    pub fn from_tableau<T: Tableau + ?Sized>(tableau: &T) -> Self {
+        let s = tableau.stages();
+        let coefficient = FileCoefficient::from_coefficient;
+        let b_hat: Option<Vec<_>> = (0..s).map(|j| tableau.b_hat(j).map(coefficient)).collect();
+        TableauFile {
+            c: (0..s).map(|i| coefficient(tableau.c(i))).collect(),
+            a: (0..s)
+                .map(|i| (0..s).map(|j| coefficient(tableau.a(i, j))).collect())
+                .collect(),
+            b: (0..s).map(|j| coefficient(tableau.b(j))).collect(),
+            b_hat,
+            ..Default::default()
+        }
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    //the consistency errors of a tableau, reported at the table it was read from
+    fn at_table(&self, e: RKError) -> RKError {
+        match e {
+            RKError::RowSum(i, tol) => RKError::Parse(self.position, ParseError::RowSum(i, tol)),
+            RKError::WeightSum(i, tol) => {
+                let key = if i == self.a.len() { "b" } else { "b_hat" };
+                RKError::Parse(self.position, ParseError::WeightSum(key, tol))
+            }
+            e => e,
+        }
+    }
+
+    //the tableau with its coefficients as written, so that fractions stay exact
+    pub fn exact_tableau(&self) -> Result<OwnedRungeKutta<FileCoefficient>, RKError> {
+        OwnedRungeKutta::new(self.c.clone(), self.a.clone(), self.b.clone())
+            .map_err(|e| self.at_table(e))
+    }
+
+    pub fn exact_adaptive_tableau(
+        &self,
+    ) -> Result<OwnedAdaptiveRungeKutta<FileCoefficient>, RKError> {
+        match &self.b_hat {
+            Some(b_hat) => OwnedAdaptiveRungeKutta::new(
+                self.c.clone(),
+                self.a.clone(),
+                self.b.clone(),
+                b_hat.clone(),
+            )
+            .map_err(|e| self.at_table(e)),
+            None => Err(RKError::Parse(
+                self.position,
+                ParseError::MissingKey("b_hat"),
+            )),
+        }
+    }
+
+    pub fn tableau(&self) -> Result<OwnedRungeKutta, RKError> {
+        self.exact_tableau()?;
+        OwnedRungeKutta::new(
+            floats(&self.c),
+            self.a.iter().map(|r| floats(r)).collect(),
+            floats(&self.b),
+        )
+        .map_err(|e| self.at_table(e))
+    }
+
+    pub fn adaptive_tableau(&self) -> Result<OwnedAdaptiveRungeKutta, RKError> {
+        let b_hat = floats(self.exact_adaptive_tableau()?.b_hat_vec());
+        OwnedAdaptiveRungeKutta::new(
+            floats(&self.c),
+            self.a.iter().map(|r| floats(r)).collect(),
+            floats(&self.b),
+            b_hat,
+        )
+        .map_err(|e| self.at_table(e))
+    }
+
+    //a registry entry for the method using the name given in the file, checking the orders
+    //(if given) against the actual orders of the tableau, exactly if its coefficients are
+    pub fn entry(&self) -> Result<Entry, RKError> {
+        let name = self.name.as_deref().ok_or(RKError::Parse(
+            self.position,
+            ParseError::MissingKey("name"),
+        ))?;
+        let (method, order, embedded_order) = match self.b_hat {
+            Some(_) => {
+                let exact = self.exact_adaptive_tableau()?;
+                let method = Method::OwnedAdaptiveRungeKutta(self.adaptive_tableau()?);
+                (method, exact.order(), Some(exact.embedded_order()))
+            }
+            None => {
+                let exact = self.exact_tableau()?;
+                (
+                    Method::OwnedRungeKutta(self.tableau()?),
+                    exact.order(),
+                    None,
+                )
+            }
+        };
+        if let Some(claimed) = self.order {
+            if claimed != order {
+                return Err(RKError::OrderMismatch(claimed, order));
+            }
+        }
+        if let (Some(claimed), Some(actual)) = (self.embedded_order, embedded_order) {
+            if claimed != actual {
+                return Err(RKError::OrderMismatch(claimed, actual));
+            }
+        }
+
+        let mut entry = Entry::new(name, method);
+        entry.info.order = order;
+        entry.info.embedded_order = embedded_order;
+        Ok(entry)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, false).unwrap();
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, true).unwrap();
+        out
+    }
+
+    fn write<W: Write>(&self, out: &mut W, json: bool) -> ::core::fmt::Result {
+        //fractions are written as strings so that the output is still valid TOML or JSON
+        fn vec_str(v: &[FileCoefficient]) -> String {
+            let items: Vec<String> = v
+                .iter()
+                .map(|x| match x {
+                    FileCoefficient::Exact(q) if q.denom() != 1 => format!("\"{}\"", q),
+                    x => x.to_string(),
+                })
+                .collect();
+            format!("[{}]", items.join(", "))
+        }
+        fn mat_str(m: &[Vec<FileCoefficient>], indent: &str) -> String {
+            let rows: Vec<String> = m
+                .iter()
+                .map(|row| format!("{}    {}", indent, vec_str(row)))
+                .collect();
+            format!("[\n{}\n{}]", rows.join(",\n"), indent)
+        }
+
+        let mut fields: Vec<(&str, String)> = Vec::new();
+        if let Some(name) = &self.name {
+            fields.push(("name", quoted(name)));
+        }
+        if let Some(order) = self.order {
+            fields.push(("order", order.to_string()));
+        }
+        if let Some(order) = self.embedded_order {
+            fields.push(("embedded_order", order.to_string()));
+        }
+        let indent = if json { "    " } else { "" };
+        fields.push(("c", vec_str(&self.c)));
+        fields.push(("a", mat_str(&self.a, indent)));
+        fields.push(("b", vec_str(&self.b)));
+        if let Some(b_hat) = &self.b_hat {
+            fields.push(("b_hat", vec_str(b_hat)));
+        }
+        if let Some(dense) = &self.dense {
+            fields.push(("dense", mat_str(dense, indent)));
+        }
+
+        if json {
+            writeln!(out, "{{")?;
+            for (i, (key, value)) in fields.iter().enumerate() {
+                let sep = if i + 1 < fields.len() { "," } else { "" };
+                writeln!(out, "    \"{}\": {}{}", key, value, sep)?;
+            }
+            writeln!(out, "}}")
+        } else {
+            for (key, value) in fields {
+                writeln!(out, "{} = {}", key, value)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+//a string with the escapes that TOML and JSON have in common
+fn quoted(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn floats(v: &[FileCoefficient]) -> Vec<f64> {
+    v.iter().map(|x| x.to_f64()).collect()
+}
+
+impl FromStr for TableauFile {
+    type Err = RKError;
+    fn from_str(s: &str) -> Result<Self, RKError> {
+        TableauFile::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractions_are_kept_exact() {
+        let file = TableauFile::parse("a = [[0, 0], [1/3, 0]]\nb = [\"1/4\", 0.75]").unwrap();
+        assert_eq!(file.a[1][0], FileCoefficient::Exact(Rational::new(1, 3)));
+        assert_eq!(file.c[1], FileCoefficient::Exact(Rational::new(1, 3)));
+        assert!(matches!(file.b[1], FileCoefficient::Float(x) if x == 0.75));
+
+        let toml = file.to_toml();
+        assert!(toml.contains("[\"1/3\", 0]"), "{}", toml);
+        assert!(toml.contains("[\"1/4\", 0.75]"), "{}", toml);
+        assert_eq!(TableauFile::parse(&toml).unwrap(), file);
+    }
+
+    #[test]
+    fn built_in_tableaux_round_trip() {
+        let file = TableauFile::from_tableau(&DORMAND_PRINCE).named("dopri5");
+        for text in [file.to_toml(), file.to_json()] {
+            let mut parsed = TableauFile::parse(&text).unwrap();
+            parsed.position = file.position;
+            assert_eq!(parsed, file);
+
+            let entry = parsed.entry().unwrap();
+            assert_eq!(entry.info.order, 5);
+            assert_eq!(entry.info.embedded_order, Some(4));
+        }
+    }
+
+    #[test]
+    fn dense_rows_take_their_width_from_the_first() {
+        let src = "a = [[0, 0], [1, 0]]\nb = [1/2, 1/2]\ndense = [[1, -1/2, 0, 0], [0, 1/2, 0, 0]]";
+        let file = TableauFile::parse(src).unwrap();
+        assert_eq!(file.dense.as_ref().unwrap()[1].len(), 4);
+
+        let src = "a = [[0, 0], [1, 0]]\nb = [1/2, 1/2]\ndense = [[1, -1/2], [0, 1/2, 0]]";
+        match TableauFile::parse(src) {
+            Err(RKError::Parse(pos, ParseError::WrongLength(2, 3))) => {
+                assert_eq!(
+                    pos,
+                    Position {
+                        line: 3,
+                        column: 21
+                    }
+                )
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn missing_keys_are_reported_at_the_table() {
+        let file = TableauFile::parse("\n\n  {\"a\": [[0]], \"b\": [1]}").unwrap();
+        let table = Position { line: 3, column: 3 };
+        match file.adaptive_tableau() {
+            Err(RKError::Parse(pos, ParseError::MissingKey("b_hat"))) => assert_eq!(pos, table),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        match file.entry() {
+            Err(RKError::Parse(pos, ParseError::MissingKey("name"))) => assert_eq!(pos, table),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        match TableauFile::parse("# comment\nb = [1]") {
+            Err(RKError::Parse(pos, ParseError::MissingKey("a"))) => {
+                assert_eq!(pos, Position { line: 2, column: 1 })
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn names_are_escaped() {
+        let name = "a \"quoted\" back\\slash\ttab\nnew line\r\u{1}\u{8}\u{c}\u{7f} ünïcode";
+        let file = TableauFile::from_tableau(&RK4).named(name);
+        for text in [file.to_toml(), file.to_json()] {
+            assert!(
+                !text.chars().any(|c| c.is_control() && c != '\n'),
+                "{}",
+                text
+            );
+            assert_eq!(
+                TableauFile::parse(&text).unwrap().name.as_deref(),
+                Some(name)
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn escaped_names_are_valid_json() {
+        let name = "\"\\\t\n\r\u{0}\u{1f}\u{7f}";
+        let json = TableauFile::from_tableau(&RK4).named(name).to_json();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["name"], name);
+    }
+
+    #[test]
+    fn inconsistent_tableaux_are_reported_at_the_table() {
+        let file =
+            TableauFile::parse("\n\n{\"a\": [[0, 0], [1/2, 0]], \"c\": [0, 1], \"b\": [1/2, 1/2]}")
+                .unwrap();
+        let table = Position { line: 3, column: 1 };
+        match file.tableau() {
+            Err(RKError::Parse(pos, ParseError::RowSum(1, _))) => assert_eq!(pos, table),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+
+        let file = TableauFile::parse("# comment\na = [[0]]\nb = [1/2]\nb_hat = [0.9]").unwrap();
+        let table = Position { line: 2, column: 1 };
+        match file.exact_tableau() {
+            Err(RKError::Parse(pos, ParseError::WeightSum("b", _))) => assert_eq!(pos, table),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+
+        let file = TableauFile::parse("# comment\na = [[0]]\nb = [1]\nb_hat = [0.9]").unwrap();
+        match file.adaptive_tableau() {
+            Err(RKError::Parse(pos, ParseError::WeightSum(key, _))) => {
+                assert_eq!((pos, key), (table, "b_hat"))
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use super::*;
+
+use core::iter::FromIterator;
+
+//The solution of an integration: the time, auxiliary data, and state at each step
+//
+//If `slopes` is present, it holds the derivative of the state at each point, and the
+//solution is interpolated between points with cubic Hermite polynomials. Otherwise, it
+//is interpolated linearly. `stats` holds the work done to compute it, if it came from a solver.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trajectory<R, D, S> {
+    pub times: Vec<R>,
+    pub data: Vec<D>,
+    pub states: Vec<S>,
+    pub slopes: Option<Vec<S>>,
+    pub stats: Stats<R>,
+}
+
+impl<R, D, S> Default for Trajectory<R, D, S> {
+    fn default() -> Self {
+        Trajectory {
+            times: Vec::new(),
+            data: Vec::new(),
+            states: Vec::new(),
+            slopes: None,
+            stats: Default::default(),
+        }
+    }
+}
+
+impl<R, D, S> Trajectory<R, D, S> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    //adds a point without a slope, which drops any dense output of the trajectory
+    pub fn push(&mut self, t: R, data: D, state: S) {
+        self.times.push(t);
+        self.data.push(data);
+        self.states.push(state);
+        self.slopes = None;
+    }
+
+    //adds a point along with the derivative of the state there
+    //
+    //the slope is only kept if every other point in the trajectory has one too
+    pub fn push_dense(&mut self, t: R, data: D, state: S, slope: S) {
+        let slopes = match self.slopes.take() {
+            Some(slopes) => Some(slopes),
+            None if self.is_empty() => Some(Vec::new()),
+            None => None,
+        };
+        self.push(t, data, state);
+        self.slopes = slopes.map(|mut slopes| {
+            slopes.push(slope);
+            slopes
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.times.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+    pub fn is_dense(&self) -> bool {
+        self.slopes.is_some()
+    }
+
+    pub fn first(&self) -> Option<(&R, &D, &S)> {
+        self.get(0)
+    }
+    pub fn last(&self) -> Option<(&R, &D, &S)> {
+        self.get(self.len().checked_sub(1)?)
+    }
+    pub fn get(&self, i: usize) -> Option<(&R, &D, &S)> {
+        Some((self.times.get(i)?, &self.data[i], &self.states[i]))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&R, &D, &S)> {
+        self.times
+            .iter()
+            .zip(self.data.iter())
+            .zip(self.states.iter())
+            .map(|((t, d), s)| (t, d, s))
+    }
+
+    //a series of some quantity derived from the state at each point, eg a single component
+    pub fn series<T, G: FnMut(&S) -> T>(&self, g: G) -> Vec<T> {
+        self.states.iter().map(g).collect()
+    }
+}
+
+impl<R: Real, D: Clone + Default, S: State<R>> Trajectory<R, D, S> {
+    //the index of the interval `[times[i], times[i+1]]` containing `t`, if any
+    fn interval(&self, t: &R) -> Option<usize> {
+        let (first, last) = (self.times.first()?, self.times.last()?);
+        if t < first || t > last {
+            return None;
+        }
+        let i = self.times.partition_point(|s| s <= t);
+        Some(i.saturating_sub(1).min(self.len().saturating_sub(2)))
+    }
+
+    //the state at any time between the first and last points
+    pub fn at(&self, t: R) -> Option<S> {
+        let i = self.interval(&t)?;
+        if self.len() == 1 || t == self.times[i] {
+            return Some(self.states[i].clone());
+        }
+
+        let h = self.times[i + 1].clone() - self.times[i].clone();
+        let theta = (t - self.times[i].clone()) / h.clone();
+        let (y0, y1) = (self.states[i].clone(), self.states[i + 1].clone());
+
+        match &self.slopes {
+            Some(slopes) => {
+                let theta2 = theta.clone() * theta.clone();
+                let theta3 = theta2.clone() * theta.clone();
+                let (two, three) = (R::repr(2.0), R::repr(3.0));
+
+                let h00 = two.clone() * theta3.clone() - three.clone() * theta2.clone() + R::one();
+                let h10 = theta3.clone() - two.clone() * theta2.clone() + theta;
+                let h01 = three * theta2.clone() - two * theta3.clone();
+                let h11 = theta3 - theta2;
+
+                Some(
+                    y0 * h00
+                        + slopes[i].clone() * (h10 * h.clone())
+                        + y1 * h01
+                        + slopes[i + 1].clone() * (h11 * h),
+                )
+            }
+            None => Some(y0.clone() + (y1 - y0) * theta),
+        }
+    }
+
+    //the trajectory interpolated onto the given times, which have to be within its time span
+    //
+    //since the auxiliary data can't be interpolated, it is only kept at times that match a
+    //point exactly
+    pub fn resample<T: IntoIterator<Item = R>>(&self, times: T) -> Option<Self> {
+        let mut resampled = Trajectory::new();
+        for t in times {
+            let state = self.at(t.clone())?;
+            let data = match self.times.iter().position(|s| *s == t) {
+                Some(i) => self.data[i].clone(),
+                None => Default::default(),
+            };
+            resampled.push(t, data, state);
+        }
+        resampled.stats = self.stats.clone();
+        Some(resampled)
+    }
+
+    //the trajectory interpolated onto `n` evenly spaced times from its first to its last point
+    pub fn uniform(&self, n: usize) -> Self {
+        let (t0, t1) = match (self.times.first(), self.times.last()) {
+            (Some(t0), Some(t1)) if n > 0 => (t0.clone(), t1.clone()),
+            _ => return Trajectory::new(),
+        };
+        let steps = R::repr(n.saturating_sub(1).max(1) as f64);
+        let times = (0..n).map(|i| {
+            if i + 1 == n && n > 1 {
+                t1.clone()
+            } else {
+                t0.clone() + (t1.clone() - t0.clone()) * R::repr(i as f64) / steps.clone()
+            }
+        });
+        self.resample(times).unwrap()
+    }
+
+    //the points with times in `[start, end]`
+    pub fn slice(&self, start: R, end: R) -> Self {
+        let from = self.times.partition_point(|t| *t < start);
+        let to = self.times.partition_point(|t| *t <= end).max(from);
+        Trajectory {
+            times: self.times[from..to].to_vec(),
+            data: self.data[from..to].to_vec(),
+            states: self.states[from..to].to_vec(),
+            slopes: self.slopes.as_ref().map(|s| s[from..to].to_vec()),
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+impl<R, D, S> Extend<(R, D, S)> for Trajectory<R, D, S> {
+    fn extend<T: IntoIterator<Item = (R, D, S)>>(&mut self, iter: T) {
+        for (t, d, s) in iter {
+            self.push(t, d, s);
+        }
+    }
+}
+
+impl<R, D, S> FromIterator<(R, D, S)> for Trajectory<R, D, S> {
+    fn from_iter<T: IntoIterator<Item = (R, D, S)>>(iter: T) -> Self {
+        let mut trajectory = Trajectory::new();
+        trajectory.extend(iter);
+        trajectory
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    //a cubic and its derivative, which cubic Hermite interpolation reproduces exactly
+    fn p(t: f64) -> f64 {
+        t * t * t - 2.0 * t * t + t + 1.0
+    }
+    fn dp(t: f64) -> f64 {
+        3.0 * t * t - 4.0 * t + 1.0
+    }
+
+    //the cubic at unevenly spaced times, numbering the points in the data
+    fn cubic(dense: bool) -> Trajectory<f64, usize, f64> {
+        let mut trajectory = Trajectory::new();
+        for (i, t) in [0.0, 0.5, 1.5, 2.0].into_iter().enumerate() {
+            if dense {
+                trajectory.push_dense(t, i, p(t), dp(t));
+            } else {
+                trajectory.push(t, i, p(t));
+            }
+        }
+        trajectory
+    }
+
+    #[test]
+    fn dense_trajectories_interpolate_cubics_exactly() {
+        let trajectory = cubic(true);
+        assert!(trajectory.is_dense());
+        for i in 0..=40 {
+            let t = i as f64 * 0.05;
+            let y = trajectory.at(t).unwrap();
+            assert!((y - p(t)).abs() < 1e-12, "{t} {y}");
+        }
+        assert_eq!(trajectory.at(-0.1), None);
+        assert_eq!(trajectory.at(2.1), None);
+    }
+
+    #[test]
+    fn other_trajectories_interpolate_linearly() {
+        let trajectory = cubic(false);
+        assert!(!trajectory.is_dense());
+
+        //halfway between the points at 0.5 and 1.5 is the middle of the chord
+        let y = trajectory.at(1.0).unwrap();
+        assert!((y - (p(0.5) + p(1.5)) / 2.0).abs() < 1e-12, "{y}");
+        assert!((y - p(1.0)).abs() > 0.1);
+        assert_eq!(trajectory.at(1.5), Some(p(1.5)));
+
+        //adding a point without a slope drops the dense output
+        let mut trajectory = cubic(true);
+        trajectory.push(2.5, 4, p(2.5));
+        assert!(!trajectory.is_dense());
+        trajectory.push_dense(3.0, 5, p(3.0), dp(3.0));
+        assert!(!trajectory.is_dense());
+
+        let single: Trajectory<f64, (), f64> = [(1.0, (), 2.0)].into_iter().collect();
+        assert_eq!(single.at(1.0), Some(2.0));
+        assert_eq!(single.at(1.5), None);
+    }
+
+    #[test]
+    fn resampling_keeps_data_only_at_existing_points() {
+        let trajectory = cubic(true);
+        let resampled = trajectory.resample([0.25, 0.5, 1.0, 2.0]).unwrap();
+        assert_eq!(resampled.times, [0.25, 0.5, 1.0, 2.0]);
+        assert_eq!(resampled.data, [0, 1, 0, 3]);
+        for (t, y) in resampled.times.iter().zip(resampled.states.iter()) {
+            assert!((y - p(*t)).abs() < 1e-12, "{t} {y}");
+        }
+        assert_eq!(trajectory.resample([1.0, 2.5]), None);
+    }
+
+    #[test]
+    fn uniform_times_span_the_whole_trajectory() {
+        let trajectory = cubic(true);
+        let uniform = trajectory.uniform(5);
+        assert_eq!(uniform.times, [0.0, 0.5, 1.0, 1.5, 2.0]);
+        assert_eq!(uniform.data, [0, 1, 0, 2, 3]);
+        for (t, y) in uniform.times.iter().zip(uniform.states.iter()) {
+            assert!((y - p(*t)).abs() < 1e-12, "{t} {y}");
+        }
+
+        //the last time is the end exactly, even when the spacing doesn't divide evenly
+        let uniform = trajectory.uniform(7);
+        assert_eq!(uniform.times.len(), 7);
+        assert_eq!(*uniform.times.last().unwrap(), 2.0);
+
+        assert_eq!(trajectory.uniform(1).times, [0.0]);
+        assert!(trajectory.uniform(0).is_empty());
+    }
+
+    #[test]
+    fn slices_include_both_ends() {
+        let trajectory = cubic(true);
+        let slice = trajectory.slice(0.5, 1.5);
+        assert_eq!(slice.times, [0.5, 1.5]);
+        assert_eq!(slice.data, [1, 2]);
+        assert_eq!(slice.slopes, Some(vec![dp(0.5), dp(1.5)]));
+
+        //so the slice interpolates just like the whole trajectory between its points
+        assert_eq!(slice.at(1.0), trajectory.at(1.0));
+
+        assert_eq!(trajectory.slice(0.6, 1.4).len(), 0);
+        assert_eq!(trajectory.slice(-1.0, 3.0), trajectory);
+    }
+}
@@ -0,0 +1,380 @@
+use super::*;
+
+use core::fmt::{Debug, Formatter};
+
+//the highest order that will be checked when computing the order of a tableau
+pub const MAX_ORDER: usize = 10;
+
+const TOLERANCE: f64 = 1e-10;
+
+//a rooted tree, stored as the (sorted) list of subtrees attached to the root
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RootedTree(Vec<RootedTree>);
+
+impl Debug for RootedTree {
+    fn fmt(&self, f: &mut Formatter) -> ::core::fmt::Result {
+        //Butcher's bracket notation: τ is the single vertex and [t1 t2 ...] grafts t1, t2, ... onto a new root
+        if self.0.is_empty() {
+            write!(f, "τ")
+        } else {
+            write!(f, "[")?;
+            for t in self.0.iter() {
+                write!(f, "{:?}", t)?;
+            }
+            write!(f, "]")
+        }
+    }
+}
+
+impl RootedTree {
+    pub fn leaf() -> Self {
+        RootedTree(Vec::new())
+    }
+
+    pub fn graft(mut children: Vec<RootedTree>) -> Self {
+        children.sort();
+        RootedTree(children)
+    }
+
+    pub fn children(&self) -> &[RootedTree] {
+        &self.0
+    }
+
+    //the number of vertices
+    pub fn order(&self) -> usize {
+        1 + self.0.iter().map(|t| t.order()).sum::<usize>()
+    }
+
+    //γ(t), so that the order condition for t is Φ(t) = 1/γ(t)
+    pub fn density(&self) -> usize {
+        self.order() * self.0.iter().map(|t| t.density()).product::<usize>()
+    }
+
+    //σ(t), the order of the automorphism group of t
+    pub fn symmetry(&self) -> usize {
+        let mut sigma = 1;
+        let mut i = 0;
+        while i < self.0.len() {
+            let n = self.0[i..].iter().take_while(|t| **t == self.0[i]).count();
+            sigma *= (1..=n).product::<usize>() * self.0[i].symmetry().pow(n as u32);
+            i += n;
+        }
+        sigma
+    }
+}
+
+//builds all trees with `n` vertices, given all trees with fewer vertices sorted by order
+fn next_order(all: &[RootedTree], n: usize) -> Vec<RootedTree> {
+    //adds every multiset of trees from `all` (taken in non-increasing index to avoid repeats)
+    //with the given total order to `out`
+    fn forests(
+        all: &[RootedTree],
+        remaining: usize,
+        max_index: usize,
+        current: &mut Vec<RootedTree>,
+        out: &mut Vec<RootedTree>,
+    ) {
+        if remaining == 0 {
+            out.push(RootedTree::graft(current.clone()));
+            return;
+        }
+        for i in (0..max_index).rev() {
+            let order = all[i].order();
+            if order <= remaining {
+                current.push(all[i].clone());
+                forests(all, remaining - order, i + 1, current, out);
+                current.pop();
+            }
+        }
+    }
+
+    let mut trees = Vec::new();
+    forests(all, n - 1, all.len(), &mut Vec::new(), &mut trees);
+    trees.sort();
+    trees
+}
+
+//all rooted trees with each number of vertices from 1 up to and including `max_order`
+pub fn trees_up_to(max_order: usize) -> Vec<Vec<RootedTree>> {
+    let mut by_order: Vec<Vec<RootedTree>> = Vec::with_capacity(max_order);
+    let mut all: Vec<RootedTree> = Vec::new();
+    for n in 1..=max_order {
+        let trees = next_order(&all, n);
+        all.extend(trees.iter().cloned());
+        by_order.push(trees);
+    }
+    by_order
+}
+
+//all rooted trees with exactly `order` vertices
+pub fn trees(order: usize) -> Vec<RootedTree> {
+    if order == 0 {
+        Vec::new()
+    } else {
+        trees_up_to(order).pop().unwrap()
+    }
+}
+
+//the arithmetic needed to evaluate elementary weights, either exactly or in floating point
+trait Weight: Copy {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn add(self, rhs: Self) -> Option<Self>;
+    fn mul(self, rhs: Self) -> Option<Self>;
+}
+
+impl Weight for f64 {
+    fn zero() -> Self {
+        0.0
+    }
+    fn one() -> Self {
+        1.0
+    }
+    fn add(self, rhs: Self) -> Option<Self> {
+        Some(self + rhs)
+    }
+    fn mul(self, rhs: Self) -> Option<Self> {
+        Some(self * rhs)
+    }
+}
+
+impl Weight for Rational {
+    fn zero() -> Self {
+        Rational::ZERO
+    }
+    fn one() -> Self {
+        Rational::ONE
+    }
+    fn add(self, rhs: Self) -> Option<Self> {
+        self.checked_add(rhs)
+    }
+    fn mul(self, rhs: Self) -> Option<Self> {
+        self.checked_mul(rhs)
+    }
+}
+
+//Φ_i(t) for each stage i, or None if a coefficient is missing or the arithmetic overflows
+fn internal_weights<K: Weight, A: Fn(usize, usize) -> Option<K>>(
+    stages: usize,
+    a: &A,
+    tree: &RootedTree,
+) -> Option<Vec<K>> {
+    let mut phi = vec![K::one(); stages];
+    for child in tree.children() {
+        let psi = internal_weights(stages, a, child)?;
+        for (i, phi_i) in phi.iter_mut().enumerate() {
+            let mut sum = K::zero();
+            for (j, &psi_j) in psi.iter().enumerate() {
+                sum = sum.add(a(i, j)?.mul(psi_j)?)?;
+            }
+            *phi_i = phi_i.mul(sum)?;
+        }
+    }
+    Some(phi)
+}
+
+fn weight_in<K: Weight, A: Fn(usize, usize) -> Option<K>, B: Fn(usize) -> Option<K>>(
+    stages: usize,
+    a: &A,
+    b: &B,
+    tree: &RootedTree,
+) -> Option<K> {
+    let phi = internal_weights(stages, a, tree)?;
+    let mut sum = K::zero();
+    for (i, phi_i) in phi.into_iter().enumerate() {
+        sum = sum.add(b(i)?.mul(phi_i)?)?;
+    }
+    Some(sum)
+}
+
+//the residual Φ(t) - 1/γ(t) of an order condition
+#[derive(Clone, Copy)]
+enum Residual {
+    Exact(Rational),
+    Approximate(f64),
+}
+
+impl Residual {
+    fn is_satisfied(self) -> bool {
+        match self {
+            Residual::Exact(r) => r == Rational::ZERO,
+            Residual::Approximate(r) => r.abs() <= TOLERANCE,
+        }
+    }
+    fn value(self) -> f64 {
+        match self {
+            Residual::Exact(r) => r.to_f64(),
+            Residual::Approximate(r) => r,
+        }
+    }
+}
+
+//computes the residual exactly if every coefficient is rational, only falling back
+//to floating point if they are not or if the exact arithmetic overflows
+fn residual_with<T: Tableau + ?Sized, B: Fn(usize) -> T::Coeff>(
+    tableau: &T,
+    tree: &RootedTree,
+    b: &B,
+) -> Residual {
+    let s = tableau.stages();
+    let exact = weight_in(
+        s,
+        &|i, j| tableau.a(i, j).to_rational(),
+        &|j| b(j).to_rational(),
+        tree,
+    )
+    .and_then(|phi| phi.checked_sub(Rational::new(1, tree.density() as i128)));
+    match exact {
+        Some(r) => Residual::Exact(r),
+        None => Residual::Approximate(weight_with(tableau, tree, b) - 1.0 / tree.density() as f64),
+    }
+}
+
+fn weight_with<T: Tableau + ?Sized, B: Fn(usize) -> T::Coeff>(
+    tableau: &T,
+    tree: &RootedTree,
+    b: &B,
+) -> f64 {
+    weight_in(
+        tableau.stages(),
+        &|i, j| Some(tableau.a(i, j).to_f64()),
+        &|j| Some(b(j).to_f64()),
+        tree,
+    )
+    .unwrap()
+}
+
+//the elementary weight Φ(t) of the tableau's main weights
+pub fn elementary_weight<T: Tableau + ?Sized>(tableau: &T, tree: &RootedTree) -> f64 {
+    weight_with(tableau, tree, &|j| tableau.b(j))
+}
+
+//the elementary weight Φ(t) of the tableau's embedded weights, if it has any
+pub fn embedded_elementary_weight<T: Tableau + ?Sized>(
+    tableau: &T,
+    tree: &RootedTree,
+) -> Option<f64> {
+    tableau.b_hat(0)?;
+    Some(weight_with(tableau, tree, &|j| tableau.b_hat(j).unwrap()))
+}
+
+//the elementary weight Φ(t) of the tableau's main weights in exact arithmetic,
+//if its coefficients are exact and the computation does not overflow
+pub fn exact_elementary_weight<T: Tableau + ?Sized>(
+    tableau: &T,
+    tree: &RootedTree,
+) -> Option<Rational> {
+    weight_in(
+        tableau.stages(),
+        &|i, j| tableau.a(i, j).to_rational(),
+        &|j| tableau.b(j).to_rational(),
+        tree,
+    )
+}
+
+fn order_with<T: Tableau + ?Sized, B: Fn(usize) -> T::Coeff>(tableau: &T, b: B) -> usize {
+    //only generate the trees of the next order once all of the lower ones are satisfied
+    let mut all: Vec<RootedTree> = Vec::new();
+    for n in 1..=MAX_ORDER {
+        let trees = next_order(&all, n);
+        let satisfied = trees
+            .iter()
+            .all(|t| residual_with(tableau, t, &b).is_satisfied());
+        if !satisfied {
+            return n - 1;
+        }
+        all.extend(trees);
+    }
+    MAX_ORDER
+}
+
+//the classical order of the method (capped at `MAX_ORDER`)
+//
+//the order conditions are checked exactly for tableaux with rational coefficients
+//and up to a small tolerance otherwise
+pub fn classical_order<T: Tableau + ?Sized>(tableau: &T) -> usize {
+    order_with(tableau, |j| tableau.b(j))
+}
+
+//the classical order of the method's embedded weights (capped at `MAX_ORDER`)
+pub fn embedded_order<T: Tableau + ?Sized>(tableau: &T) -> Option<usize> {
+    tableau.b_hat(0)?;
+    Some(order_with(tableau, |j| tableau.b_hat(j).unwrap()))
+}
+
+//the first tree of at most the given order whose order condition is not satisfied by the main weights
+pub fn violated_condition<T: Tableau + ?Sized>(
+    tableau: &T,
+    order: usize,
+) -> Option<(RootedTree, f64)> {
+    let b = |j| tableau.b(j);
+    trees_up_to(order).into_iter().flatten().find_map(|t| {
+        let residual = residual_with(tableau, &t, &b);
+        if residual.is_satisfied() {
+            None
+        } else {
+            Some((t, residual.value()))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_rooted_trees() {
+        let counts: Vec<usize> = trees_up_to(8).iter().map(|t| t.len()).collect();
+        assert_eq!(counts, [1, 1, 2, 4, 9, 20, 48, 115]);
+        assert!(trees(5).iter().all(|t| t.order() == 5));
+    }
+
+    #[test]
+    fn orders_of_built_in_tableaux() {
+        let fixed = [
+            (EULER, 1),
+            (MIDPOINT, 2),
+            (HEUN2, 2),
+            (RALSTON, 2),
+            (RK3, 3),
+            (HEUN3, 3),
+            (RK4, 4),
+            (RK_3_8, 4),
+        ];
+        for (tableau, order) in fixed {
+            assert_eq!(classical_order(&tableau), order, "{:?}", tableau);
+            assert_eq!(embedded_order(&tableau), None);
+        }
+
+        let adaptive = [
+            (EULER_HEUN, 2, 1),
+            (BOGACKI_SHAMPINE, 3, 2),
+            (RK_FELBERG, 5, 4),
+            (DORMAND_PRINCE, 5, 4),
+        ];
+        for (tableau, order, embedded) in adaptive {
+            assert_eq!(classical_order(&tableau), order, "{:?}", tableau);
+            assert_eq!(embedded_order(&tableau), Some(embedded), "{:?}", tableau);
+        }
+    }
+
+    #[test]
+    fn float_tableaux_agree_with_exact_ones() {
+        let dp = DORMAND_PRINCE;
+        let s = dp.stages();
+        let tableau = OwnedAdaptiveRungeKutta::new(
+            (0..s).map(|i| dp.c(i).to_f64()).collect(),
+            (0..s)
+                .map(|i| (0..s).map(|j| dp.a(i, j).to_f64()).collect())
+                .collect(),
+            (0..s).map(|j| dp.b(j).to_f64()).collect(),
+            (0..s).map(|j| dp.b_hat(j).unwrap().to_f64()).collect(),
+        )
+        .unwrap();
+        assert_eq!(classical_order(&tableau), 5);
+        assert_eq!(Tableau::order(&tableau), 5);
+        assert_eq!(embedded_order(&tableau), Some(4));
+        assert!(violated_condition(&tableau, 5).is_none());
+        assert_eq!(violated_condition(&tableau, 6).unwrap().0.order(), 6);
+    }
+}
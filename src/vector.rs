@@ -0,0 +1,337 @@
+use super::*;
+
+use core::ops::{Deref, DerefMut};
+use maths_traits::algebra::*;
+
+//A fixed-size state vector, stored inline
+//
+//Together with `DVector`, this implements `VectorSpace` over its components and
+//`InnerProductSpace` for real components, so that it works with every integrator and with
+//`InnerProductMetric` for the adaptive ones.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SVector<T, const N: usize>(pub [T; N]);
+
+//A state vector of any length, stored on the heap
+//
+//Since `zero()` can't know the length, missing components are treated as zero, so adding
+//vectors of different lengths pads the shorter one.
+#[cfg(feature = "alloc")]
+#[derive(PartialEq, Eq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DVector<T>(pub Vec<T>);
+
+//written out so that `clone_from` reuses the storage the vector already has
+#[cfg(feature = "alloc")]
+impl<T: Clone> Clone for DVector<T> {
+    fn clone(&self) -> Self {
+        DVector(self.0.clone())
+    }
+    fn clone_from(&mut self, source: &Self) {
+        self.0.clone_from(&source.0);
+    }
+}
+
+impl<T, const N: usize> SVector<T, N> {
+    pub fn new(components: [T; N]) -> Self {
+        SVector(components)
+    }
+}
+
+impl<T: Zero, const N: usize> SVector<T, N> {
+    pub fn zeros() -> Self {
+        SVector(::core::array::from_fn(|_| T::zero()))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> DVector<T> {
+    pub fn new(components: Vec<T>) -> Self {
+        DVector(components)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Zero> DVector<T> {
+    pub fn zeros(n: usize) -> Self {
+        DVector((0..n).map(|_| T::zero()).collect())
+    }
+}
+
+impl<T: Default, const N: usize> Default for SVector<T, N> {
+    fn default() -> Self {
+        SVector(::core::array::from_fn(|_| T::default()))
+    }
+}
+
+impl<T, const N: usize> From<[T; N]> for SVector<T, N> {
+    fn from(components: [T; N]) -> Self {
+        SVector(components)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> From<Vec<T>> for DVector<T> {
+    fn from(components: Vec<T>) -> Self {
+        DVector(components)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> FromIterator<T> for DVector<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        DVector(iter.into_iter().collect())
+    }
+}
+
+impl<T: AddAssign, const N: usize> AddAssign for SVector<T, N> {
+    fn add_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a += b;
+        }
+    }
+}
+
+impl<T: SubAssign, const N: usize> SubAssign for SVector<T, N> {
+    fn sub_assign(&mut self, rhs: Self) {
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a -= b;
+        }
+    }
+}
+
+impl<'a, T: Clone + AddAssign, const N: usize> AddAssign<&'a Self> for SVector<T, N> {
+    fn add_assign(&mut self, rhs: &'a Self) {
+        for (a, b) in self.0.iter_mut().zip(&rhs.0) {
+            *a += b.clone();
+        }
+    }
+}
+
+impl<'a, T: Clone + SubAssign, const N: usize> SubAssign<&'a Self> for SVector<T, N> {
+    fn sub_assign(&mut self, rhs: &'a Self) {
+        for (a, b) in self.0.iter_mut().zip(&rhs.0) {
+            *a -= b.clone();
+        }
+    }
+}
+
+impl<T: Zero + AddAssign, const N: usize> Zero for SVector<T, N> {
+    fn zero() -> Self {
+        Self::zeros()
+    }
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(T::is_zero)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Zero + AddAssign> AddAssign for DVector<T> {
+    fn add_assign(&mut self, rhs: Self) {
+        if self.0.len() < rhs.0.len() {
+            self.0.resize_with(rhs.0.len(), T::zero);
+        }
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a += b;
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Zero + SubAssign> SubAssign for DVector<T> {
+    fn sub_assign(&mut self, rhs: Self) {
+        if self.0.len() < rhs.0.len() {
+            self.0.resize_with(rhs.0.len(), T::zero);
+        }
+        for (a, b) in self.0.iter_mut().zip(rhs.0) {
+            *a -= b;
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T: Clone + Zero + AddAssign> AddAssign<&'a Self> for DVector<T> {
+    fn add_assign(&mut self, rhs: &'a Self) {
+        if self.0.len() < rhs.0.len() {
+            self.0.resize_with(rhs.0.len(), T::zero);
+        }
+        for (a, b) in self.0.iter_mut().zip(&rhs.0) {
+            *a += b.clone();
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T: Clone + Zero + SubAssign> SubAssign<&'a Self> for DVector<T> {
+    fn sub_assign(&mut self, rhs: &'a Self) {
+        if self.0.len() < rhs.0.len() {
+            self.0.resize_with(rhs.0.len(), T::zero);
+        }
+        for (a, b) in self.0.iter_mut().zip(&rhs.0) {
+            *a -= b.clone();
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Zero + AddAssign> Zero for DVector<T> {
+    fn zero() -> Self {
+        DVector(Vec::new())
+    }
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(T::is_zero)
+    }
+}
+
+//the operations that are the same for both kinds of vector
+macro_rules! impl_vector {
+    ($([$($gen:tt)*] $ty:ty where $bound:path),*) => {$(
+        impl<T: $bound + AddAssign, $($gen)*> Add for $ty {
+            type Output = Self;
+            fn add(mut self, rhs: Self) -> Self {
+                self += rhs;
+                self
+            }
+        }
+
+        impl<T: $bound + SubAssign, $($gen)*> Sub for $ty {
+            type Output = Self;
+            fn sub(mut self, rhs: Self) -> Self {
+                self -= rhs;
+                self
+            }
+        }
+
+        impl<T: Clone + Neg<Output = T>, $($gen)*> Neg for $ty {
+            type Output = Self;
+            fn neg(mut self) -> Self {
+                for a in self.0.iter_mut() {
+                    *a = -a.clone();
+                }
+                self
+            }
+        }
+
+        impl<T: Clone + MulAssign, $($gen)*> MulAssign<T> for $ty {
+            fn mul_assign(&mut self, rhs: T) {
+                for a in self.0.iter_mut() {
+                    *a *= rhs.clone();
+                }
+            }
+        }
+
+        impl<T: Clone + MulAssign, $($gen)*> Mul<T> for $ty {
+            type Output = Self;
+            fn mul(mut self, rhs: T) -> Self {
+                self *= rhs;
+                self
+            }
+        }
+
+        impl<T: Clone + DivAssign, $($gen)*> DivAssign<T> for $ty {
+            fn div_assign(&mut self, rhs: T) {
+                for a in self.0.iter_mut() {
+                    *a /= rhs.clone();
+                }
+            }
+        }
+
+        impl<T: Clone + DivAssign, $($gen)*> Div<T> for $ty {
+            type Output = Self;
+            fn div(mut self, rhs: T) -> Self {
+                self /= rhs;
+                self
+            }
+        }
+
+        impl<T, $($gen)*> AddAssociative for $ty {}
+        impl<T, $($gen)*> AddCommutative for $ty {}
+        impl<T, $($gen)*> Distributive<T> for $ty {}
+
+        //the standard dot product, where missing components of a `DVector` don't contribute
+        impl<T: Real, $($gen)*> InnerProductSpace<T> for $ty {
+            fn inner_product(self, rhs: Self) -> T {
+                self.0
+                    .into_iter()
+                    .zip(rhs.0)
+                    .fold(T::zero(), |sum, (a, b)| sum + a * b)
+            }
+        }
+
+        impl<T, $($gen)*> Deref for $ty {
+            type Target = [T];
+            fn deref(&self) -> &[T] {
+                &self.0
+            }
+        }
+
+        impl<T, $($gen)*> DerefMut for $ty {
+            fn deref_mut(&mut self) -> &mut [T] {
+                &mut self.0
+            }
+        }
+    )*};
+}
+
+impl_vector!([const N: usize] SVector<T, N> where Sized);
+#[cfg(feature = "alloc")]
+impl_vector!([] DVector<T> where Zero);
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use maths_traits::analysis::metric::InnerProductMetric;
+    use maths_traits::analysis::Metric;
+
+    fn v(x: &[f64]) -> DVector<f64> {
+        DVector::from(x.to_vec())
+    }
+
+    #[test]
+    fn shorter_vectors_are_padded_with_zeros() {
+        let mut a = v(&[1.0, 2.0]);
+        a += v(&[1.0, 1.0, 1.0]);
+        assert_eq!(a, v(&[2.0, 3.0, 1.0]));
+        a -= &v(&[0.0, 0.0, 0.0, 4.0]);
+        assert_eq!(a, v(&[2.0, 3.0, 1.0, -4.0]));
+
+        //a longer vector keeps the components that the other one doesn't have
+        a += &v(&[1.0]);
+        assert_eq!(a, v(&[3.0, 3.0, 1.0, -4.0]));
+        a -= v(&[]);
+        assert_eq!(a, v(&[3.0, 3.0, 1.0, -4.0]));
+        assert_eq!(a.clone() - v(&[3.0]), v(&[0.0, 3.0, 1.0, -4.0]));
+        assert_eq!(v(&[1.0]) + a.clone(), v(&[4.0, 3.0, 1.0, -4.0]));
+    }
+
+    #[test]
+    fn zero_has_no_components() {
+        let zero = DVector::<f64>::zero();
+        assert!(zero.0.is_empty() && zero.is_zero());
+        assert!(v(&[0.0, 0.0]).is_zero());
+        assert!(!v(&[0.0, 1.0]).is_zero());
+
+        let a = v(&[1.0, -2.0]);
+        assert_eq!(zero.clone() - a.clone(), -a.clone());
+        assert_eq!(zero.clone() + a.clone(), a);
+        assert_eq!(a.clone() * 2.0 / 4.0, v(&[0.5, -1.0]));
+    }
+
+    #[test]
+    fn missing_components_dont_contribute_to_inner_products() {
+        assert_eq!(v(&[1.0, 2.0, 3.0]).inner_product(v(&[4.0, 5.0])), 14.0);
+        assert_eq!(v(&[1.0, 2.0]).inner_product(DVector::zero()), 0.0);
+        let d = InnerProductMetric.distance(v(&[3.0, 4.0]), DVector::zero());
+        assert_eq!(d, 5.0);
+        assert_eq!(SVector([1.0, 2.0]).inner_product(SVector([3.0, -1.0])), 1.0);
+    }
+
+    #[test]
+    fn static_vectors_combine_componentwise() {
+        let mut a = SVector([1.0, 2.0, 3.0]);
+        a += &SVector([1.0, 1.0, 1.0]);
+        a -= SVector([0.5, 0.0, 4.0]);
+        assert_eq!(a, SVector([1.5, 3.0, 0.0]));
+        assert_eq!(-a * 2.0, SVector([-3.0, -6.0, 0.0]));
+        assert!(SVector::<f64, 3>::zero().is_zero());
+    }
+}
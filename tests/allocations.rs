@@ -0,0 +1,50 @@
+#![cfg(feature = "std")]
+
+use numerical_integration::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+//counts the allocations made on threads that ask for it, so that tests running in parallel
+//don't get in the way
+thread_local! {
+    static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get().map(|n| n + 1)));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations<F: FnOnce()>(f: F) -> usize {
+    ALLOCATIONS.with(|n| n.set(Some(0)));
+    f();
+    ALLOCATIONS.with(|n| n.replace(None)).unwrap()
+}
+
+#[test]
+fn runge_kutta_steps_in_place_without_allocating() {
+    let force = InPlace(|_t: f64, y: &DVector<f64>, dy: &mut DVector<f64>| {
+        dy.clone_from(y);
+    });
+    let mut buffer = Integrator::init(&RK4, DVector::from(vec![1.0, 2.0]), 0.1, force);
+    let allocations = allocations(|| {
+        for i in 0..10 {
+            Integrator::step_in_place(&RK4, i as f64 * 0.1, &mut buffer, 0.1, force);
+        }
+    });
+    assert_eq!(allocations, 0);
+
+    let y = &buffer[0].1;
+    assert!((y[0] - 1.0f64.exp()).abs() < 1e-5);
+    assert!((y[1] - 2.0 * 1.0f64.exp()).abs() < 1e-5);
+}